@@ -0,0 +1,394 @@
+use ggez::{GameResult, Context};
+use ggez::graphics::*;
+use glam::f32::Vec2;
+use std::collections::HashSet;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use bindings::{Bindings, Command};
+use layout;
+use locale::Locale;
+use palette::Palette;
+use life::Stats;
+use text;
+
+
+pub const SCREEN_TOP:     f32 = 90.0;
+pub const SCREEN_SPACING: f32 = 36.0;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    // shown one at a time, in order
+    Tutorial,
+    // optional, unlocked in any order
+    Achievement,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+    GreaterOrEqual,
+    Greater,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operand {
+    Field(String),
+    Number(f32),
+}
+
+// e.g. "leaves >= 2" or "branches < branches_max"
+#[derive(Clone, Debug, PartialEq)]
+pub struct Condition {
+    pub field: String,
+    pub comparison: Comparison,
+    pub operand: Operand,
+}
+
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub id: String,
+    pub kind: Kind,
+    pub name: String,
+    pub message: String,
+    pub conditions: Vec<Condition>,
+    pub achieved: bool,
+}
+
+// The tutorial and the achievements, loaded from resources/achievements.txt,
+// and which of them have been achieved, loaded from the user data directory.
+#[derive(Debug)]
+pub struct Achievements {
+    path: PathBuf,
+    pub tutorial: Vec<Entry>,
+    pub achievements: Vec<Entry>,
+}
+
+// The names which conditions may use, see resources/achievements.txt.
+pub fn field_value(stats: &Stats, field: &str) -> Option<f32> {
+    let value = match field {
+        "leaves"         => stats.leaf_count,
+        "flowers"        => stats.flower_count,
+        "beehives"       => stats.beehive_count,
+        "berries"        => stats.berry_count,
+        "nuts"           => stats.nut_count,
+        "bird_nests"     => stats.birdnest_count,
+        "squirrels"      => stats.squirrel_count,
+        "moss"           => stats.moss_count,
+        "branches"       => stats.branch_lv1_count + stats.branch_lv2_count,
+        "thin_branches"  => stats.branch_lv1_count,
+        "thick_branches" => stats.branch_lv2_count,
+        "branches_max"   => stats.branches_max,
+        "branch_length3" => stats.branch_length3_count,
+        "branch_length4" => stats.branch_length4_count,
+        "branch_length5" => stats.branch_length5_count,
+        "life_max"       => stats.life_max,
+        "d_pressed"      => stats.d_pressed as usize,
+        "moss_added"     => stats.moss_added as usize,
+        "moss_removed"   => stats.moss_removed as usize,
+        _                => return None,
+    };
+    Some(value as f32)
+}
+
+impl Comparison {
+    fn parse(s: &str) -> Option<Comparison> {
+        match s {
+            "<"  => Some(Comparison::Less),
+            "<=" => Some(Comparison::LessOrEqual),
+            "==" => Some(Comparison::Equal),
+            "!=" => Some(Comparison::NotEqual),
+            ">=" => Some(Comparison::GreaterOrEqual),
+            ">"  => Some(Comparison::Greater),
+            _    => None,
+        }
+    }
+
+    fn holds(self, lhs: f32, rhs: f32) -> bool {
+        match self {
+            Comparison::Less           => lhs < rhs,
+            Comparison::LessOrEqual    => lhs <= rhs,
+            Comparison::Equal          => lhs == rhs,
+            Comparison::NotEqual       => lhs != rhs,
+            Comparison::GreaterOrEqual => lhs >= rhs,
+            Comparison::Greater        => lhs > rhs,
+        }
+    }
+}
+
+impl Condition {
+    // Checks the field names up front, so that a typo in the data file is
+    // reported when it is loaded rather than silently never unlocking.
+    pub fn parse(s: &str) -> Option<Condition> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        if words.len() != 3 {
+            return None;
+        }
+        let stats = Stats::default();
+        field_value(&stats, words[0])?;
+        let operand = match words[2].parse() {
+            Ok(number) => Operand::Number(number),
+            Err(_)     => {
+                field_value(&stats, words[2])?;
+                Operand::Field(words[2].to_string())
+            },
+        };
+        Some(Condition {
+            field: words[0].to_string(),
+            comparison: Comparison::parse(words[1])?,
+            operand,
+        })
+    }
+
+    pub fn holds(&self, stats: &Stats) -> bool {
+        let lhs = field_value(stats, &self.field).unwrap_or(0.0);
+        let rhs = match self.operand {
+            Operand::Number(number)    => number,
+            Operand::Field(ref field)  => field_value(stats, field).unwrap_or(0.0),
+        };
+        self.comparison.holds(lhs, rhs)
+    }
+}
+
+impl Entry {
+    pub fn holds(&self, stats: &Stats) -> bool {
+        self.conditions.iter().all(|condition| condition.holds(stats))
+    }
+
+    fn key(&self) -> String {
+        match self.kind {
+            Kind::Tutorial    => format!("tutorial.{}", self.id),
+            Kind::Achievement => format!("achievement.{}", self.id),
+        }
+    }
+
+    // The translations which the other languages must provide.
+    #[cfg(test)]
+    pub fn keys(&self) -> Vec<String> {
+        match self.kind {
+            Kind::Tutorial    => vec!(format!("{}.message", self.key())),
+            Kind::Achievement => vec!(format!("{}.name", self.key()), format!("{}.message", self.key())),
+        }
+    }
+
+    pub fn localized_name(&self, locale: &Locale) -> String {
+        locale.get_or(&format!("{}.name", self.key()), &self.name)
+    }
+
+    pub fn localized_message(&self, locale: &Locale) -> String {
+        locale.get_or(&format!("{}.message", self.key()), &self.message)
+    }
+}
+
+pub fn parse(contents: &str) -> Result<Vec<Entry>, String> {
+    let mut entries: Vec<Entry> = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = match line.find('#') {
+            Some(j) => &line[..j],
+            None    => line,
+        }.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (keyword, rest) = match line.find(' ') {
+            Some(j) => (&line[..j], line[j..].trim()),
+            None    => (line, ""),
+        };
+        let error = || format!("line {}: could not parse {:?}", i + 1, line);
+        let kind = match keyword {
+            "tutorial"    => Some(Kind::Tutorial),
+            "achievement" => Some(Kind::Achievement),
+            _             => None,
+        };
+        if let Some(kind) = kind {
+            entries.push(Entry {
+                id: rest.to_string(),
+                kind,
+                name: rest.to_string(),
+                message: String::new(),
+                conditions: Vec::new(),
+                achieved: false,
+            });
+            continue;
+        }
+        let entry = entries.last_mut().ok_or_else(error)?;
+        match keyword {
+            "name"    => entry.name = rest.to_string(),
+            "message" => entry.message = rest.to_string(),
+            "when"    => entry.conditions.push(Condition::parse(rest).ok_or_else(error)?),
+            _         => return Err(error()),
+        }
+    }
+    Ok(entries)
+}
+
+impl Achievements {
+    pub fn load(ctx: &mut Context, progress_path: &Path) -> Achievements {
+        let mut contents = String::new();
+        let entries = ggez::filesystem::open(ctx, "/achievements.txt")
+            .map_err(|e| e.to_string())
+            .and_then(|mut file| file.read_to_string(&mut contents).map_err(|e| e.to_string()))
+            .and_then(|_| parse(&contents))
+            .unwrap_or_else(|e| {
+                println!("could not load the achievements: {}", e);
+                Vec::new()
+            });
+
+        let achieved: HashSet<String> = fs::read_to_string(progress_path)
+            .map(|contents| contents.lines().map(|line| line.trim().to_string()).collect())
+            .unwrap_or_default();
+        let (tutorial, achievements): (Vec<Entry>, Vec<Entry>) = entries.into_iter()
+            .map(|mut entry| {
+                entry.achieved = achieved.contains(&entry.id);
+                entry
+            })
+            .partition(|entry| entry.kind == Kind::Tutorial);
+        Achievements {
+            path: progress_path.to_path_buf(),
+            tutorial,
+            achievements,
+        }
+    }
+
+    pub fn current_tutorial(&self) -> Option<&Entry> {
+        self.tutorial.iter().find(|entry| !entry.achieved)
+    }
+
+    // Returns the achievements which were just unlocked.
+    pub fn update(&mut self, stats: &Stats) -> Vec<Entry> {
+        let mut changed = false;
+
+        // don't mark a tutorial step when its hint was never displayed yet
+        if let Some(entry) = self.tutorial.iter_mut().find(|entry| !entry.achieved) {
+            if entry.holds(stats) {
+                entry.achieved = true;
+                changed = true;
+            }
+        }
+
+        let mut unlocked = Vec::new();
+        for entry in self.achievements.iter_mut() {
+            if !entry.achieved && entry.holds(stats) {
+                entry.achieved = true;
+                unlocked.push(entry.clone());
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.save();
+        }
+        unlocked
+    }
+
+    fn save(&self) {
+        let contents: String = self.tutorial.iter()
+            .chain(self.achievements.iter())
+            .filter(|entry| entry.achieved)
+            .map(|entry| format!("{}\n", entry.id))
+            .collect();
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).unwrap_or(());
+        }
+        if let Err(e) = fs::write(&self.path, contents) {
+            println!("could not save the achievements to {:?}: {}", self.path, e);
+        }
+    }
+
+    pub fn draw_screen(&self, ctx: &mut Context, font: Font, palette: &Palette, locale: &Locale, bindings: &Bindings) -> GameResult<()> {
+        let screen = layout::screen_size(ctx);
+        Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(0.0, 0.0, screen.x, screen.y),
+            Color::new(0.0, 0.0, 0.0, 0.8)
+        )?.draw(ctx, DrawParam::default())?;
+
+        let unlocked_count = self.achievements.iter().filter(|entry| entry.achieved).count();
+        text::draw_centered_message(
+            ctx,
+            font,
+            &locale.format("achievements.title", &[&unlocked_count, &self.achievements.len()]),
+            Vec2::new(screen.x / 2.0, 50.0),
+            palette.accent,
+        )?;
+        for (i, entry) in self.achievements.iter().enumerate() {
+            let y = SCREEN_TOP + i as f32 * SCREEN_SPACING;
+            let (name, color) = if entry.achieved {
+                (entry.localized_name(locale), palette.accent)
+            } else {
+                (locale.format("achievements.locked", &[&entry.localized_name(locale)]), palette.disabled)
+            };
+            text::draw_centered_message(ctx, font, &name, Vec2::new(screen.x / 2.0, y), color)?;
+            text::draw_centered_message(ctx, font, &entry.localized_message(locale), Vec2::new(screen.x / 2.0, y + 15.0), palette.text)?;
+        }
+        text::draw_centered_message(
+            ctx,
+            font,
+            &locale.format("achievements.help", &[&bindings.describe(locale, Command::Achievements), &bindings.describe(locale, Command::Back)]),
+            Vec2::new(screen.x / 2.0, screen.y - 20.0),
+            palette.help,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn stats(leaves: usize, branches_max: usize) -> Stats {
+        Stats {
+            leaf_count: leaves,
+            branches_max,
+            ..Stats::default()
+        }
+    }
+
+    #[test]
+    fn the_shipped_entries_parse() {
+        let entries = parse(&fs::read_to_string("resources/achievements.txt").unwrap()).unwrap();
+        assert!(entries.iter().any(|entry| entry.kind == Kind::Tutorial));
+        assert!(entries.iter().any(|entry| entry.kind == Kind::Achievement));
+        assert!(entries.iter().all(|entry| !entry.conditions.is_empty()), "every entry needs a condition");
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        assert_eq!(parse("message before any entry").err(), Some("line 1: could not parse \"message before any entry\"".to_string()));
+        // a typo in a field name
+        assert!(parse("achievement a\nwhen leafs > 2").is_err());
+        assert!(parse("achievement a\nwhen leaves >> 2").is_err());
+        assert!(parse("achievement a\nwhen leaves > 2 3").is_err());
+        assert!(parse("achievement a\ncolor green").is_err());
+    }
+
+    #[test]
+    fn conditions_compare_to_numbers_and_fields() {
+        let entries = parse("achievement a\nwhen leaves >= 2\nwhen leaves < branches_max").unwrap();
+        assert!(entries[0].holds(&stats(2, 3)));
+        assert!(!entries[0].holds(&stats(1, 3)));
+        assert!(!entries[0].holds(&stats(3, 3)));
+    }
+
+    #[test]
+    fn the_tutorial_advances_one_step_at_a_time() {
+        let entries = parse("tutorial first\nwhen leaves > 0\ntutorial second\nwhen leaves > 0\nachievement a\nwhen leaves > 1").unwrap();
+        let (tutorial, achievements) = entries.into_iter().partition(|entry| entry.kind == Kind::Tutorial);
+        let mut all = Achievements {
+            path: env::temp_dir().join("ludum-dare-44-test-achievements.txt"),
+            tutorial,
+            achievements,
+        };
+        assert_eq!(all.update(&stats(2, 0)).len(), 1);
+        assert_eq!(all.current_tutorial().map(|entry| entry.id.as_str()), Some("second"));
+        assert!(all.update(&stats(2, 0)).is_empty());
+        assert_eq!(all.current_tutorial().map(|entry| entry.id.as_str()), None);
+        fs::remove_file(&all.path).unwrap_or(());
+    }
+}
@@ -0,0 +1,280 @@
+use core::time::Duration;
+use ggez::{GameResult, Context};
+use ggez::graphics::*;
+use glam::f32::Vec2;
+use std::collections::VecDeque;
+
+use bindings::{Bindings, Command};
+use board::ActionError;
+use layout;
+use locale::Locale;
+use palette::Palette;
+use text;
+
+
+// how long each toast stays on screen
+pub const ALERT_DURATION: Duration = Duration::from_millis(2000);
+// how many rejected actions the log remembers
+pub const LOG_SIZE: usize = 200;
+pub const LOG_ROWS: usize = 20;
+pub const LOG_SPACING: f32 = 22.0;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    // only written to the log
+    Debug,
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlertMessage {
+    Rejected(ActionError),
+    ClickForBranch,
+    ClickForMoss,
+}
+
+#[derive(Clone, Debug)]
+pub struct Alert {
+    pub message: String,
+    pub severity: Severity,
+    // the hex which was clicked, if any
+    pub position: Option<Vec2>,
+    // how many times the same message was raised while it was waiting
+    pub count: usize,
+    pub until_time: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    pub turn: usize,
+    pub message: String,
+    pub severity: Severity,
+}
+
+// Toasts are shown one at a time, most severe first; repeated messages are
+// merged rather than queued again. Everything also goes to the log.
+#[derive(Debug, Default)]
+pub struct Alerts {
+    pub current: Option<Alert>,
+    pub queue: VecDeque<Alert>,
+    pub log: VecDeque<LogEntry>,
+    // how many entries from the most recent one the log is scrolled up
+    pub log_scroll: usize,
+}
+
+pub fn describe(locale: &Locale, alert_message: AlertMessage) -> String {
+    let key = match alert_message {
+        AlertMessage::Rejected(ActionError::NotEnoughBounty {need, have}) => {
+            return locale.format("alert.not_enough_bounty", &[&format!("{:.1}", need), &format!("{:.1}", have)]);
+        },
+        AlertMessage::Rejected(ActionError::TooManyThinBranches) => "alert.too_many_thin_branches",
+        AlertMessage::Rejected(ActionError::ParentTooThin)       => "alert.parent_too_thin",
+        AlertMessage::Rejected(ActionError::WouldFormCycle)      => "alert.would_form_cycle",
+        AlertMessage::Rejected(ActionError::NotAttached)         => "alert.not_attached",
+        AlertMessage::Rejected(ActionError::Occupied)            => "alert.occupied",
+        AlertMessage::Rejected(ActionError::MaxLevelReached)     => "alert.max_level_reached",
+        AlertMessage::Rejected(ActionError::NotATip)             => "alert.not_a_tip",
+        AlertMessage::Rejected(ActionError::NoBranch)            => "alert.no_branch",
+        AlertMessage::Rejected(ActionError::IsRoot)              => "alert.is_root",
+        AlertMessage::Rejected(ActionError::OutsideBoard)        => "alert.outside_board",
+        AlertMessage::Rejected(ActionError::NoRoom)              => "alert.no_room",
+        AlertMessage::Rejected(ActionError::ActionLocked)        => "alert.action_locked",
+        AlertMessage::ClickForBranch                             => "alert.click_for_branch",
+        AlertMessage::ClickForMoss                               => "alert.click_for_moss",
+    };
+    locale.get(key)
+}
+
+pub fn severity(alert_message: AlertMessage) -> Severity {
+    match alert_message {
+        AlertMessage::Rejected(ActionError::NotEnoughBounty {..})  => Severity::Warning,
+        AlertMessage::Rejected(ActionError::TooManyThinBranches)   => Severity::Warning,
+        AlertMessage::Rejected(ActionError::ParentTooThin)         => Severity::Warning,
+        AlertMessage::Rejected(ActionError::WouldFormCycle)        => Severity::Error,
+        AlertMessage::Rejected(ActionError::NotAttached)           => Severity::Info,
+        AlertMessage::Rejected(ActionError::Occupied)              => Severity::Debug,
+        AlertMessage::Rejected(ActionError::MaxLevelReached)       => Severity::Error,
+        AlertMessage::Rejected(ActionError::NotATip)               => Severity::Debug,
+        AlertMessage::Rejected(ActionError::NoBranch)              => Severity::Debug,
+        AlertMessage::Rejected(ActionError::IsRoot)                => Severity::Debug,
+        AlertMessage::Rejected(ActionError::OutsideBoard)          => Severity::Debug,
+        AlertMessage::Rejected(ActionError::NoRoom)                => Severity::Debug,
+        AlertMessage::Rejected(ActionError::ActionLocked)          => Severity::Error,
+        AlertMessage::ClickForBranch                               => Severity::Info,
+        AlertMessage::ClickForMoss                                 => Severity::Info,
+    }
+}
+
+fn severity_color(palette: &Palette, severity: Severity) -> Color {
+    match severity {
+        Severity::Debug   => palette.debug,
+        Severity::Info    => palette.text,
+        Severity::Warning => palette.warning,
+        Severity::Error   => palette.error,
+    }
+}
+
+// so that the severity doesn't depend on colour alone
+fn severity_marker(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Debug   => "",
+        Severity::Info    => "",
+        Severity::Warning => "[!] ",
+        Severity::Error   => "[X] ",
+    }
+}
+
+impl Alerts {
+    pub fn new() -> Alerts {
+        Alerts::default()
+    }
+
+    pub fn push_alert(&mut self, locale: &Locale, now: Duration, turn: usize, alert_message: AlertMessage, position: Option<Vec2>) {
+        self.push(now, turn, &describe(locale, alert_message), severity(alert_message), position);
+    }
+
+    pub fn push(&mut self, now: Duration, turn: usize, message: &str, severity: Severity, position: Option<Vec2>) {
+        self.log.push_back(LogEntry {
+            turn,
+            message: message.to_string(),
+            severity,
+        });
+        while self.log.len() > LOG_SIZE {
+            self.log.pop_front();
+        }
+        if severity == Severity::Debug {
+            return;
+        }
+
+        // the same message again: keep it up a bit longer instead of queueing it
+        if let Some(ref mut current) = self.current {
+            if current.message == message {
+                current.count += 1;
+                current.position = position;
+                current.until_time = now + ALERT_DURATION;
+                return;
+            }
+        }
+        if let Some(queued) = self.queue.iter_mut().find(|alert| alert.message == message) {
+            queued.count += 1;
+            queued.position = position;
+            return;
+        }
+
+        let alert = Alert {
+            message: message.to_string(),
+            severity,
+            position,
+            count: 1,
+            until_time: now + ALERT_DURATION,
+        };
+        let i = self.queue.iter()
+            .position(|queued| queued.severity < severity)
+            .unwrap_or(self.queue.len());
+        self.queue.insert(i, alert);
+        self.update(now);
+    }
+
+    pub fn update(&mut self, now: Duration) {
+        let expired = match self.current {
+            Some(ref current) => current.until_time < now,
+            None              => true,
+        };
+        if expired {
+            self.current = self.queue.pop_front().map(|mut alert| {
+                alert.until_time = now + ALERT_DURATION;
+                alert
+            });
+        }
+    }
+
+    pub fn scroll(&mut self, rows: isize) {
+        let max_scroll = self.log.len().saturating_sub(LOG_ROWS);
+        self.log_scroll = (self.log_scroll as isize + rows).max(0).min(max_scroll as isize) as usize;
+    }
+
+    // Returns whether a toast was drawn at the bottom of the screen, in
+    // which case nothing else should be drawn there.
+    pub fn draw(&self, ctx: &mut Context, font: Font, palette: &Palette) -> GameResult<bool> {
+        let screen = layout::screen_size(ctx);
+        let current = match self.current {
+            Some(ref current) => current,
+            None              => return Ok(false),
+        };
+        let message = if current.count > 1 {
+            format!("{}{} (x{})", severity_marker(current.severity), current.message, current.count)
+        } else {
+            format!("{}{}", severity_marker(current.severity), current.message)
+        };
+        let text = Text::new(
+            TextFragment::new(message.as_str())
+                .font(font)
+        );
+        let width = text.width(ctx) as f32;
+        match current.position {
+            Some(position) => {
+                // above the hex, but kept on screen
+                let center = Vec2::new(
+                    position.x.max(width / 2.0 + 10.0).min(screen.x - width / 2.0 - 10.0),
+                    (position.y - 30.0).max(45.0),
+                );
+                Mesh::new_rectangle(
+                    ctx,
+                    DrawMode::fill(),
+                    Rect::new(center.x - width / 2.0 - 6.0, center.y - 11.0, width + 12.0, 22.0),
+                    Color::new(0.0, 0.0, 0.0, 0.75)
+                )?.draw(ctx, DrawParam::default())?;
+                text::draw_centered_text(ctx, &text, center, 0.0, DrawParam::default().color(severity_color(palette, current.severity)))?;
+                Ok(false)
+            },
+            None => {
+                let center = Vec2::new(screen.x / 2.0, screen.y - 20.0);
+                text::draw_centered_text(ctx, &text, center, 0.0, DrawParam::default().color(severity_color(palette, current.severity)))?;
+                Ok(true)
+            },
+        }
+    }
+
+    pub fn draw_log(&self, ctx: &mut Context, font: Font, palette: &Palette, locale: &Locale, bindings: &Bindings) -> GameResult<()> {
+        let screen = layout::screen_size(ctx);
+        Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(0.0, 0.0, screen.x, screen.y),
+            Color::new(0.0, 0.0, 0.0, 0.8)
+        )?.draw(ctx, DrawParam::default())?;
+        text::draw_centered_message(ctx, font, &locale.get("log.title"), Vec2::new(screen.x / 2.0, 30.0), palette.accent)?;
+
+        // most recent at the bottom
+        let end = self.log.len() - self.log_scroll.min(self.log.len());
+        let start = end.saturating_sub(LOG_ROWS);
+        for (row, entry) in self.log.iter().skip(start).take(end - start).enumerate() {
+            let line = Text::new(
+                TextFragment::new(locale.format("log.entry", &[&entry.turn, &format!("{}{}", severity_marker(entry.severity), entry.message)]))
+                    .font(font)
+            );
+            line.draw(
+                ctx,
+                DrawParam::default()
+                    .dest(Vec2::new(40.0, 60.0 + row as f32 * LOG_SPACING))
+                    .color(severity_color(palette, entry.severity))
+            )?;
+        }
+        if self.log.is_empty() {
+            text::draw_centered_message(ctx, font, &locale.get("log.empty"), Vec2::new(screen.x / 2.0, screen.y / 2.0), palette.disabled)?;
+        }
+        let keys: Vec<String> = [Command::LogUp, Command::LogDown, Command::Log, Command::Back].iter()
+            .map(|&command| bindings.describe(locale, command))
+            .collect();
+        text::draw_centered_message(
+            ctx,
+            font,
+            &locale.format("log.help", &[&keys[0], &keys[1], &keys[2], &keys[3]]),
+            Vec2::new(screen.x / 2.0, screen.y - 20.0),
+            palette.help,
+        )
+    }
+}
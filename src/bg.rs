@@ -1,5 +1,8 @@
 use ggez::{GameResult, Context};
 use ggez::graphics::{Drawable, DrawParam, Image};
+use glam::f32::Vec2;
+
+use layout;
 
 
 #[derive(Debug)]
@@ -15,6 +18,18 @@ pub fn load_assets(ctx: &mut Context) -> GameResult<Assets> {
     )
 }
 
+// Covers the whole screen, cropping the sides or the top rather than
+// stretching the picture.
 pub fn draw_bg(ctx: &mut Context, assets: &Assets) -> GameResult<()> {
-    assets.bg.draw(ctx, DrawParam::default())
+    let screen = layout::screen_size(ctx);
+    let scale = (screen.x / assets.bg.width() as f32).max(screen.y / assets.bg.height() as f32);
+    assets.bg.draw(
+        ctx,
+        DrawParam::default()
+            .dest(Vec2::new(
+                (screen.x - assets.bg.width() as f32 * scale) / 2.0,
+                screen.y - assets.bg.height() as f32 * scale,
+            ))
+            .scale(Vec2::new(scale, scale))
+    )
 }
@@ -0,0 +1,761 @@
+use ggez::{GameResult, Context};
+use ggez::event::{Button, MouseButton};
+use ggez::graphics::*;
+use ggez::input::keyboard::{KeyCode, KeyMods};
+use glam::f32::Vec2;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use layout;
+use locale::Locale;
+use palette::Palette;
+use text;
+
+
+pub const SCREEN_TOP:     f32 = 70.0;
+pub const SCREEN_SPACING: f32 = 16.0;
+// as many as fit above the help line; the list scrolls past them
+pub const SCREEN_ROWS:    usize = 31;
+
+
+// Everything the player can bind to a key or a mouse button.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    // these apply to the hex under the mouse
+    Grow,
+    PruneOrMoss,
+    Prune,
+    Moss,
+    // these move the hex cursor
+    CursorUp,
+    CursorDown,
+    CursorLeft,
+    CursorRight,
+    // these pick a tool from the build menu
+    ToolBranch,
+    ToolUpgrade,
+    ToolPrune,
+    ToolMoss,
+    // these are global
+    FreeBuild,
+    Reset,
+    Quit,
+    Autoplay,
+    NextRace,
+    Levels,
+    NextLevel,
+    Achievements,
+    Log,
+    Sandbox,
+    ExportLevel,
+    Select,
+    Daily,
+    Bindings,
+    Palette,
+    Language,
+    Fullscreen,
+    Settings,
+    Screenshot,
+    Stats,
+    // these apply on the screens over the board
+    Confirm,
+    Back,
+    LogUp,
+    LogDown,
+    LogPageUp,
+    LogPageDown,
+    ExportStats,
+    // these drive the sandbox's simulation
+    SandboxFreeze,
+    SandboxStep,
+    // these apply to the selected limb, in selection mode
+    SelectionUpgrade0,
+    SelectionUpgrade1,
+    SelectionUpgrade2,
+    SelectionUpgrade3,
+    SelectionPrune,
+    SelectionMoss,
+    SelectionCopy,
+    SelectionPaste,
+    SelectionRotate,
+    SelectionMirror,
+    SelectionConfirm,
+    SelectionCancel,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Input {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(Button),
+}
+
+// e.g. "shift+mouse_left"
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Binding {
+    pub input: Input,
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+}
+
+// The default bindings, overridden by bindings.txt in the user's config
+// directory, which the rebinding screen writes.
+#[derive(Debug)]
+pub struct Bindings {
+    path: PathBuf,
+    pub bindings: Vec<(Command, Binding)>,
+}
+
+impl Command {
+    pub fn all() -> Vec<Command> {
+        vec!(
+            Command::Grow,
+            Command::PruneOrMoss,
+            Command::Prune,
+            Command::Moss,
+            Command::CursorUp,
+            Command::CursorDown,
+            Command::CursorLeft,
+            Command::CursorRight,
+            Command::ToolBranch,
+            Command::ToolUpgrade,
+            Command::ToolPrune,
+            Command::ToolMoss,
+            Command::FreeBuild,
+            Command::Reset,
+            Command::Quit,
+            Command::Autoplay,
+            Command::NextRace,
+            Command::Levels,
+            Command::NextLevel,
+            Command::Achievements,
+            Command::Log,
+            Command::Sandbox,
+            Command::ExportLevel,
+            Command::Select,
+            Command::Daily,
+            Command::Bindings,
+            Command::Palette,
+            Command::Language,
+            Command::Fullscreen,
+            Command::Settings,
+            Command::Screenshot,
+            Command::Stats,
+            Command::Confirm,
+            Command::Back,
+            Command::LogUp,
+            Command::LogDown,
+            Command::LogPageUp,
+            Command::LogPageDown,
+            Command::ExportStats,
+            Command::SandboxFreeze,
+            Command::SandboxStep,
+            Command::SelectionUpgrade0,
+            Command::SelectionUpgrade1,
+            Command::SelectionUpgrade2,
+            Command::SelectionUpgrade3,
+            Command::SelectionPrune,
+            Command::SelectionMoss,
+            Command::SelectionCopy,
+            Command::SelectionPaste,
+            Command::SelectionRotate,
+            Command::SelectionMirror,
+            Command::SelectionConfirm,
+            Command::SelectionCancel,
+        )
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Command::Grow         => "grow",
+            Command::PruneOrMoss  => "prune_or_moss",
+            Command::Prune        => "prune",
+            Command::Moss         => "moss",
+            Command::CursorUp     => "cursor_up",
+            Command::CursorDown   => "cursor_down",
+            Command::CursorLeft   => "cursor_left",
+            Command::CursorRight  => "cursor_right",
+            Command::ToolBranch   => "tool_branch",
+            Command::ToolUpgrade  => "tool_upgrade",
+            Command::ToolPrune    => "tool_prune",
+            Command::ToolMoss     => "tool_moss",
+            Command::FreeBuild    => "free_build",
+            Command::Reset        => "reset",
+            Command::Quit         => "quit",
+            Command::Autoplay     => "autoplay",
+            Command::NextRace     => "next_race",
+            Command::Levels       => "levels",
+            Command::NextLevel    => "next_level",
+            Command::Achievements => "achievements",
+            Command::Log          => "log",
+            Command::Sandbox      => "sandbox",
+            Command::ExportLevel  => "export_level",
+            Command::Select       => "select",
+            Command::Daily        => "daily",
+            Command::Bindings     => "bindings",
+            Command::Palette      => "palette",
+            Command::Language     => "language",
+            Command::Fullscreen   => "fullscreen",
+            Command::Settings     => "settings",
+            Command::Screenshot   => "screenshot",
+            Command::Stats        => "stats",
+            Command::Confirm      => "confirm",
+            Command::Back         => "back",
+            Command::LogUp        => "log_up",
+            Command::LogDown      => "log_down",
+            Command::LogPageUp    => "log_page_up",
+            Command::LogPageDown  => "log_page_down",
+            Command::ExportStats  => "export_stats",
+            Command::SandboxFreeze => "sandbox_freeze",
+            Command::SandboxStep  => "sandbox_step",
+            Command::SelectionUpgrade0 => "selection_upgrade_0",
+            Command::SelectionUpgrade1 => "selection_upgrade_1",
+            Command::SelectionUpgrade2 => "selection_upgrade_2",
+            Command::SelectionUpgrade3 => "selection_upgrade_3",
+            Command::SelectionPrune    => "selection_prune",
+            Command::SelectionMoss     => "selection_moss",
+            Command::SelectionCopy     => "selection_copy",
+            Command::SelectionPaste    => "selection_paste",
+            Command::SelectionRotate   => "selection_rotate",
+            Command::SelectionMirror   => "selection_mirror",
+            Command::SelectionConfirm  => "selection_confirm",
+            Command::SelectionCancel   => "selection_cancel",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Command> {
+        Command::all().into_iter().find(|command| command.name() == name)
+    }
+
+    pub fn describe(self, locale: &Locale) -> String {
+        locale.get(&format!("command.{}", self.name()))
+    }
+
+    // the commands which apply to the hex under the mouse
+    pub fn targets_hex(self) -> bool {
+        matches!(self, Command::Grow | Command::PruneOrMoss | Command::Prune | Command::Moss)
+    }
+
+    // the commands which only mean something in selection mode
+    pub fn in_selection(self) -> bool {
+        matches!(
+            self,
+            Command::SelectionUpgrade0 | Command::SelectionUpgrade1 | Command::SelectionUpgrade2
+            | Command::SelectionUpgrade3 | Command::SelectionPrune | Command::SelectionMoss
+            | Command::SelectionCopy | Command::SelectionPaste | Command::SelectionRotate
+            | Command::SelectionMirror | Command::SelectionConfirm | Command::SelectionCancel
+        )
+    }
+
+    // the commands which only mean something on a screen over the board
+    pub fn on_screen(self) -> bool {
+        matches!(
+            self,
+            Command::Confirm | Command::Back | Command::LogUp | Command::LogDown
+            | Command::LogPageUp | Command::LogPageDown | Command::ExportStats
+        )
+    }
+
+    // the commands which only mean something in the sandbox
+    pub fn in_sandbox(self) -> bool {
+        matches!(self, Command::SandboxFreeze | Command::SandboxStep)
+    }
+
+    // destructive commands ask for confirmation first
+    pub fn needs_confirmation(self) -> bool {
+        matches!(self, Command::Reset | Command::Quit)
+    }
+}
+
+// The keys which can be bound, named as in ggez's KeyCode.
+fn bindable_keys() -> Vec<KeyCode> {
+    vec!(
+        KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G,
+        KeyCode::H, KeyCode::I, KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N,
+        KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R, KeyCode::S, KeyCode::T, KeyCode::U,
+        KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z,
+        KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+        KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+        KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6,
+        KeyCode::F7, KeyCode::F8, KeyCode::F9, KeyCode::F10, KeyCode::F11, KeyCode::F12,
+        KeyCode::Escape, KeyCode::Tab, KeyCode::Space, KeyCode::Return, KeyCode::Back,
+        KeyCode::Delete, KeyCode::Insert, KeyCode::Home, KeyCode::End,
+        KeyCode::PageUp, KeyCode::PageDown,
+        KeyCode::Up, KeyCode::Down, KeyCode::Left, KeyCode::Right,
+        KeyCode::Minus, KeyCode::Equals, KeyCode::Comma, KeyCode::Period, KeyCode::Slash,
+        KeyCode::Semicolon, KeyCode::LBracket, KeyCode::RBracket, KeyCode::Backslash,
+        KeyCode::Grave,
+    )
+}
+
+// Named "pad_" followed by gilrs' name, e.g. "pad_DPadUp".
+fn bindable_buttons() -> Vec<Button> {
+    vec!(
+        Button::South, Button::East, Button::North, Button::West,
+        Button::LeftTrigger, Button::LeftTrigger2, Button::RightTrigger, Button::RightTrigger2,
+        Button::Select, Button::Start, Button::Mode, Button::LeftThumb, Button::RightThumb,
+        Button::DPadUp, Button::DPadDown, Button::DPadLeft, Button::DPadRight,
+    )
+}
+
+fn is_modifier(keycode: KeyCode) -> bool {
+    matches!(
+        keycode,
+        KeyCode::LShift | KeyCode::RShift
+        | KeyCode::LControl | KeyCode::RControl
+        | KeyCode::LAlt | KeyCode::RAlt
+        | KeyCode::LWin | KeyCode::RWin
+    )
+}
+
+impl Input {
+    fn name(self) -> String {
+        match self {
+            Input::Key(keycode)                => format!("{:?}", keycode),
+            Input::Mouse(MouseButton::Left)    => "mouse_left".to_string(),
+            Input::Mouse(MouseButton::Right)   => "mouse_right".to_string(),
+            Input::Mouse(MouseButton::Middle)  => "mouse_middle".to_string(),
+            Input::Mouse(MouseButton::Other(i)) => format!("mouse_{}", i),
+            Input::Gamepad(button)             => format!("pad_{:?}", button),
+        }
+    }
+
+    fn parse(s: &str) -> Option<Input> {
+        match s {
+            "mouse_left"   => return Some(Input::Mouse(MouseButton::Left)),
+            "mouse_right"  => return Some(Input::Mouse(MouseButton::Right)),
+            "mouse_middle" => return Some(Input::Mouse(MouseButton::Middle)),
+            _              => (),
+        }
+        if let Some(name) = s.strip_prefix("pad_") {
+            return bindable_buttons()
+                .into_iter()
+                .find(|button| format!("{:?}", button).eq_ignore_ascii_case(name))
+                .map(Input::Gamepad);
+        }
+        if let Some(number) = s.strip_prefix("mouse_") {
+            return number.parse().ok().map(|i| Input::Mouse(MouseButton::Other(i)));
+        }
+        bindable_keys()
+            .into_iter()
+            .find(|keycode| format!("{:?}", keycode).eq_ignore_ascii_case(s))
+            .map(Input::Key)
+    }
+}
+
+impl Binding {
+    pub fn new(input: Input, keymod: KeyMods) -> Binding {
+        Binding {
+            input,
+            shift: keymod.contains(KeyMods::SHIFT),
+            ctrl: keymod.contains(KeyMods::CTRL),
+            alt: keymod.contains(KeyMods::ALT),
+        }
+    }
+
+    fn plain(input: Input) -> Binding {
+        Binding::new(input, KeyMods::NONE)
+    }
+
+    pub fn parse(s: &str) -> Option<Binding> {
+        let mut binding = Binding::plain(Input::Key(KeyCode::Escape));
+        let mut parts: Vec<&str> = s.split('+').map(|part| part.trim()).collect();
+        let input = parts.pop()?;
+        for part in parts {
+            match part.to_lowercase().as_str() {
+                "shift" => binding.shift = true,
+                "ctrl"  => binding.ctrl = true,
+                "alt"   => binding.alt = true,
+                _       => return None,
+            }
+        }
+        binding.input = Input::parse(input)?;
+        Some(binding)
+    }
+
+    fn has_modifiers(self) -> bool {
+        self.shift || self.ctrl || self.alt
+    }
+}
+
+// The same syntax as `Binding::parse`.
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.shift {
+            write!(f, "shift+")?;
+        }
+        if self.ctrl {
+            write!(f, "ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "alt+")?;
+        }
+        write!(f, "{}", self.input.name())
+    }
+}
+
+pub fn default_bindings() -> Vec<(Command, Binding)> {
+    let mouse = |button| Input::Mouse(button);
+    vec!(
+        (Command::Grow,         Binding::plain(mouse(MouseButton::Left))),
+        (Command::PruneOrMoss,  Binding::plain(mouse(MouseButton::Right))),
+        (Command::PruneOrMoss,  Binding::new(mouse(MouseButton::Left), KeyMods::CTRL)),
+        (Command::Prune,        Binding::new(mouse(MouseButton::Left), KeyMods::SHIFT)),
+        (Command::Moss,         Binding::new(mouse(MouseButton::Left), KeyMods::ALT)),
+        (Command::Grow,         Binding::plain(Input::Key(KeyCode::Z))),
+        (Command::PruneOrMoss,  Binding::plain(Input::Key(KeyCode::X))),
+        (Command::Grow,         Binding::plain(Input::Gamepad(Button::South))),
+        (Command::PruneOrMoss,  Binding::plain(Input::Gamepad(Button::East))),
+        (Command::CursorUp,     Binding::plain(Input::Key(KeyCode::Up))),
+        (Command::CursorDown,   Binding::plain(Input::Key(KeyCode::Down))),
+        (Command::CursorLeft,   Binding::plain(Input::Key(KeyCode::Left))),
+        (Command::CursorRight,  Binding::plain(Input::Key(KeyCode::Right))),
+        (Command::CursorUp,     Binding::plain(Input::Gamepad(Button::DPadUp))),
+        (Command::CursorDown,   Binding::plain(Input::Gamepad(Button::DPadDown))),
+        (Command::CursorLeft,   Binding::plain(Input::Gamepad(Button::DPadLeft))),
+        (Command::CursorRight,  Binding::plain(Input::Gamepad(Button::DPadRight))),
+        (Command::ToolBranch,   Binding::plain(Input::Key(KeyCode::Key1))),
+        (Command::ToolUpgrade,  Binding::plain(Input::Key(KeyCode::Key2))),
+        (Command::ToolPrune,    Binding::plain(Input::Key(KeyCode::Key3))),
+        (Command::ToolMoss,     Binding::plain(Input::Key(KeyCode::Key4))),
+        (Command::FreeBuild,    Binding::plain(Input::Key(KeyCode::D))),
+        (Command::Reset,        Binding::plain(Input::Key(KeyCode::R))),
+        (Command::Quit,         Binding::plain(Input::Key(KeyCode::Escape))),
+        (Command::Autoplay,     Binding::plain(Input::Key(KeyCode::A))),
+        (Command::NextRace,     Binding::plain(Input::Key(KeyCode::G))),
+        (Command::Levels,       Binding::plain(Input::Key(KeyCode::L))),
+        (Command::NextLevel,    Binding::plain(Input::Key(KeyCode::Return))),
+        (Command::Achievements, Binding::plain(Input::Key(KeyCode::Tab))),
+        (Command::Log,          Binding::plain(Input::Key(KeyCode::H))),
+        (Command::Sandbox,      Binding::plain(Input::Key(KeyCode::S))),
+        (Command::ExportLevel,  Binding::new(Input::Key(KeyCode::X), KeyMods::SHIFT)),
+        (Command::Select,       Binding::plain(Input::Key(KeyCode::K))),
+        (Command::Daily,        Binding::plain(Input::Key(KeyCode::C))),
+        (Command::Bindings,     Binding::plain(Input::Key(KeyCode::B))),
+        (Command::Palette,      Binding::plain(Input::Key(KeyCode::P))),
+        (Command::Language,     Binding::plain(Input::Key(KeyCode::F2))),
+        (Command::Fullscreen,   Binding::plain(Input::Key(KeyCode::F11))),
+        (Command::Settings,     Binding::plain(Input::Key(KeyCode::O))),
+        (Command::Settings,     Binding::plain(Input::Gamepad(Button::Start))),
+        (Command::Screenshot,   Binding::plain(Input::Key(KeyCode::F12))),
+        (Command::Stats,        Binding::plain(Input::Key(KeyCode::V))),
+        // these are only looked up where they apply, so they can share keys
+        // with the ones above
+        (Command::Confirm,      Binding::plain(Input::Key(KeyCode::Y))),
+        (Command::Confirm,      Binding::plain(Input::Gamepad(Button::South))),
+        (Command::Back,         Binding::plain(Input::Key(KeyCode::Escape))),
+        (Command::LogUp,        Binding::plain(Input::Key(KeyCode::Up))),
+        (Command::LogDown,      Binding::plain(Input::Key(KeyCode::Down))),
+        (Command::LogPageUp,    Binding::plain(Input::Key(KeyCode::PageUp))),
+        (Command::LogPageDown,  Binding::plain(Input::Key(KeyCode::PageDown))),
+        (Command::ExportStats,  Binding::plain(Input::Key(KeyCode::E))),
+        (Command::SandboxFreeze, Binding::plain(Input::Key(KeyCode::Space))),
+        (Command::SandboxStep,  Binding::plain(Input::Key(KeyCode::N))),
+        // away from the keys above, which still work in selection mode
+        (Command::SelectionUpgrade0, Binding::new(Input::Key(KeyCode::Key1), KeyMods::SHIFT)),
+        (Command::SelectionUpgrade1, Binding::new(Input::Key(KeyCode::Key2), KeyMods::SHIFT)),
+        (Command::SelectionUpgrade2, Binding::new(Input::Key(KeyCode::Key3), KeyMods::SHIFT)),
+        (Command::SelectionUpgrade3, Binding::new(Input::Key(KeyCode::Key4), KeyMods::SHIFT)),
+        (Command::SelectionPrune,    Binding::plain(Input::Key(KeyCode::Delete))),
+        (Command::SelectionMoss,     Binding::plain(Input::Key(KeyCode::M))),
+        (Command::SelectionCopy,     Binding::new(Input::Key(KeyCode::C), KeyMods::CTRL)),
+        (Command::SelectionPaste,    Binding::new(Input::Key(KeyCode::V), KeyMods::CTRL)),
+        (Command::SelectionRotate,   Binding::plain(Input::Key(KeyCode::T))),
+        (Command::SelectionMirror,   Binding::plain(Input::Key(KeyCode::F))),
+        (Command::SelectionConfirm,  Binding::plain(Input::Key(KeyCode::Space))),
+        (Command::SelectionCancel,   Binding::plain(Input::Key(KeyCode::Escape))),
+    )
+}
+
+// One "command binding" per line, e.g. "prune shift+mouse_left". A command
+// listed in the file loses its default bindings.
+pub fn parse(contents: &str, mut bindings: Vec<(Command, Binding)>) -> Vec<(Command, Binding)> {
+    let mut overridden: Vec<Command> = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = match line.find('#') {
+            Some(j) => &line[..j],
+            None    => line,
+        }.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let parsed = if words.len() == 2 {
+            Command::from_name(words[0]).and_then(|command| Binding::parse(words[1]).map(|binding| (command, binding)))
+        } else {
+            None
+        };
+        match parsed {
+            Some((command, binding)) => {
+                if !overridden.contains(&command) {
+                    overridden.push(command);
+                    bindings.retain(|&(other, _)| other != command);
+                }
+                bindings.push((command, binding));
+            },
+            None => println!("bindings, line {}: ignoring {:?}, expected e.g. \"prune shift+mouse_left\"", i + 1, line),
+        }
+    }
+    bindings
+}
+
+impl Bindings {
+    pub fn load(path: &Path) -> Bindings {
+        let bindings = match fs::read_to_string(path) {
+            Ok(contents) => parse(&contents, default_bindings()),
+            Err(_)       => default_bindings(),
+        };
+        Bindings {
+            path: path.to_path_buf(),
+            bindings,
+        }
+    }
+
+    pub fn save(&self) {
+        let mut contents = String::from("# command binding, see the key bindings screen (B)\n");
+        for command in Command::all() {
+            for binding in self.bindings_for(command) {
+                contents.push_str(&format!("{} {}\n", command.name(), binding));
+            }
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).unwrap_or(());
+        }
+        if let Err(e) = fs::write(&self.path, contents) {
+            println!("could not save the key bindings to {:?}: {}", self.path, e);
+        }
+    }
+
+    pub fn bindings_for(&self, command: Command) -> Vec<Binding> {
+        self.bindings.iter()
+            .filter(|&&(other, _)| other == command)
+            .map(|&(_, binding)| binding)
+            .collect()
+    }
+
+    // Among the commands which `filter` accepts, an exact match including the
+    // modifiers wins; otherwise a binding without modifiers, so that e.g.
+    // shift+right-click still prunes.
+    pub fn command_among(&self, input: Input, keymod: KeyMods, filter: impl Fn(Command) -> bool) -> Option<Command> {
+        let pressed = Binding::new(input, keymod);
+        let candidates = || self.bindings.iter().filter(|&&(command, _)| filter(command));
+        candidates()
+            .find(|&&(_, binding)| binding == pressed)
+            .or_else(|| candidates().find(|&&(_, binding)| binding.input == input && !binding.has_modifiers()))
+            .map(|&(command, _)| command)
+    }
+
+    // For commands which last while a key is held: whichever modifiers were
+    // pressed, releasing the key ends them.
+    pub fn is_bound_to(&self, input: Input, command: Command) -> bool {
+        self.bindings.iter().any(|&(other, binding)| other == command && binding.input == input)
+    }
+
+    pub fn rebind(&mut self, command: Command, binding: Binding) {
+        self.bindings.retain(|&(other, _)| other != command);
+        self.bindings.push((command, binding));
+        self.save();
+    }
+
+    pub fn restore_defaults(&mut self) {
+        self.bindings = default_bindings();
+        self.save();
+    }
+
+    pub fn describe(&self, locale: &Locale, command: Command) -> String {
+        let names: Vec<String> = self.bindings_for(command).iter().map(|binding| binding.to_string()).collect();
+        if names.is_empty() {
+            locale.get("bindings.unbound")
+        } else {
+            names.join(", ")
+        }
+    }
+}
+
+// The rebinding screen: the selected row, and whether the next key or click
+// becomes its new binding.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rebinding {
+    pub selected: usize,
+    pub waiting: bool,
+}
+
+impl Rebinding {
+    pub fn new() -> Rebinding {
+        Rebinding {
+            selected: 0,
+            waiting: false,
+        }
+    }
+
+    // Returns false once the player leaves the screen.
+    pub fn key_down(&mut self, bindings: &mut Bindings, keycode: KeyCode, keymod: KeyMods) -> bool {
+        let commands = Command::all();
+        if self.waiting {
+            if is_modifier(keycode) {
+                return true;
+            }
+            if keycode != KeyCode::Escape || keymod != KeyMods::NONE {
+                bindings.rebind(commands[self.selected], Binding::new(Input::Key(keycode), keymod));
+            }
+            self.waiting = false;
+            return true;
+        }
+        match keycode {
+            KeyCode::Up     => self.selected = (self.selected + commands.len() - 1) % commands.len(),
+            KeyCode::Down   => self.selected = (self.selected + 1) % commands.len(),
+            KeyCode::Return => self.waiting = true,
+            KeyCode::Back   => bindings.restore_defaults(),
+            KeyCode::Escape => return false,
+            _               => (),
+        }
+        true
+    }
+
+    // the row at the top of the screen, so that the selected one is shown
+    fn first_row(&self) -> usize {
+        (self.selected + 1).saturating_sub(SCREEN_ROWS)
+    }
+
+    pub fn mouse_down(&mut self, bindings: &mut Bindings, button: MouseButton, keymod: KeyMods, y: f32) {
+        let commands = Command::all();
+        if self.waiting {
+            bindings.rebind(commands[self.selected], Binding::new(Input::Mouse(button), keymod));
+            self.waiting = false;
+            return;
+        }
+        let row = ((y - SCREEN_TOP) / SCREEN_SPACING + 0.5).floor();
+        if row >= 0.0 && (row as usize) < SCREEN_ROWS && self.first_row() + (row as usize) < commands.len() {
+            self.selected = self.first_row() + row as usize;
+            self.waiting = true;
+        }
+    }
+
+    // Like `key_down`, with the d-pad to choose, South to rebind and East to
+    // go back.
+    pub fn button_down(&mut self, bindings: &mut Bindings, button: Button) -> bool {
+        let commands = Command::all();
+        if self.waiting {
+            bindings.rebind(commands[self.selected], Binding::plain(Input::Gamepad(button)));
+            self.waiting = false;
+            return true;
+        }
+        match button {
+            Button::DPadUp   => self.selected = (self.selected + commands.len() - 1) % commands.len(),
+            Button::DPadDown => self.selected = (self.selected + 1) % commands.len(),
+            Button::South    => self.waiting = true,
+            Button::East     => return false,
+            _                => (),
+        }
+        true
+    }
+
+    pub fn draw(&self, ctx: &mut Context, font: Font, palette: &Palette, locale: &Locale, bindings: &Bindings) -> GameResult<()> {
+        let screen = layout::screen_size(ctx);
+        Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(0.0, 0.0, screen.x, screen.y),
+            Color::new(0.0, 0.0, 0.0, 0.8)
+        )?.draw(ctx, DrawParam::default())?;
+        text::draw_centered_message(ctx, font, &locale.get("bindings.title"), Vec2::new(screen.x / 2.0, 40.0), palette.accent)?;
+
+        for (i, &command) in Command::all().iter().enumerate().skip(self.first_row()).take(SCREEN_ROWS) {
+            let y = SCREEN_TOP + (i - self.first_row()) as f32 * SCREEN_SPACING;
+            let selected = i == self.selected;
+            let color = if selected {
+                palette.accent
+            } else {
+                palette.text
+            };
+            let binding = if selected && self.waiting {
+                locale.get("bindings.waiting")
+            } else {
+                bindings.describe(locale, command)
+            };
+            text::draw_centered_message(ctx, font, &command.describe(locale), Vec2::new(screen.x / 2.0 - 150.0, y), color)?;
+            text::draw_centered_message(ctx, font, &binding, Vec2::new(screen.x / 2.0 + 150.0, y), color)?;
+        }
+
+        text::draw_centered_message(
+            ctx,
+            font,
+            &locale.get("bindings.help"),
+            Vec2::new(screen.x / 2.0, screen.y - 20.0),
+            palette.help,
+        )
+    }
+}
+
+// The overlay asking to confirm a destructive command.
+pub fn draw_confirmation(ctx: &mut Context, font: Font, palette: &Palette, locale: &Locale, command: Command, bindings: &Bindings) -> GameResult<()> {
+    let screen = layout::screen_size(ctx);
+    let center = Vec2::new(screen.x / 2.0, screen.y / 2.0);
+    Mesh::new_rectangle(
+        ctx,
+        DrawMode::fill(),
+        Rect::new(center.x - 200.0, center.y - 40.0, 400.0, 80.0),
+        Color::new(0.0, 0.0, 0.0, 0.8)
+    )?.draw(ctx, DrawParam::default())?;
+    text::draw_centered_message(ctx, font, &locale.format("confirm.title", &[&command.describe(locale)]), center + Vec2::new(0.0, -15.0), palette.accent)?;
+    text::draw_centered_message(
+        ctx,
+        font,
+        &locale.format("confirm.help", &[&bindings.describe(locale, Command::Confirm), &bindings.describe(locale, command)]),
+        center + Vec2::new(0.0, 15.0),
+        palette.text,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> Bindings {
+        Bindings {
+            path: PathBuf::new(),
+            bindings: default_bindings(),
+        }
+    }
+
+    #[test]
+    fn screen_commands_only_apply_on_their_screens() {
+        let bindings = defaults();
+        let up = Input::Key(KeyCode::Up);
+        let global = |command: Command| !command.in_selection() && !command.on_screen() && !command.in_sandbox();
+        assert_eq!(bindings.command_among(up, KeyMods::NONE, global), Some(Command::CursorUp));
+        assert_eq!(bindings.command_among(up, KeyMods::NONE, Command::on_screen), Some(Command::LogUp));
+        let space = Input::Key(KeyCode::Space);
+        assert_eq!(bindings.command_among(space, KeyMods::NONE, global), None);
+        assert_eq!(bindings.command_among(space, KeyMods::NONE, Command::in_sandbox), Some(Command::SandboxFreeze));
+    }
+
+    #[test]
+    fn modifiers_fall_back_to_the_plain_binding() {
+        let bindings = defaults();
+        let left = Input::Mouse(MouseButton::Left);
+        assert_eq!(bindings.command_among(left, KeyMods::SHIFT, |_| true), Some(Command::Prune));
+        assert_eq!(bindings.command_among(left, KeyMods::SHIFT | KeyMods::CTRL, |_| true), Some(Command::Grow));
+    }
+
+    #[test]
+    fn a_listed_command_loses_its_defaults() {
+        let bindings = parse("# comment\nlog_up shift+w\nlog_up pad_dpadup\nnonsense\nquit ctrl+zz\n", default_bindings());
+        let log_up: Vec<String> = bindings.iter()
+            .filter(|&&(command, _)| command == Command::LogUp)
+            .map(|(_, binding)| binding.to_string())
+            .collect();
+        assert_eq!(log_up, vec!("shift+W", "pad_DPadUp"));
+        assert!(bindings.contains(&(Command::Quit, Binding::plain(Input::Key(KeyCode::Escape)))));
+    }
+
+    #[test]
+    fn every_command_has_a_name_and_a_default() {
+        for command in Command::all() {
+            assert_eq!(Command::from_name(command.name()), Some(command));
+            assert!(default_bindings().iter().any(|&(other, _)| other == command), "{:?}", command);
+        }
+    }
+}
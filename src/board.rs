@@ -0,0 +1,718 @@
+use glam::f32::Vec2;
+use std::collections::HashMap;
+
+use cell;
+use globals::*;
+use hex;
+use life;
+use life::Stats;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Action {
+    Place(hex::BranchPoint),
+    Upgrade(hex::BranchPoint),
+    Prune(hex::BranchPoint),
+    Moss(hex::GiftPoint),
+    Wait,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ActionKind {
+    Place,
+    Upgrade,
+    Prune,
+    Moss,
+}
+
+// How much Life an action costs.
+pub type Cost = f32;
+
+// Why the rules reject an action.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ActionError {
+    NotEnoughBounty {need: Cost, have: Cost},
+    // too many thin branches in a row
+    TooManyThinBranches,
+    // a branch can't be thicker than the branch supporting it
+    ParentTooThin,
+    WouldFormCycle,
+    // not next to the tree
+    NotAttached,
+    // there is already a branch there
+    Occupied,
+    MaxLevelReached,
+    // moss only grows on the tips of branches
+    NotATip,
+    NoBranch,
+    IsRoot,
+    OutsideBoard,
+    // the branch would grow off the edge of the grid, e.g. into the ground
+    NoRoom,
+    ActionLocked,
+}
+
+// How high the bounty (called Life in the UI) can grow. The original game
+// caps it at MAX_BOUNTY, but the design document says that the bounty cannot
+// exceed life, so the other two economies make the cap grow with the tree.
+// The upgrade ladder costs 5, 25, 125 and 625 times life::BASE, and the
+// constants below are chosen so that each rung becomes affordable in turn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Economy {
+    Fixed,
+    // LIFE_CAP_TURNS turns of the current life production
+    Life,
+    // SIZE_CAP_PER_BRANCH for each branch of the tree
+    Size,
+}
+
+// Few enough that saving up for the last two upgrades takes a life level of 5
+// and 25, rather than patience.
+pub const LIFE_CAP_TURNS: f32 = 5.0;
+// The cost of a new branch, so the last two upgrades need 25 and 125
+// branches.
+pub const SIZE_CAP_PER_BRANCH: f32 = life::BASE * 5.0;
+// Enough for the starting bounty and the first upgrade.
+pub const MIN_BOUNTY_CAP: f32 = life::BASE * 25.0;
+
+// Everything which the game rules need, but none of the assets, so that it
+// can be cloned and simulated without a window.
+#[derive(Clone)]
+pub struct Board {
+    pub bounty_amount: f32,
+    pub life_amount: f32,
+    pub turn: usize,
+    pub root_point: hex::BranchPoint,
+    pub branches: HashMap<hex::BranchPoint, cell::BranchCell>,
+    pub gifts: HashMap<hex::GiftPoint, cell::GiftCell>,
+    pub stats: Stats,
+    pub forbidden: HashMap<hex::GiftPoint, bool>,
+    pub cost_multiplier: f32, // for debugging
+    pub shape: hex::Shape,
+    pub locked: Vec<ActionKind>,
+    pub economy: Economy,
+}
+
+impl Action {
+    pub fn kind(self) -> Option<ActionKind> {
+        match self {
+            Action::Place(_)   => Some(ActionKind::Place),
+            Action::Upgrade(_) => Some(ActionKind::Upgrade),
+            Action::Prune(_)   => Some(ActionKind::Prune),
+            Action::Moss(_)    => Some(ActionKind::Moss),
+            Action::Wait       => None,
+        }
+    }
+
+    // where it happens, in board coordinates
+    pub fn to_point(self) -> Option<Vec2> {
+        match self {
+            Action::Place(branch_point)
+            | Action::Upgrade(branch_point)
+            | Action::Prune(branch_point) => Some(branch_point.to_point()),
+            Action::Moss(gift_point)      => Some(gift_point.to_point()),
+            Action::Wait                  => None,
+        }
+    }
+}
+
+impl ActionKind {
+    pub fn all() -> Vec<ActionKind> {
+        vec!(ActionKind::Place, ActionKind::Upgrade, ActionKind::Prune, ActionKind::Moss)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ActionKind::Place   => "place",
+            ActionKind::Upgrade => "upgrade",
+            ActionKind::Prune   => "prune",
+            ActionKind::Moss    => "moss",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ActionKind> {
+        ActionKind::all()
+            .into_iter()
+            .find(|kind| kind.name() == name)
+    }
+}
+
+impl Economy {
+    pub fn all() -> Vec<Economy> {
+        vec!(Economy::Fixed, Economy::Life, Economy::Size)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Economy::Fixed => "fixed",
+            Economy::Life  => "life",
+            Economy::Size  => "size",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Economy> {
+        Economy::all()
+            .into_iter()
+            .find(|economy| economy.name() == name)
+    }
+}
+
+impl Board {
+    pub fn new() -> Board {
+        let mut board = Board {
+            bounty_amount: 0.0,
+            life_amount: 0.0,
+            turn: 0,
+            root_point: hex::BranchPoint::new(hex::HexPoint::new(0, 1)),
+            branches: HashMap::with_capacity(100),
+            gifts: HashMap::with_capacity(100),
+            stats: Stats::default(),
+            forbidden: HashMap::with_capacity(100),
+            cost_multiplier: 1.0,
+            shape: hex::Shape::FULL,
+            locked: Vec::new(),
+            economy: Economy::Fixed,
+        };
+        board.reset();
+        board
+    }
+
+    pub fn reset(&mut self) {
+        self.bounty_amount = 5.0;
+        self.life_amount = 0.0;
+        self.turn = 0;
+
+        self.branches.clear();
+        self.root_point = hex::BranchPoint::new(hex::HexPoint::new(0, 1));
+        let root_gift_point = hex::GiftPoint::new(hex::HexPoint::new(0, 0));
+        let mut root_cell = cell::BranchCell::new(None);
+        root_cell.branch_upgrade = 3;
+        self.branches.insert(self.root_point, root_cell);
+
+        self.forbidden.clear();
+        self.forbidden.insert(root_gift_point, true);
+
+        self.gifts.clear();
+        let origin_point = hex::GiftPoint::new(hex::HexPoint::new(0, 0));
+        let origin_cell = cell::GiftCell::new(self.root_point);
+        self.gifts.insert(origin_point, origin_cell);
+    }
+
+    pub fn next_turn(&mut self) {
+        // let basic_amount = 0.1f32; // get this amount even if no life
+        // self.bounty_amount = (self.bounty_amount+self.life_amount+basic_amount).min(30.0);
+        self.life_amount = life::life_production(&self.gifts);
+        self.bounty_amount = (self.bounty_amount + self.life_amount).min(self.bounty_cap());
+        self.turn += 1;
+
+        life::life_cycle(
+            &mut self.gifts, &self.branches, &self.forbidden, &mut self.stats
+        );
+    }
+
+    // The most bounty the player can hold at the moment.
+    pub fn bounty_cap(&self) -> f32 {
+        let cap = match self.economy {
+            Economy::Fixed => return MAX_BOUNTY,
+            Economy::Life  => self.life_amount * LIFE_CAP_TURNS,
+            Economy::Size  => self.branches.len() as f32 * SIZE_CAP_PER_BRANCH,
+        };
+        cap.clamp(MIN_BOUNTY_CAP, MAX_BOUNTY)
+    }
+
+    pub fn update_stats(&mut self) {
+        self.stats.life_max = self.stats.life_max.max(self.life_amount.floor() as usize);
+        self.stats.branches_max = self.stats.branches_max.max(self.stats.branch_lv1_count + self.stats.branch_lv2_count);
+
+        // calculate the moss count
+        // Need to skip non-tips. Check that children is [] when we get those!
+        self.stats.moss_count = 0;
+        for (&gift_point, &b) in self.forbidden.iter() {
+            if b && self.gift_children(gift_point).is_empty() {
+                self.stats.moss_count += 1;
+            }
+        }
+    }
+
+    pub fn is_moss(&self, gift_point: hex::GiftPoint) -> bool {
+        *self.forbidden.get(&gift_point).unwrap_or(&false) && self.gift_children(gift_point).is_empty()
+    }
+
+    pub fn branch_parent_branch(&self, branch_point: hex::BranchPoint) -> Option<hex::BranchPoint> {
+        let branch_cell = self.branches.get(&branch_point)?;
+        let gift_point = branch_cell.parent?;
+        let gift_cell = self.gifts.get(&gift_point)?;
+        Some(gift_cell.parent)
+    }
+
+    pub fn branch_nth_parent_branch_cell(&self, branch_point: hex::BranchPoint, n: u8) -> Option<cell::BranchCell> {
+        if n == 0 {
+            self.branches.get(&branch_point).map(|b| *b)
+        } else {
+            let parent_point = self.branch_parent_branch(branch_point)?;
+            self.branch_nth_parent_branch_cell(parent_point, n-1)
+        }
+    }
+
+    pub fn branch_nth_parent_branch_cell_or_root(&self, branch_point: hex::BranchPoint, n: u8) -> cell::BranchCell {
+        match self.branch_nth_parent_branch_cell(branch_point, n) {
+            Some(branch_cell) => branch_cell,
+            None => {
+                *self.branches.get(&self.root_point).unwrap()
+            },
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn gift_parent_gift(&self, gift_point: hex::GiftPoint) -> Option<hex::GiftPoint> {
+        let gift_cell = self.gifts.get(&gift_point)?;
+        let branch_point = gift_cell.parent;
+        let branch_cell = self.branches.get(&branch_point)?;
+        branch_cell.parent
+    }
+
+    pub fn branch_children(&self, branch_point: hex::BranchPoint) -> Vec<hex::GiftPoint> {
+        branch_point.gift_neighbours()
+            .iter()
+            .map(|g| *g)
+            .filter(|g|
+                match self.gifts.get(g) {
+                    None => false,
+                    Some(gift_cell) => gift_cell.parent == branch_point,
+                }
+            )
+            .collect()
+    }
+
+    pub fn gift_children(&self, gift_point: hex::GiftPoint) -> Vec<hex::BranchPoint> {
+        gift_point.branch_neighbours()
+            .iter()
+            .map(|b| *b)
+            .filter(|b|
+                match self.branches.get(b) {
+                    None => false,
+                    Some(branch_cell) => branch_cell.parent == Some(gift_point),
+                }
+            )
+            .collect()
+    }
+
+    // A branch and everything which grows from it, parents first.
+    pub fn subtree(&self, branch_point: hex::BranchPoint) -> (Vec<hex::BranchPoint>, Vec<hex::GiftPoint>) {
+        let mut branches = vec!(branch_point);
+        let mut gifts = Vec::new();
+        let mut i = 0;
+        while i < branches.len() {
+            for gift_point in self.branch_children(branches[i]) {
+                gifts.push(gift_point);
+                branches.extend(self.gift_children(gift_point));
+            }
+            i += 1;
+        }
+        (branches, gifts)
+    }
+
+    pub fn prune_branch(&mut self, branch_point: hex::BranchPoint) {
+        if let Some(_) = self.branches.get(&branch_point) {
+            for gift_point in self.branch_children(branch_point) {
+                self.prune_gift(gift_point);
+            }
+
+            if let Some(branch_cell) = self.branches.remove(&branch_point) {
+                match branch_cell.branch_upgrade {
+                    0 => self.stats.branch_lv1_count -= 1,
+                    _ => self.stats.branch_lv2_count -= 1,
+                };
+            }
+        }
+    }
+
+    pub fn prune_gift(&mut self, gift_point: hex::GiftPoint) {
+        if let Some(_) = self.gifts.get(&gift_point) {
+            for branch_point in self.gift_children(gift_point) {
+                self.prune_branch(branch_point);
+            }
+            if let Some(gift_cell) = self.gifts.remove(&gift_point) {
+                self.stats.remove_gift(gift_cell.gift);
+            }
+        }
+        if let Some(_) = self.forbidden.get(&gift_point) {
+            self.forbidden.remove(&gift_point);
+        }
+    }
+
+    pub fn remove_gift(&mut self, gift_point: hex::GiftPoint) {
+        if let Some(gift_cell) = self.gifts.get(&gift_point) {
+            self.stats.remove_gift(gift_cell.gift);
+        }
+
+        self.gifts
+            .entry(gift_point)
+            .and_modify(|g| g.gift = None);
+        if self.gifts.contains_key(&gift_point) && self.gift_children(gift_point).is_empty() {
+            self.forbidden
+                .entry(gift_point)
+                .and_modify(|b| *b ^= true)
+                .or_insert(true);
+            if *self.forbidden.get(&gift_point).unwrap() {
+                self.stats.moss_added = true;
+            } else {
+                self.stats.moss_removed = true;
+            }
+        }
+    }
+
+    // The tree cells on either side of a branch point: (full, empty).
+    fn attachment(&self, branch_point: hex::BranchPoint) -> (Vec<hex::GiftPoint>, Vec<hex::GiftPoint>) {
+        branch_point.gift_neighbours()
+            .into_iter()
+            .partition(|g| self.gifts.get(g).is_some())
+    }
+
+    fn attach_branch(&mut self, branch_point: hex::BranchPoint, full_gift_point: hex::GiftPoint, empty_neighbour: hex::GiftPoint) {
+        let full_gift_cell = *self.gifts.get(&full_gift_point).unwrap();
+        self.stats.branch_lv1_count += 1;
+        let branch_cell = cell::BranchCell::new(Some(full_gift_point));
+        let gift_cell = cell::GiftCell::new(branch_point);
+        self.branches.insert(branch_point, branch_cell);
+        self.gifts.insert(empty_neighbour, gift_cell);
+        self.forbidden.insert(full_gift_point, true);
+        if full_gift_cell.gift.is_some() {
+            self.remove_gift(full_gift_point);
+        }
+
+        if self.branch_nth_parent_branch_cell(full_gift_cell.parent, 2).is_some() {
+            self.stats.branch_length3_count += 1;
+        }
+        if self.branch_nth_parent_branch_cell(full_gift_cell.parent, 3).is_some() {
+            self.stats.branch_length4_count += 1;
+        }
+        if self.branch_nth_parent_branch_cell(full_gift_cell.parent, 4).is_some() {
+            self.stats.branch_length5_count += 1;
+        }
+    }
+
+    // Grows a branch of the given thickness for free, ignoring the strain
+    // rules, as long as it is attached to the tree. For levels and debugging.
+    pub fn insert_branch(&mut self, branch_point: hex::BranchPoint, branch_upgrade: usize) -> Result<(), ActionError> {
+        let (full_neighbours, empty_neighbours) = self.attachment(branch_point);
+        if self.branches.contains_key(&branch_point) {
+            self.set_branch_upgrade(branch_point, branch_upgrade);
+            Ok(())
+        } else if empty_neighbours.len() == 1 && full_neighbours.len() == 1 {
+            self.attach_branch(branch_point, full_neighbours[0], empty_neighbours[0]);
+            self.set_branch_upgrade(branch_point, branch_upgrade);
+            Ok(())
+        } else if full_neighbours.len() == 2 {
+            Err(ActionError::WouldFormCycle)
+        } else {
+            Err(ActionError::NotAttached)
+        }
+    }
+
+    pub fn set_branch_upgrade(&mut self, branch_point: hex::BranchPoint, branch_upgrade: usize) {
+        if let Some(branch_cell) = self.branches.get_mut(&branch_point) {
+            let branch_upgrade = branch_upgrade.min(3);
+            match (branch_cell.branch_upgrade, branch_upgrade) {
+                (0, 0) => {},
+                (0, _) => {
+                    self.stats.branch_lv1_count -= 1;
+                    self.stats.branch_lv2_count += 1;
+                },
+                (_, 0) => {
+                    self.stats.branch_lv1_count += 1;
+                    self.stats.branch_lv2_count -= 1;
+                },
+                _ => {},
+            }
+            branch_cell.branch_upgrade = branch_upgrade;
+        }
+    }
+
+    // Replaces whatever grows on a gift cell. For levels and debugging.
+    pub fn set_gift(&mut self, gift_point: hex::GiftPoint, gift: Option<cell::Gift>) {
+        if let Some(gift_cell) = self.gifts.get_mut(&gift_point) {
+            self.stats.remove_gift(gift_cell.gift);
+            gift_cell.gift = gift;
+            self.stats.add_gift(gift_cell.gift);
+        }
+    }
+
+    // Performs an action which `validate_action` accepts, and pays for it.
+    pub fn apply(&mut self, action: Action) -> Result<Cost, ActionError> {
+        let cost = validate_action(self, action)?;
+        self.bounty_amount -= cost;
+        match action {
+            Action::Place(branch_point) => {
+                let (full_neighbours, empty_neighbours) = self.attachment(branch_point);
+                self.attach_branch(branch_point, full_neighbours[0], empty_neighbours[0]);
+            },
+            Action::Upgrade(branch_point) => {
+                let branch_upgrade = self.branches[&branch_point].branch_upgrade;
+                self.set_branch_upgrade(branch_point, branch_upgrade + 1);
+            },
+            Action::Prune(branch_point) => self.prune_branch(branch_point),
+            Action::Moss(gift_point) => self.remove_gift(gift_point),
+            Action::Wait => {},
+        }
+        Ok(cost)
+    }
+}
+
+// The actions which might make sense on this board, valid or not: growing
+// next to the tree, and acting on its branches and gifts. Sorted, so that
+// the bots are deterministic despite the HashMap iteration order.
+pub fn candidate_actions(board: &Board) -> Vec<Action> {
+    let mut candidates: Vec<Action> = Vec::new();
+    for (&gift_point, gift_cell) in board.gifts.iter() {
+        for branch_point in gift_point.branch_neighbours() {
+            if !board.branches.contains_key(&branch_point) {
+                candidates.push(Action::Place(branch_point));
+            }
+        }
+        if gift_cell.gift.is_some() || board.is_moss(gift_point) {
+            candidates.push(Action::Moss(gift_point));
+        }
+    }
+    for &branch_point in board.branches.keys() {
+        candidates.push(Action::Upgrade(branch_point));
+        candidates.push(Action::Prune(branch_point));
+    }
+
+    // a branch point can be next to two gift cells
+    candidates.sort();
+    candidates.dedup();
+    candidates
+}
+
+// Whether the rules allow an action, and if so, how much it would cost. This
+// doesn't modify the board, so the UI and the bots can ask before acting.
+pub fn validate_action(board: &Board, action: Action) -> Result<Cost, ActionError> {
+    if let Some(kind) = action.kind() {
+        if board.locked.contains(&kind) {
+            return Err(ActionError::ActionLocked);
+        }
+    }
+    let cost = match action {
+        Action::Place(branch_point) => {
+            if board.branches.contains_key(&branch_point) {
+                return Err(ActionError::Occupied);
+            }
+            let (full_neighbours, empty_neighbours) = board.attachment(branch_point);
+            match full_neighbours.len() {
+                2 => return Err(ActionError::WouldFormCycle),
+                1 => {},
+                _ => return Err(ActionError::NotAttached),
+            }
+            if !board.shape.contains(branch_point.hex_point) {
+                return Err(ActionError::OutsideBoard);
+            }
+            match empty_neighbours.first() {
+                Some(empty_neighbour) if !board.shape.contains(empty_neighbour.hex_point) => return Err(ActionError::OutsideBoard),
+                Some(_) => {},
+                None    => return Err(ActionError::NoRoom),
+            }
+            let full_gift_cell = board.gifts[&full_neighbours[0]];
+            let grandparent_cell = board.branch_nth_parent_branch_cell_or_root(full_gift_cell.parent, 2);
+            if grandparent_cell.branch_upgrade == 0 {
+                return Err(ActionError::TooManyThinBranches);
+            }
+            board.cost_multiplier * life::BASE * 5.0
+        },
+        Action::Upgrade(branch_point) => {
+            let branch_cell = board.branches.get(&branch_point).ok_or(ActionError::NoBranch)?;
+            let parent_cell = board.branch_nth_parent_branch_cell_or_root(branch_point, 1);
+            let grandparent_cell = board.branch_nth_parent_branch_cell_or_root(branch_point, 3);
+            let cost = match branch_cell.branch_upgrade {
+                0 => board.cost_multiplier * life::BASE * 25.0,
+                1 => board.cost_multiplier * life::BASE * 125.0,
+                2 => board.cost_multiplier * life::BASE * 625.0,
+                _ => return Err(ActionError::MaxLevelReached),
+            };
+            if branch_cell.branch_upgrade >= parent_cell.branch_upgrade {
+                return Err(ActionError::ParentTooThin);
+            }
+            if branch_cell.branch_upgrade + 1 >= grandparent_cell.branch_upgrade && grandparent_cell.branch_upgrade < 3 {
+                return Err(ActionError::TooManyThinBranches);
+            }
+            cost
+        },
+        Action::Prune(branch_point) => {
+            if branch_point == board.root_point {
+                return Err(ActionError::IsRoot);
+            }
+            if !board.branches.contains_key(&branch_point) {
+                return Err(ActionError::NoBranch);
+            }
+            0.0
+        },
+        Action::Moss(gift_point) => {
+            // as with the original right-click, the gift on a cell which
+            // branches grow from is released, but only tips get moss
+            let gift_cell = board.gifts.get(&gift_point).ok_or(ActionError::NotATip)?;
+            if gift_cell.gift.is_none() && !board.gift_children(gift_point).is_empty() {
+                return Err(ActionError::NotATip);
+            }
+            0.0
+        },
+        Action::Wait => 0.0,
+    };
+    if board.bounty_amount < cost {
+        return Err(ActionError::NotEnoughBounty {need: cost, have: board.bounty_amount});
+    }
+    Ok(cost)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn branch(q: i32, r: i32) -> hex::BranchPoint {
+        hex::BranchPoint::new(hex::HexPoint::new(q, r))
+    }
+
+    fn gift(q: i32, r: i32) -> hex::GiftPoint {
+        hex::GiftPoint::new(hex::HexPoint::new(q, r))
+    }
+
+    // A board with plenty of bounty and these (q, r, thickness) branches,
+    // attached in order. The trunk grows up from (0, 0) through (0, -1).
+    fn tree(branches: &[(i32, i32, usize)]) -> Board {
+        let mut board = Board::new();
+        board.bounty_amount = 1000.0;
+        for &(q, r, branch_upgrade) in branches {
+            board.insert_branch(branch(q, r), branch_upgrade).unwrap();
+        }
+        board
+    }
+
+    fn with<F: FnOnce(&mut Board)>(mut board: Board, f: F) -> Board {
+        f(&mut board);
+        board
+    }
+
+    #[test]
+    fn validate_action_errors() {
+        let cases: Vec<(&str, Board, Action, ActionError)> = vec!(
+            ("locked",              with(tree(&[]), |board| board.locked.push(ActionKind::Place)),
+                                    Action::Place(branch(0, -1)),  ActionError::ActionLocked),
+            ("occupied",            tree(&[(0, -1, 0)]),
+                                    Action::Place(branch(0, -1)),  ActionError::Occupied),
+            ("cycle",               tree(&[(0, -1, 0), (1, -1, 0)]),
+                                    Action::Place(branch(1, -2)),  ActionError::WouldFormCycle),
+            ("slot off the shape",  with(tree(&[(-1, 0, 1)]), |board| board.shape = hex::Shape {width: 1, height: 12}),
+                                    Action::Place(branch(-3, 0)),  ActionError::OutsideBoard),
+            ("cell off the shape",  with(tree(&[]), |board| board.shape = hex::Shape {width: 1, height: 12}),
+                                    Action::Place(branch(-1, 0)),  ActionError::OutsideBoard),
+            ("into the ground",     tree(&[]),
+                                    Action::Place(branch(1, 0)),   ActionError::NoRoom),
+            ("detached",            tree(&[]),
+                                    Action::Place(branch(0, -3)),  ActionError::NotAttached),
+            ("thin place",          tree(&[(0, -1, 0), (0, -3, 0), (0, -5, 0)]),
+                                    Action::Place(branch(0, -7)),  ActionError::TooManyThinBranches),
+            ("thin upgrade",        tree(&[(0, -1, 1), (0, -3, 1), (0, -5, 1), (0, -7, 0)]),
+                                    Action::Upgrade(branch(0, -7)), ActionError::TooManyThinBranches),
+            ("upgrade nothing",     tree(&[]),
+                                    Action::Upgrade(branch(0, -1)), ActionError::NoBranch),
+            ("prune nothing",       tree(&[]),
+                                    Action::Prune(branch(0, -1)),  ActionError::NoBranch),
+            // reported before the parent, whose thickness is capped at 3 too
+            ("max level",           tree(&[(0, -1, 3)]),
+                                    Action::Upgrade(branch(0, -1)), ActionError::MaxLevelReached),
+            ("parent too thin",     tree(&[(0, -1, 0), (0, -3, 0)]),
+                                    Action::Upgrade(branch(0, -3)), ActionError::ParentTooThin),
+            // below the board, so only scripts can reach it
+            ("root",                tree(&[]),
+                                    Action::Prune(branch(0, 1)),   ActionError::IsRoot),
+            ("moss off the tree",   tree(&[]),
+                                    Action::Moss(gift(0, -4)),     ActionError::NotATip),
+            ("moss on a fork",      tree(&[(0, -1, 0), (0, -3, 0)]),
+                                    Action::Moss(gift(0, -2)),     ActionError::NotATip),
+            ("broke",               with(tree(&[]), |board| board.bounty_amount = 0.5),
+                                    Action::Place(branch(0, -1)),  ActionError::NotEnoughBounty {need: life::BASE * 5.0, have: 0.5}),
+        );
+        for (name, board, action, action_error) in cases {
+            assert_eq!(validate_action(&board, action), Err(action_error), "{}", name);
+        }
+    }
+
+    #[test]
+    fn validate_action_costs() {
+        let cases: Vec<(&str, Board, Action, Cost)> = vec!(
+            ("place",       tree(&[]),                               Action::Place(branch(0, -1)),   life::BASE * 5.0),
+            ("upgrade 1",   tree(&[(0, -1, 0)]),                     Action::Upgrade(branch(0, -1)), life::BASE * 25.0),
+            ("upgrade 2",   tree(&[(0, -1, 1)]),                     Action::Upgrade(branch(0, -1)), life::BASE * 125.0),
+            ("upgrade 3",   tree(&[(0, -1, 2)]),                     Action::Upgrade(branch(0, -1)), life::BASE * 625.0),
+            ("prune",       tree(&[(0, -1, 0)]),                     Action::Prune(branch(0, -1)),   0.0),
+            ("moss",        tree(&[(0, -1, 0)]),                     Action::Moss(gift(0, -2)),      0.0),
+            ("release",     with(tree(&[(0, -1, 0), (0, -3, 0)]), |board| board.set_gift(gift(0, -2), Some(cell::Gift::Leaves))),
+                                                                     Action::Moss(gift(0, -2)),      0.0),
+            ("wait",        tree(&[]),                               Action::Wait,                   0.0),
+        );
+        for (name, board, action, cost) in cases {
+            assert_eq!(validate_action(&board, action), Ok(cost), "{}", name);
+
+            // free building, even when broke
+            let board = with(board, |board| {
+                board.cost_multiplier = 0.0;
+                board.bounty_amount = 0.0;
+            });
+            assert_eq!(validate_action(&board, action), Ok(0.0), "{} for free", name);
+        }
+    }
+
+    #[test]
+    fn apply_pays_the_cost() {
+        let mut board = tree(&[]);
+        assert_eq!(board.apply(Action::Place(branch(0, -1))), Ok(life::BASE * 5.0));
+        assert_eq!(board.bounty_amount, 1000.0 - life::BASE * 5.0);
+        assert!(board.branches.contains_key(&branch(0, -1)));
+        assert!(board.gifts.contains_key(&gift(0, -2)));
+    }
+
+    #[test]
+    fn apply_leaves_the_board_alone_when_rejected() {
+        let mut board = with(tree(&[(0, -1, 0), (0, -3, 0)]), |board| board.bounty_amount = 3.0);
+        assert_eq!(board.apply(Action::Upgrade(branch(0, -3))), Err(ActionError::ParentTooThin));
+        assert_eq!(board.apply(Action::Prune(board.root_point)), Err(ActionError::IsRoot));
+        assert_eq!(board.bounty_amount, 3.0);
+        assert_eq!(board.branches.len(), 3);
+        assert_eq!(board.branches[&branch(0, -3)].branch_upgrade, 0);
+    }
+
+    #[test]
+    fn moss_toggles_on_tips_and_releases_gifts_on_forks() {
+        let mut board = tree(&[(0, -1, 0), (0, -3, 0)]);
+        board.apply(Action::Moss(gift(0, -4))).unwrap();
+        assert!(board.is_moss(gift(0, -4)));
+        board.apply(Action::Moss(gift(0, -4))).unwrap();
+        assert!(!board.is_moss(gift(0, -4)));
+
+        board.set_gift(gift(0, -2), Some(cell::Gift::Leaves));
+        board.apply(Action::Moss(gift(0, -2))).unwrap();
+        assert_eq!(board.gifts[&gift(0, -2)].gift, None);
+        assert_eq!(board.stats.leaf_count, 0);
+    }
+
+    #[test]
+    fn the_growing_caps_bind_before_the_fixed_one() {
+        // eight branches with the root, which soon make 1 bounty a turn
+        let bounty_after = |economy: Economy, turns: usize| {
+            let branches = [(0, -1, 2), (-1, -2, 1), (1, -3, 1), (0, -3, 1), (-2, -3, 0), (0, -5, 0), (1, -5, 0)];
+            let mut board = with(tree(&branches), |board| {
+                board.bounty_amount = 0.0;
+                board.economy = economy;
+            });
+            for _ in 0..turns {
+                board.next_turn();
+            }
+            (board.bounty_amount, board.bounty_cap())
+        };
+        let (fixed, _) = bounty_after(Economy::Fixed, 12);
+        for &economy in &[Economy::Life, Economy::Size] {
+            let (bounty, cap) = bounty_after(economy, 12);
+            assert_eq!(bounty, cap, "{:?}", economy);
+            assert!(bounty < fixed, "{:?}: {} is not below {}", economy, bounty, fixed);
+        }
+    }
+}
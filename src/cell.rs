@@ -18,37 +18,42 @@ pub struct Assets {
     squirrel_images: Vec<Image>,
 }
 
+// from the twig to the trunk, one per `branch_upgrade`
+pub const BRANCH_IMAGES: [&str; 4] = [
+    "/twig.png",
+    "/small branch.png",
+    "/big branch.png",
+    "/trunk.png",
+];
+
 pub fn load_assets(ctx: &mut Context) -> GameResult<Assets> {
     Ok(Assets {
-        branch_images: vec!(
-            Image::new(ctx, "/twig.png")?,
-            Image::new(ctx, "/small branch.png")?,
-            Image::new(ctx, "/big branch.png")?,
-            Image::new(ctx, "/trunk.png")?,
-        ),
+        branch_images: BRANCH_IMAGES.iter()
+            .map(|path| Image::new(ctx, path))
+            .collect::<GameResult<Vec<Image>>>()?,
         leaf_images: vec!(
-            Image::new(ctx, "/leaves.png")?,
+            Image::new(ctx, Gift::Leaves.image_path())?,
         ),
         flower_images: vec!(
-            Image::new(ctx, "/flowers.png")?,
+            Image::new(ctx, Gift::Flowers.image_path())?,
             //Image::new(ctx, "/flower1.png")?,
             //Image::new(ctx, "/flower2.png")?,
             //Image::new(ctx, "/flower3.png")?,
         ),
         berry_images: vec!(
-            Image::new(ctx, "/berry bunch.png")?,
+            Image::new(ctx, Gift::Berries.image_path())?,
         ),
         nut_images: vec!(
-            Image::new(ctx, "/nut bunch.png")?,
+            Image::new(ctx, Gift::Nuts.image_path())?,
         ),
         beehive_images: vec!(
-            Image::new(ctx, "/beehive.png")?,
+            Image::new(ctx, Gift::Beehive.image_path())?,
         ),
         birdnest_images: vec!(
-            Image::new(ctx, "/nest.png")?,
+            Image::new(ctx, Gift::Birdnest.image_path())?,
         ),
         squirrel_images: vec!(
-            Image::new(ctx, "/squirrel.png")?,
+            Image::new(ctx, Gift::Squirrel.image_path())?,
         ),
     })
 }
@@ -100,7 +105,28 @@ pub struct BranchCell {
 }
 
 impl Gift {
-    pub fn singular(self) -> &'static str {
+    pub fn all() -> Vec<Gift> {
+        vec!(
+            Gift::Leaves,
+            Gift::Flowers,
+            Gift::Berries,
+            Gift::Nuts,
+            Gift::Beehive,
+            Gift::Birdnest,
+            Gift::Squirrel,
+        )
+    }
+
+    // accepts either the singular or the plural name
+    pub fn from_name(name: &str) -> Option<Gift> {
+        Gift::all()
+            .into_iter()
+            .find(|gift| gift.id() == name || gift.plural_id() == name)
+    }
+
+    // The names used in the level, goal and script files. The names which
+    // the player sees are in resources/lang, see `Locale::gifts`.
+    pub fn id(self) -> &'static str {
         match self {
             Gift::Leaves   => "leaf",
             Gift::Flowers  => "flower",
@@ -112,8 +138,21 @@ impl Gift {
         }
     }
 
-    #[allow(dead_code)]
-    pub fn plural(self) -> &'static str {
+    // the first variant, see `Assets::gift_images`
+    pub fn image_path(self) -> &'static str {
+        match self {
+            Gift::Leaves   => "/leaves.png",
+            Gift::Flowers  => "/flowers.png",
+            Gift::Berries  => "/berry bunch.png",
+            Gift::Nuts     => "/nut bunch.png",
+            Gift::Beehive  => "/beehive.png",
+            Gift::Birdnest => "/nest.png",
+            Gift::Squirrel => "/squirrel.png",
+        }
+    }
+
+    // as in "gift -2 0 flowers"
+    pub fn plural_id(self) -> &'static str {
         match self {
             Gift::Leaves   => "leaves",
             Gift::Flowers  => "flowers",
@@ -154,12 +193,16 @@ impl BranchCell {
         draw_param: DrawParam
     ) -> GameResult<()> {
         let image = &assets.branch_images[self.branch_upgrade];
-        let angle = match branch_point.orientation() {
-            Orientation::Vert     => 0.0,
-            Orientation::Diag     => 60.0 * PI / 180.0,
-            Orientation::AntiDiag => 120.0 * PI / 180.0,
-        };
-        center::draw_centered_image(ctx, image, branch_point.to_point(), angle, draw_param)
+        center::draw_centered_image(ctx, image, branch_point.to_point(), branch_angle(branch_point), draw_param)
+    }
+}
+
+// The rotation of the branch images, which point up.
+pub fn branch_angle(branch_point: BranchPoint) -> f32 {
+    match branch_point.orientation() {
+        Orientation::Vert     => 0.0,
+        Orientation::Diag     => 60.0 * PI / 180.0,
+        Orientation::AntiDiag => 120.0 * PI / 180.0,
     }
 }
 
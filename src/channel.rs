@@ -1,67 +1,101 @@
 use core::time::Duration;
-use ggez::{GameResult, Context};
-use ggez::audio;
-use ggez::audio::SoundSource;
+use ggez::GameResult;
 
 use globals::*;
+use sound::{Audio, Backend, Sound};
 
 
+// How a fade progresses from one volume to the next.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    // starts slowly
+    EaseIn,
+    // ends slowly
+    EaseOut,
+    EaseInOut,
+}
+
 #[derive(Debug)]
 pub struct Channel {
-    pub source: audio::Source,
+    pub source: Box<dyn Sound>,
     pub start_time: Duration,
     pub duration: Duration,
     pub initial_volume: f32,
     pub target_volume: f32,
-    playing: bool,
+    pub easing: Easing,
+    // the music volume from the settings, which the fades are scaled by
+    pub gain: f32,
+}
+
+impl Easing {
+    pub fn from_name(name: &str) -> Option<Easing> {
+        match name {
+            "linear"      => Some(Easing::Linear),
+            "ease_in"     => Some(Easing::EaseIn),
+            "ease_out"    => Some(Easing::EaseOut),
+            "ease_in_out" => Some(Easing::EaseInOut),
+            _             => None,
+        }
+    }
+
+    // from 0.0 to 1.0, for a fade which is `t` of the way through
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear    => t,
+            Easing::EaseIn    => t * t,
+            Easing::EaseOut   => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
 }
 
 impl Channel {
-    pub fn new(ctx: &mut Context, path: &'static str) -> GameResult<Channel> {
-        let mut source = audio::Source::new(ctx, path)?;
+    pub fn new(audio: &mut dyn Audio, backend: &Backend, path: &str) -> GameResult<Channel> {
+        let mut source = backend.load(audio, path, false)?;
         source.set_repeat(true);
-        source.set_volume(0.0);
+        source.set_volume(audio, 0.0);
 
         Ok(Channel {
             source,
-            start_time: get_current_time(ctx),
+            start_time: audio.now(),
             duration: Duration::ZERO,
             initial_volume: 0.0,
             target_volume: 0.0,
-            playing: false,
+            easing: Easing::Linear,
+            gain: 1.0,
         })
     }
 
-    pub fn enable(&mut self, ctx: &mut Context, should_be_playing: bool) {
-        if self.playing && !should_be_playing {
-            self.playing = false;
-            self.set_future_volume(ctx, Duration::from_millis(1000), 0.0);
-        } else if !self.playing && should_be_playing {
-            self.playing = true;
-            self.set_future_volume(ctx, Duration::from_millis(1000), 1.0);
-        }
-    }
-
-    pub fn set_future_volume(&mut self, ctx: &mut Context, duration: Duration, volume: f32) {
-        self.start_time = get_current_time(ctx);
+    // The fade may start later, e.g. on the next bar; until then the volume
+    // stays where it is.
+    pub fn set_future_volume(&mut self, audio: &dyn Audio, start_time: Duration, duration: Duration, volume: f32) {
+        self.initial_volume = self.volume(audio);
+        self.start_time = start_time;
         self.duration = duration;
-        self.initial_volume = self.source.volume();
         self.target_volume = volume;
     }
 
-    pub fn update(&mut self, ctx: &mut Context) {
+    // before the gain is applied
+    pub fn volume(&self, audio: &dyn Audio) -> f32 {
         let t0 = duration_to_f32(self.start_time);
         let t1 = duration_to_f32(self.start_time + self.duration);
         let dt = duration_to_f32(self.duration);
-        let t = duration_to_f32(get_current_time(ctx));
+        let t = duration_to_f32(audio.now());
         let v0 = self.initial_volume;
         let v1 = self.target_volume;
         let dv = v1 - v0;
-        if t >= t1 {
-            self.source.set_volume(v1);
+        if t <= t0 {
+            v0
+        } else if t >= t1 {
+            v1
         } else {
-            let v = v0 + (t - t0) * dv / dt;
-            self.source.set_volume(v);
+            v0 + self.easing.apply((t - t0) / dt) * dv
         }
     }
+
+    pub fn update(&mut self, audio: &dyn Audio) {
+        let volume = self.volume(audio);
+        self.source.set_volume(audio, volume * self.gain);
+    }
 }
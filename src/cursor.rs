@@ -0,0 +1,67 @@
+use hex::{DirectionIndex, HexPoint, HexVector, InBoundsPoint, Shape};
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Move {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+// A hex cursor for playing without a mouse. Each step moves to one of the six
+// neighbouring points, so it alternates between branch slots and gift cells.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cursor {
+    pub hex_point: HexPoint,
+    // There is no neighbour straight to the left or right, so sideways steps
+    // alternate between the upper and the lower diagonal in order to stay on
+    // the same row; true when the next one should go down.
+    zig: bool,
+}
+
+impl Cursor {
+    pub fn new() -> Cursor {
+        Cursor {
+            // just above the trunk
+            hex_point: HexPoint::new(0, -1),
+            zig: false,
+        }
+    }
+
+    // e.g. when the mouse moves
+    pub fn jump_to(&mut self, in_bounds_point: InBoundsPoint) {
+        self.hex_point = match in_bounds_point {
+            InBoundsPoint::BranchPoint(branch_point) => branch_point.hex_point,
+            InBoundsPoint::GiftPoint(gift_point)     => gift_point.hex_point,
+        };
+    }
+
+    // Stays put rather than leaving the board, but a sideways step at the
+    // edge of the board still tries the other diagonal.
+    pub fn step(&mut self, direction: Move, shape: Shape) -> Option<InBoundsPoint> {
+        // see HexVector::from_index
+        let direction_indices: Vec<DirectionIndex> = match (direction, self.zig) {
+            (Move::Up,    _)     => vec!(0),
+            (Move::Down,  _)     => vec!(3),
+            (Move::Left,  false) => vec!(1, 2),
+            (Move::Left,  true)  => vec!(2, 1),
+            (Move::Right, false) => vec!(5, 4),
+            (Move::Right, true)  => vec!(4, 5),
+        };
+        for direction_index in direction_indices {
+            let hex_point = self.hex_point + HexVector::from_index(direction_index);
+            if shape.is_in_bounds(hex_point).is_some() {
+                self.hex_point = hex_point;
+                // after going up, the next sideways step goes down
+                self.zig = direction_index == 1 || direction_index == 5;
+                break;
+            }
+        }
+        self.in_bounds_point(shape)
+    }
+
+    pub fn in_bounds_point(self, shape: Shape) -> Option<InBoundsPoint> {
+        shape.is_in_bounds(self.hex_point)
+    }
+}
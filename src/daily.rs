@@ -0,0 +1,255 @@
+use ggez::{GameResult, Context};
+use ggez::graphics::*;
+use glam::f32::Vec2;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bindings::{Bindings, Command};
+use board::Board;
+use cell::Gift;
+use layout;
+use locale::Locale;
+use palette::Palette;
+use prng::Prng;
+use goal::Goal;
+use hex;
+use level;
+use text;
+
+
+// Days since 1970-01-01, in UTC so that everyone gets the same puzzle at the
+// same time.
+pub fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() / 86400)
+        .unwrap_or(0)
+}
+
+// "2019-04-27", using Howard Hinnant's days-to-civil algorithm.
+pub fn format_day(day: u64) -> String {
+    let z = day as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let d = day_of_year - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 {mp + 3} else {mp - 9};
+    let y = year_of_era + era * 400 + if m <= 2 {1} else {0};
+    format!("{}-{:02}-{:02}", y, m, d)
+}
+
+// The goals are all known to be reachable on any shape the generator picks.
+fn goals() -> Vec<Goal> {
+    vec!(
+        Goal::LifeLevel(3),
+        Goal::LifeLevel(5),
+        Goal::Creature(Gift::Flowers, 2),
+        Goal::Creature(Gift::Beehive, 1),
+        Goal::Creature(Gift::Berries, 1),
+        Goal::Creature(Gift::Nuts, 1),
+    )
+}
+
+// A few patches of moss, sorted, never on the cells around the trunk so
+// that the tree can always start growing.
+pub fn random_moss(rng: &mut Prng, shape: hex::Shape) -> Vec<hex::GiftPoint> {
+    let origin = hex::GiftPoint::new(hex::HexPoint::new(0, 0));
+    let near_origin = origin.gift_neighbours();
+    let mut candidates: Vec<hex::GiftPoint> = Vec::new();
+    for q in -shape.width..=shape.width {
+        for r in -40..=0 {
+            if let Some(hex::InBoundsPoint::GiftPoint(gift_point)) = shape.is_in_bounds(hex::HexPoint::new(q, r)) {
+                if gift_point != origin && !near_origin.contains(&gift_point) {
+                    candidates.push(gift_point);
+                }
+            }
+        }
+    }
+    let moss_count = rng.range(2..7) as usize;
+    let mut moss = rng.sample(&candidates, moss_count);
+    moss.sort();
+    moss
+}
+
+// The puzzle of the day. Everything is derived from the day number alone, so
+// the same day always produces the same puzzle, on every machine.
+pub fn generate(day: u64) -> level::Level {
+    let mut rng = Prng::new(day);
+    let mut level = level::Level::new(&format!("daily-{}", day));
+    level.name = format!("Daily {}", format_day(day));
+    level.hint = "Everyone gets the same puzzle today - how few turns can you do it in?".to_string();
+    level.shape = hex::Shape {
+        width: rng.range(4..11),
+        height: rng.range(12..34),
+    };
+    level.bounty = rng.range(3..16) as f32;
+
+    level.moss = random_moss(&mut rng, level.shape);
+
+    level.goals.push(*rng.choose(&goals()).unwrap());
+    level
+}
+
+// The best number of turns for each day, one "day turns" per line.
+#[derive(Debug)]
+pub struct History {
+    path: PathBuf,
+    pub best_turns: BTreeMap<u64, usize>,
+}
+
+impl History {
+    pub fn load(path: &Path) -> History {
+        let best_turns = fs::read_to_string(path)
+            .map(|contents|
+                contents.lines()
+                    .filter_map(|line| {
+                        let words: Vec<&str> = line.split_whitespace().collect();
+                        Some((words.first()?.parse().ok()?, words.get(1)?.parse().ok()?))
+                    })
+                    .collect()
+            )
+            .unwrap_or_default();
+        History {
+            path: path.to_path_buf(),
+            best_turns,
+        }
+    }
+
+    pub fn record(&mut self, day: u64, turns: usize) {
+        let best = self.best_turns.get(&day).map_or(turns, |&best| best.min(turns));
+        self.best_turns.insert(day, best);
+        self.save();
+    }
+
+    // The number of consecutive days solved, ending today, or yesterday if
+    // today's puzzle isn't solved yet.
+    pub fn streak(&self, today: u64) -> usize {
+        let mut day = if self.best_turns.contains_key(&today) {today} else {today.wrapping_sub(1)};
+        let mut streak = 0;
+        while self.best_turns.contains_key(&day) {
+            streak += 1;
+            day = day.wrapping_sub(1);
+        }
+        streak
+    }
+
+    fn save(&self) {
+        let contents: String = self.best_turns.iter()
+            .map(|(day, turns)| format!("{} {}\n", day, turns))
+            .collect();
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).unwrap_or(());
+        }
+        if let Err(e) = fs::write(&self.path, contents) {
+            println!("could not save the daily history to {:?}: {}", self.path, e);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Daily {
+    pub day: u64,
+    pub level: level::Level,
+    // the number of turns it took, once the goal is reached
+    pub finished: Option<usize>,
+    // free building or the bot were used, so the turns aren't recorded
+    pub unranked: bool,
+    pub history: History,
+}
+
+impl Daily {
+    pub fn new(day: u64, history: History) -> Daily {
+        Daily {
+            day,
+            level: generate(day),
+            finished: None,
+            unranked: false,
+            history,
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.finished = None;
+        self.unranked = false;
+    }
+
+    // Call once per frame; records the result the first time the goal is met.
+    // `assisted` is whether the player is getting help right now.
+    pub fn update(&mut self, board: &Board, assisted: bool) {
+        if self.finished.is_none() {
+            self.unranked = self.unranked || assisted;
+            if let level::Outcome::Won(_) = self.level.outcome(board) {
+                self.finished = Some(board.turn);
+                if !self.unranked {
+                    self.history.record(self.day, board.turn);
+                }
+            }
+        }
+    }
+
+    pub fn draw(&self, ctx: &mut Context, font: Font, palette: &Palette, locale: &Locale, bindings: &Bindings, turn: usize) -> GameResult<()> {
+        let screen = layout::screen_size(ctx);
+        let streak = self.history.streak(self.day);
+        text::draw_centered_message(
+            ctx,
+            font,
+            &locale.format("daily.status", &[&format_day(self.day), &self.level.describe(locale), &self.finished.unwrap_or(turn), &streak]),
+            Vec2::new(screen.x / 2.0, 15.0),
+            palette.text,
+        )?;
+
+        if let Some(turns) = self.finished {
+            let center = Vec2::new(screen.x / 2.0, screen.y / 2.0);
+            Mesh::new_rectangle(
+                ctx,
+                DrawMode::fill(),
+                Rect::new(center.x - 180.0, center.y - 70.0, 360.0, 140.0),
+                Color::new(0.0, 0.0, 0.0, 0.75)
+            )?.draw(ctx, DrawParam::default())?;
+            let best = self.history.best_turns.get(&self.day).cloned().unwrap_or(turns);
+            text::draw_centered_message(ctx, font, &locale.get("daily.finished"), center + Vec2::new(0.0, -45.0), palette.accent)?;
+            text::draw_centered_message(
+                ctx,
+                font,
+                &locale.format("daily.turns", &[&locale.plural("turns", turns), &best]),
+                center + Vec2::new(0.0, -15.0),
+                palette.text,
+            )?;
+            text::draw_centered_message(
+                ctx,
+                font,
+                &locale.plural("daily.streak", streak),
+                center + Vec2::new(0.0, 10.0),
+                palette.text,
+            )?;
+            if self.unranked {
+                text::draw_centered_message(ctx, font, &locale.get("daily.unranked"), center + Vec2::new(0.0, 28.0), palette.warning)?;
+            }
+            let help = locale.format("daily.help", &[&bindings.describe(locale, Command::Reset), &bindings.describe(locale, Command::Daily)]);
+            text::draw_centered_message(ctx, font, &help, center + Vec2::new(0.0, 45.0), palette.help)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // If this changes, everyone's history and streaks point at other puzzles.
+    #[test]
+    fn the_puzzle_of_a_day_never_changes() {
+        let level = generate(18013);
+        assert_eq!(level.name, "Daily 2019-04-27");
+        assert_eq!(level.shape, hex::Shape {width: 10, height: 15});
+        assert_eq!(level.bounty, 14.0);
+        let moss: Vec<(i32, i32)> = level.moss.iter().map(|gift_point| (gift_point.hex_point.q, gift_point.hex_point.r)).collect();
+        assert_eq!(moss, vec!((-8, -2), (-4, 0), (-2, -4), (4, -8), (6, -10), (10, -10)));
+        assert_eq!(level.goals, vec!(Goal::LifeLevel(5)));
+        assert!(level.to_board().is_ok());
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use board::Board;
+use cell::{self, BRANCH_IMAGES};
+use globals::PI;
+use hex::{GiftPoint, VISIBLE_HEX_RADIUS};
+use palette::Palette;
+use glam::f32::Vec2;
+
+
+// Renders the tree as an SVG picture, cropped around it, without needing a
+// GPU. The images are embedded in the file, so that it can be shared on its
+// own; `read_file` reads them from paths such as "/twig.png".
+pub fn to_svg(board: &Board, palette: &Palette, read_file: &mut dyn FnMut(&str) -> Option<Vec<u8>>) -> Result<String, String> {
+    // sorted, so that exporting the same tree twice gives the same file
+    let mut branches: Vec<_> = board.branches.iter().collect();
+    branches.sort_by_key(|&(&branch_point, _)| branch_point);
+    let mut gifts: Vec<_> = board.gifts.iter()
+        .filter_map(|(gift_point, gift_cell)| gift_cell.gift.map(|gift| (gift_point, gift)))
+        .collect();
+    gifts.sort_by_key(|&(&gift_point, _)| gift_point);
+    let mut mosses: Vec<GiftPoint> = board.forbidden.iter()
+        .filter(|&(&gift_point, &forbidden)| forbidden && board.gift_children(gift_point).is_empty())
+        .map(|(&gift_point, _)| gift_point)
+        .collect();
+    mosses.sort();
+
+    let points: Vec<Vec2> = branches.iter().map(|&(branch_point, _)| branch_point.to_point())
+        .chain(gifts.iter().map(|&(gift_point, _)| gift_point.to_point()))
+        .collect();
+    if points.is_empty() {
+        return Err("there is no tree to export".to_string());
+    }
+    let min = points.iter().fold(points[0], |min, &point| min.min(point)) - Vec2::splat(VISIBLE_HEX_RADIUS * 2.0);
+    let max = points.iter().fold(points[0], |max, &point| max.max(point)) + Vec2::splat(VISIBLE_HEX_RADIUS * 2.0);
+    let size = max - min;
+
+    let mut svg = String::new();
+    let mut images = Images::new();
+    writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\">",
+        size.x.round(), size.y.round(), min.x, min.y, size.x, size.y,
+    ).unwrap();
+
+    let mut body = String::new();
+    // the game tints the branches black, and the moss with its palette colour
+    for (branch_point, branch_cell) in branches {
+        let path = BRANCH_IMAGES[branch_cell.branch_upgrade];
+        let (id, width, height) = images.get(path, read_file)?;
+        let point = branch_point.to_point();
+        writeln!(
+            body,
+            "<use xlink:href=\"#{}\" transform=\"translate({} {}) rotate({}) translate({} {})\" filter=\"url(#branch)\"/>",
+            id, point.x, point.y, cell::branch_angle(*branch_point) * 180.0 / PI, -width / 2.0, -height / 2.0,
+        ).unwrap();
+    }
+    for (gift_point, gift) in gifts {
+        let (id, width, height) = images.get(gift.image_path(), read_file)?;
+        let point = gift_point.to_point();
+        writeln!(body, "<use xlink:href=\"#{}\" x=\"{}\" y=\"{}\"/>", id, point.x - width / 2.0, point.y - height / 2.0).unwrap();
+    }
+    let (r, g, b) = palette.moss.to_rgb();
+    for gift_point in mosses {
+        let (id, width, height) = images.get("/moss.png", read_file)?;
+        let point = gift_point.to_point();
+        writeln!(body, "<use xlink:href=\"#{}\" x=\"{}\" y=\"{}\" filter=\"url(#moss)\"/>", id, point.x - width / 2.0, point.y - height / 2.0).unwrap();
+        writeln!(body, "<circle cx=\"{}\" cy=\"{}\" r=\"14\" fill=\"none\" stroke=\"#{:02x}{:02x}{:02x}\" stroke-width=\"2\"/>", point.x, point.y, r, g, b).unwrap();
+    }
+
+    writeln!(svg, "<defs>").unwrap();
+    writeln!(svg, "<filter id=\"branch\"><feColorMatrix type=\"matrix\" values=\"0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 1 0\"/></filter>").unwrap();
+    writeln!(
+        svg,
+        "<filter id=\"moss\"><feColorMatrix type=\"matrix\" values=\"{} 0 0 0 0 0 {} 0 0 0 0 0 {} 0 0 0 0 0 1 0\"/></filter>",
+        r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0,
+    ).unwrap();
+    svg.push_str(&images.defs);
+    writeln!(svg, "</defs>").unwrap();
+    svg.push_str(&body);
+    writeln!(svg, "</svg>").unwrap();
+    Ok(svg)
+}
+
+// Each image is embedded once, and then referred to by its id.
+struct Images {
+    ids: HashMap<String, (String, f32, f32)>,
+    defs: String,
+}
+
+impl Images {
+    fn new() -> Images {
+        Images {
+            ids: HashMap::new(),
+            defs: String::new(),
+        }
+    }
+
+    // the id, width and height of the image
+    fn get(&mut self, path: &str, read_file: &mut dyn FnMut(&str) -> Option<Vec<u8>>) -> Result<(String, f32, f32), String> {
+        if let Some(image) = self.ids.get(path) {
+            return Ok(image.clone());
+        }
+        let bytes = read_file(path).ok_or_else(|| format!("could not read {:?}", path))?;
+        let (width, height) = png_size(&bytes).ok_or_else(|| format!("{:?} is not a PNG image", path))?;
+        let id = format!("image{}", self.ids.len());
+        writeln!(
+            self.defs,
+            "<image id=\"{}\" width=\"{}\" height=\"{}\" xlink:href=\"data:image/png;base64,{}\"/>",
+            id, width, height, base64(&bytes),
+        ).unwrap();
+        let image = (id, width as f32, height as f32);
+        self.ids.insert(path.to_string(), image.clone());
+        Ok(image)
+    }
+}
+
+// from the IHDR chunk, which comes first
+fn png_size(bytes: &[u8]) -> Option<(u32, u32)> {
+    if bytes.len() < 24 || &bytes[..8] != b"\x89PNG\r\n\x1a\n" || &bytes[12..16] != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]);
+    let height = u32::from_be_bytes([bytes[20], bytes[21], bytes[22], bytes[23]]);
+    Some((width, height))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    use cell::Gift;
+    use hex;
+    use palette::Preset;
+
+    fn read_resource(path: &str) -> Option<Vec<u8>> {
+        fs::read(format!("resources{}", path)).ok()
+    }
+
+    #[test]
+    fn base64_matches_the_rfc() {
+        let cases = [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")];
+        for &(plain, encoded) in cases.iter() {
+            assert_eq!(base64(plain.as_bytes()), encoded);
+        }
+    }
+
+    #[test]
+    fn png_size_reads_the_header() {
+        assert_eq!(png_size(&read_resource("/moss.png").unwrap()).map(|(width, height)| width > 0 && height > 0), Some(true));
+        assert_eq!(png_size(b"GIF89a and then some more bytes"), None);
+        assert_eq!(png_size(b"\x89PNG"), None);
+    }
+
+    #[test]
+    fn each_image_is_embedded_once() {
+        let mut board = Board::new();
+        for &(q, r) in &[(0, -1), (-1, -2), (1, -3)] {
+            board.insert_branch(hex::BranchPoint::new(hex::HexPoint::new(q, r)), 0).unwrap();
+        }
+        board.set_gift(hex::GiftPoint::new(hex::HexPoint::new(-2, -2)), Some(Gift::Leaves));
+        board.set_gift(hex::GiftPoint::new(hex::HexPoint::new(2, -4)), Some(Gift::Leaves));
+        let palette = Palette::new(Preset::Default);
+
+        let svg = to_svg(&board, &palette, &mut read_resource).unwrap();
+        assert!(svg.starts_with("<svg "));
+        // the twigs, the root and the leaves
+        assert_eq!(svg.matches("<image ").count(), 3);
+        assert_eq!(svg.matches("<use ").count(), 6);
+        assert_eq!(to_svg(&board, &palette, &mut read_resource).unwrap(), svg);
+
+        assert_eq!(to_svg(&board, &palette, &mut |_| None), Err("could not read \"/twig.png\"".to_string()));
+    }
+}
@@ -8,14 +8,15 @@ pub const GAME_NAME: &str = "ludum-dare-44";
 pub const WINDOW_WIDTH:  f32 = 800.0;
 pub const WINDOW_HEIGHT: f32 = 600.0;
 
+// at the normal turn speed, see settings::TURN_SPEEDS
+pub const TURN_DURATION: Duration = Duration::from_millis(2000);
+
 
 #[allow(dead_code)]
 pub const PI:     f32 = 3.141592653589793;
 pub const SQRT_3: f32 = 1.7320508075688772;
 
 pub const MAX_BOUNTY: f32 = 300.0;
-pub const THRESHOLD1: f32 = 36.0 / 2.0;
-//pub const THRESHOLD2: f32 = 3.0 * 36.0 / 4.0;
 
 pub fn get_current_time(ctx: &Context) -> Duration {
     timer::time_since_start(ctx)
@@ -0,0 +1,74 @@
+use std::fmt;
+
+use board::Board;
+use cell::Gift;
+use locale::Locale;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Goal {
+    LifeLevel(usize),
+    Bounty(f32),
+    Creature(Gift, usize),
+}
+
+impl Goal {
+    // "life 5", "bounty 300", "creature squirrel" or "creature beehive 3"
+    pub fn parse(s: &str) -> Option<Goal> {
+        let s = s.trim();
+        let (kind, arg) = match s.find(' ') {
+            Some(i) => (&s[..i], s[i..].trim()),
+            None    => (s, ""),
+        };
+        match kind {
+            "life"     => arg.parse().ok().map(Goal::LifeLevel),
+            "bounty"   => arg.parse().ok().map(Goal::Bounty),
+            "creature" => {
+                let (name, count) = match arg.rfind(' ').and_then(|i| arg[i..].trim().parse().ok().map(|n| (i, n))) {
+                    Some((i, count)) => (&arg[..i], count),
+                    None             => (arg, 1),
+                };
+                Gift::from_name(name).map(|gift| Goal::Creature(gift, count))
+            },
+            _          => None,
+        }
+    }
+
+    pub fn presets() -> Vec<Goal> {
+        vec!(
+            Goal::LifeLevel(5),
+            Goal::LifeLevel(10),
+            Goal::Bounty(300.0),
+            Goal::Creature(Gift::Beehive, 1),
+            Goal::Creature(Gift::Squirrel, 1),
+        )
+    }
+
+    pub fn reached(self, board: &Board) -> bool {
+        match self {
+            Goal::LifeLevel(level)      => board.stats.life_max >= level,
+            Goal::Bounty(amount)        => board.bounty_amount >= amount,
+            Goal::Creature(gift, count) => board.stats.gift_count(gift) >= count,
+        }
+    }
+
+    pub fn describe(self, locale: &Locale) -> String {
+        match self {
+            Goal::LifeLevel(level)      => locale.format("goal.life", &[&level]), // Life is called Bounty in the UI
+            Goal::Bounty(amount)        => locale.format("goal.bounty", &[&amount]), // and vice versa
+            Goal::Creature(gift, count) => locale.format("goal.creature", &[&locale.gifts(gift, count)]),
+        }
+    }
+}
+
+// The same syntax as `Goal::parse`, used as the key in the leaderboard file.
+impl fmt::Display for Goal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Goal::LifeLevel(level)      => write!(f, "life {}", level),
+            Goal::Bounty(amount)        => write!(f, "bounty {}", amount),
+            Goal::Creature(gift, 1)     => write!(f, "creature {}", gift.id()),
+            Goal::Creature(gift, count) => write!(f, "creature {} {}", gift.id(), count),
+        }
+    }
+}
@@ -1,10 +1,11 @@
 
-use core::ops::{Add,AddAssign,Mul,MulAssign};
+use core::ops::{Add,AddAssign,Mul,MulAssign,Sub};
 use ggez::{GameResult, Context};
 use ggez::graphics::{Color, Drawable, DrawMode, DrawParam, Mesh};
 use glam::f32::Vec2;
 
 use globals::*;
+use palette::Palette;
 
 
 pub const HEX_RADIUS:         f32 = 16.0;
@@ -15,6 +16,8 @@ pub const VISIBLE_HEX_RADIUS: f32 = HEX_RADIUS * 2.0;
 pub const VISIBLE_HEX_WIDTH:  f32 = HEX_WIDTH  * 2.0;
 pub const VISIBLE_HEX_HEIGHT: f32 = HEX_HEIGHT * 2.0;
 
+// The board's own coordinates, in which the trunk is where it was in the
+// original 800x600 window; see layout::Layout for where it ends up.
 pub const ORIGIN_X:           f32 = WINDOW_WIDTH as f32 / 2.0;
 pub const ORIGIN_Y:           f32 = WINDOW_HEIGHT as f32 - 70.0;
 
@@ -36,7 +39,8 @@ fn load_polygon_asset(ctx: &mut Context, mode: DrawMode) -> GameResult<Mesh> {
             Vec2::new(-VISIBLE_HEX_WIDTH / 2.0, 0.0),
             Vec2::new(-0.5 * VISIBLE_HEX_RADIUS, VISIBLE_HEX_HEIGHT / 2.0),
         ],
-        Color::from_rgb(163, 186, 188)
+        // tinted by the palette when drawn
+        Color::WHITE
     )
 }
 
@@ -46,14 +50,15 @@ pub fn load_assets(ctx: &mut Context) -> GameResult<Assets> {
     })
 }
 
-pub fn draw_hex_grid(ctx: &mut Context, assets: &Assets) -> GameResult<()> {
+pub fn draw_hex_grid(ctx: &mut Context, assets: &Assets, shape: Shape, palette: &Palette) -> GameResult<()> {
     for q in -10..=10 {
         for r in -20..=0 {
-            if let Some(InBoundsPoint::GiftPoint(gift_point)) = HexPoint::new(q, r).is_in_bounds() {
+            if let Some(InBoundsPoint::GiftPoint(gift_point)) = shape.is_in_bounds(HexPoint::new(q, r)) {
                 assets.hex.draw(
                     ctx,
                     DrawParam::default()
                         .dest(gift_point.to_point())
+                        .color(palette.hex_grid)
                 )?;
             }
         }
@@ -65,7 +70,7 @@ pub fn draw_hex_grid(ctx: &mut Context, assets: &Assets) -> GameResult<()> {
 
 // using "flat-topped axial coordinates":
 // https://www.redblobgames.com/grids/hexagons/#coordinates-axial
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct HexPoint {
     pub q: i32,
     pub r: i32,
@@ -77,12 +82,12 @@ pub struct HexVector {
     pub r: i32,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct BranchPoint {
     pub hex_point: HexPoint,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct GiftPoint {
     pub hex_point: HexPoint,
 }
@@ -93,6 +98,14 @@ pub enum InBoundsPoint {
     GiftPoint(GiftPoint),
 }
 
+// The part of the grid in which a tree may grow: at most `width` columns on
+// either side of the trunk, and at most `height` half-rows above it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Shape {
+    pub width: i32,
+    pub height: i32,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Orientation {
     Vert,     // |
@@ -119,6 +132,17 @@ impl AddAssign<HexVector> for HexPoint {
     }
 }
 
+impl Sub for HexPoint {
+    type Output = HexVector;
+
+    fn sub(self, other: HexPoint) -> HexVector {
+        HexVector::new(
+            self.q - other.q,
+            self.r - other.r,
+        )
+    }
+}
+
 impl Add for HexVector {
     type Output = HexVector;
 
@@ -173,8 +197,14 @@ impl HexPoint {
         self.q % 2 == 0 && self.r % 2 == 0
     }
 
+    // like y(), but with the cells in every other column counted as if they
+    // were aligned with their neighbours
+    fn height(self) -> i32 {
+        -self.y() - (if (self.q + 100) % 4 == 2 {1} else {0})
+    }
+
     pub fn is_in_bounds(self) -> Option<InBoundsPoint> {
-        if self.r <= 0 && self.s() >= 0 && self.q >= -10 && self.q <= 10 && self.y() + (if (self.q + 100) % 4 == 2 {1} else {0}) >= -33 && self.s() < 21 {
+        if self.r <= 0 && self.s() >= 0 && self.q >= -10 && self.q <= 10 && self.height() <= 33 && self.s() < 21 {
             if self.would_be_cell_center() {
                 Some(InBoundsPoint::GiftPoint(GiftPoint::new(self)))
             } else {
@@ -216,6 +246,22 @@ impl HexPoint {
     }
 }
 
+impl Shape {
+    pub const FULL: Shape = Shape {width: 10, height: 33};
+
+    pub fn contains(self, hex_point: HexPoint) -> bool {
+        hex_point.q.abs() <= self.width && hex_point.height() <= self.height
+    }
+
+    pub fn is_in_bounds(self, hex_point: HexPoint) -> Option<InBoundsPoint> {
+        if self.contains(hex_point) {
+            hex_point.is_in_bounds()
+        } else {
+            None
+        }
+    }
+}
+
 // 0..6
 pub type DirectionIndex = i32;
 
@@ -237,6 +283,16 @@ impl HexVector {
         }
     }
 
+    // a sixth of a turn around the origin
+    pub fn rotate(self) -> HexVector {
+        HexVector::new(-self.r, self.q + self.r)
+    }
+
+    // left to right
+    pub fn mirror(self) -> HexVector {
+        HexVector::new(-self.q, self.q + self.r)
+    }
+
     #[allow(dead_code)]
     pub fn to_vector(self) -> Vec2 {
         Vec2::new(
@@ -0,0 +1,284 @@
+use ggez::{GameResult, Context};
+use ggez::graphics::{Color, DrawMode, DrawParam, Drawable, Font, Mesh, MeshBuilder, Rect, Text, TextFragment};
+use glam::f32::Vec2;
+use std::fmt::Write;
+
+use bindings::{Bindings, Command};
+use board::{ActionKind, Board};
+use cell::Gift;
+use layout;
+use locale::Locale;
+use palette::{Marker, Palette};
+use text;
+
+
+// The state of the board at the end of a turn.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub turn: usize,
+    // called Life in the UI
+    pub bounty_amount: f32,
+    // called Bounty in the UI; the life produced during the turn
+    pub life_amount: f32,
+    // in the order of Gift::all()
+    pub gift_counts: Vec<usize>,
+}
+
+// The name, colour, marker and values by turn of one line of a graph.
+type Series = (String, Color, Marker, Vec<(usize, f32)>);
+
+// how many markers a line gets at most
+const MARKERS_PER_LINE: usize = 12;
+const MARKER_RADIUS: f32 = 5.0;
+
+// What happened during the current game, turn by turn, for the statistics
+// screen: the amounts, the gift counts, and when the player acted.
+#[derive(Debug, Default)]
+pub struct History {
+    pub samples: Vec<Sample>,
+    pub actions: Vec<(usize, ActionKind)>,
+}
+
+impl Sample {
+    pub fn new(board: &Board) -> Sample {
+        Sample {
+            turn: board.turn,
+            bounty_amount: board.bounty_amount,
+            life_amount: board.life_amount,
+            gift_counts: Gift::all().into_iter().map(|gift| board.stats.gift_count(gift)).collect(),
+        }
+    }
+}
+
+impl History {
+    pub fn new() -> History {
+        History::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.actions.clear();
+    }
+
+    // The latest sample of a turn replaces the earlier ones, so this can be
+    // called every frame.
+    pub fn record(&mut self, board: &Board) {
+        let sample = Sample::new(board);
+        match self.samples.last_mut() {
+            Some(last) if last.turn == sample.turn => *last = sample,
+            _                                      => self.samples.push(sample),
+        }
+    }
+
+    pub fn record_action(&mut self, turn: usize, action_kind: ActionKind) {
+        self.actions.push((turn, action_kind));
+    }
+
+    // One row per turn, with the actions of that turn in the last column.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("turn,bounty_amount,life_amount");
+        for gift in Gift::all() {
+            write!(csv, ",{}", gift.plural_id()).unwrap();
+        }
+        csv.push_str(",actions\n");
+        for sample in self.samples.iter() {
+            write!(csv, "{},{:.2},{:.2}", sample.turn, sample.bounty_amount, sample.life_amount).unwrap();
+            for count in sample.gift_counts.iter() {
+                write!(csv, ",{}", count).unwrap();
+            }
+            let actions: Vec<&str> = self.actions.iter()
+                .filter(|&&(turn, _)| turn == sample.turn)
+                .map(|&(_, action_kind)| action_kind.name())
+                .collect();
+            writeln!(csv, ",{}", actions.join(" ")).unwrap();
+        }
+        csv
+    }
+
+    // The amounts on top, with a tick underneath for each action, and the
+    // gift counts below.
+    pub fn draw(&self, ctx: &mut Context, font: Font, palette: &Palette, locale: &Locale, bindings: &Bindings) -> GameResult<()> {
+        let screen = layout::screen_size(ctx);
+        Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(0.0, 0.0, screen.x, screen.y),
+            Color::new(0.0, 0.0, 0.0, 0.8)
+        )?.draw(ctx, DrawParam::default())?;
+        text::draw_centered_message(ctx, font, &locale.get("stats.title"), Vec2::new(screen.x / 2.0, 30.0), palette.accent)?;
+        let help = locale.format("stats.help", &[
+            &bindings.describe(locale, Command::ExportStats),
+            &bindings.describe(locale, Command::Stats),
+            &bindings.describe(locale, Command::Back),
+        ]);
+        text::draw_centered_message(ctx, font, &help, Vec2::new(screen.x / 2.0, screen.y - 20.0), palette.help)?;
+        let (first, last) = match (self.samples.first(), self.samples.last()) {
+            (Some(first), Some(last)) if last.turn > first.turn => (first.turn, last.turn),
+            _ => return text::draw_centered_message(ctx, font, &locale.get("stats.empty"), screen / 2.0, palette.disabled),
+        };
+        let turns = (first, last);
+
+        let graph_height = (screen.y - 220.0) / 2.0;
+        let amounts = Rect::new(60.0, 80.0, screen.x - 120.0, graph_height);
+        let gifts = Rect::new(60.0, amounts.bottom() + 100.0, screen.x - 120.0, graph_height);
+
+        // as in the sidebars
+        draw_graph(ctx, font, palette, amounts, turns, &[
+            (locale.get("sidebar.life"), palette.meter_bounty, Marker::Diamond, self.samples.iter().map(|sample| (sample.turn, sample.bounty_amount)).collect()),
+            (locale.get("sidebar.bounty"), palette.meter_life, Marker::Circle, self.samples.iter().map(|sample| (sample.turn, sample.life_amount)).collect()),
+        ])?;
+        let gift_series: Vec<Series> = Gift::all().into_iter().enumerate()
+            .map(|(i, gift)| (
+                locale.gift_name(gift),
+                palette.gift(gift),
+                Marker::for_gift(gift),
+                self.samples.iter().map(|sample| (sample.turn, sample.gift_counts[i] as f32)).collect(),
+            ))
+            .collect();
+        draw_graph(ctx, font, palette, gifts, turns, &gift_series)?;
+
+        // the actions, between the two graphs
+        let action_color = |action_kind: ActionKind| match action_kind {
+            ActionKind::Place | ActionKind::Upgrade => palette.accent,
+            ActionKind::Prune                       => palette.warning,
+            ActionKind::Moss                        => palette.moss,
+        };
+        if !self.actions.is_empty() {
+            let mut builder = MeshBuilder::new();
+            for &(turn, action_kind) in self.actions.iter().filter(|&&(turn, _)| turn >= first && turn <= last) {
+                let x = turn_x(amounts, turns, turn);
+                builder.line(
+                    &[Vec2::new(x, amounts.bottom() + 4.0), Vec2::new(x, amounts.bottom() + 14.0)],
+                    2.0,
+                    action_color(action_kind),
+                )?;
+            }
+            builder.build(ctx)?.draw(ctx, DrawParam::default())?;
+        }
+        let legend: Vec<(String, Color, Option<Marker>)> = ActionKind::all().into_iter()
+            .filter(|&action_kind| action_kind != ActionKind::Upgrade)
+            .map(|action_kind| (locale.get(&format!("stats.action.{}", action_kind.name())), action_color(action_kind), None))
+            .collect();
+        draw_legend(ctx, font, Vec2::new(amounts.x, amounts.bottom() + 40.0), &legend)
+    }
+}
+
+fn turn_x(rect: Rect, (first, last): (usize, usize), turn: usize) -> f32 {
+    rect.x + rect.w * (turn - first) as f32 / (last - first) as f32
+}
+
+fn add_marker(builder: &mut MeshBuilder, marker: Marker, center: Vec2, color: Color) -> GameResult<()> {
+    for stroke in marker.strokes() {
+        let points: Vec<Vec2> = stroke.into_iter().map(|point| center + point * MARKER_RADIUS).collect();
+        builder.line(&points, 2.0, color)?;
+    }
+    Ok(())
+}
+
+// The names in their colours, side by side, after their markers.
+fn draw_legend(ctx: &mut Context, font: Font, dest: Vec2, entries: &[(String, Color, Option<Marker>)]) -> GameResult<()> {
+    let mut x = dest.x;
+    for &(ref name, color, marker) in entries {
+        if let Some(marker) = marker {
+            let mut builder = MeshBuilder::new();
+            add_marker(&mut builder, marker, Vec2::new(x + MARKER_RADIUS, dest.y + 8.0), color)?;
+            builder.build(ctx)?.draw(ctx, DrawParam::default())?;
+            x += 2.0 * MARKER_RADIUS + 6.0;
+        }
+        let text = Text::new(TextFragment::new(name.as_str()).font(font));
+        text.draw(ctx, DrawParam::default().dest(Vec2::new(x, dest.y)).color(color))?;
+        x += text.width(ctx) + 20.0;
+    }
+    Ok(())
+}
+
+// Line graphs over the same turns, scaled to the highest value, with their
+// names above. Each line has a few markers along it, shifted from one line to
+// the next so that overlapping lines can still be told apart.
+fn draw_graph(
+    ctx: &mut Context,
+    font: Font,
+    palette: &Palette,
+    rect: Rect,
+    turns: (usize, usize),
+    series: &[Series],
+) -> GameResult<()> {
+    let legend: Vec<(String, Color, Option<Marker>)> = series.iter().map(|(name, color, marker, _)| (name.clone(), *color, Some(*marker))).collect();
+    draw_legend(ctx, font, Vec2::new(rect.x, rect.y - 24.0), &legend)?;
+    Mesh::new_rectangle(ctx, DrawMode::stroke(1.0), rect, palette.disabled)?.draw(ctx, DrawParam::default())?;
+
+    let top = series.iter()
+        .flat_map(|(_, _, _, values)| values.iter().map(|&(_, value)| value))
+        .fold(1.0, f32::max)
+        .ceil();
+    let label = |value: String| Text::new(TextFragment::new(value).font(font));
+    label(format!("{}", top)).draw(ctx, DrawParam::default().dest(Vec2::new(rect.x - 50.0, rect.y)).color(palette.text))?;
+    label("0".to_string()).draw(ctx, DrawParam::default().dest(Vec2::new(rect.x - 50.0, rect.bottom() - 16.0)).color(palette.text))?;
+    label(format!("{}", turns.0)).draw(ctx, DrawParam::default().dest(Vec2::new(rect.x, rect.bottom() + 20.0)).color(palette.text))?;
+    let last = label(format!("{}", turns.1));
+    let last_width = last.width(ctx);
+    last.draw(ctx, DrawParam::default().dest(Vec2::new(rect.right() - last_width, rect.bottom() + 20.0)).color(palette.text))?;
+
+    let mut builder = MeshBuilder::new();
+    let mut empty = true;
+    for (i, &(_, color, marker, ref values)) in series.iter().enumerate() {
+        let points: Vec<Vec2> = values.iter()
+            .map(|&(turn, value)| Vec2::new(turn_x(rect, turns, turn), rect.bottom() - value.max(0.0) / top * rect.h))
+            .collect();
+        if points.len() >= 2 {
+            builder.line(&points, 2.0, color)?;
+            let step = points.len().div_ceil(MARKERS_PER_LINE);
+            for &point in points.iter().skip(i % step).step_by(step) {
+                add_marker(&mut builder, marker, point, color)?;
+            }
+            empty = false;
+        }
+    }
+    if !empty {
+        builder.build(ctx)?.draw(ctx, DrawParam::default())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex;
+
+    #[test]
+    fn one_sample_per_turn() {
+        let mut history = History::new();
+        let mut board = Board::new();
+        history.record(&board);
+        board.bounty_amount = 1.5;
+        history.record(&board);
+        board.next_turn();
+        history.record(&board);
+        assert_eq!(history.samples.iter().map(|sample| sample.turn).collect::<Vec<_>>(), vec!(0, 1));
+        assert_eq!(history.samples[0].bounty_amount, 1.5);
+        assert_eq!(history.samples[0].gift_counts.len(), Gift::all().len());
+    }
+
+    #[test]
+    fn the_csv_has_a_row_per_turn_with_its_actions() {
+        let mut history = History::new();
+        let mut board = Board::new();
+        board.bounty_amount = 2.0;
+        board.set_gift(hex::GiftPoint::new(hex::HexPoint::new(0, 0)), Some(Gift::Leaves));
+        board.update_stats();
+        history.record(&board);
+        history.record_action(0, ActionKind::Place);
+        history.record_action(0, ActionKind::Moss);
+        board.turn = 1;
+        history.record(&board);
+
+        let csv = history.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "turn,bounty_amount,life_amount,leaves,flowers,berries,nuts,beehives,bird nests,squirrels,actions");
+        assert!(lines[1].starts_with("0,2.00,"), "{}", lines[1]);
+        assert!(lines[1].ends_with(",place moss"), "{}", lines[1]);
+        assert!(lines[2].starts_with("1,2.00,"), "{}", lines[2]);
+        assert!(lines[2].ends_with(","), "{}", lines[2]);
+        assert_eq!(lines.len(), 3);
+    }
+}
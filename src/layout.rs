@@ -0,0 +1,90 @@
+use ggez::{GameResult, Context};
+use ggez::graphics::{self, Rect};
+use glam::f32::Vec2;
+
+use globals::*;
+use hex::{ORIGIN_X, ORIGIN_Y};
+use sidebar::SIDEBAR_WIDTH;
+
+
+// Where things go in a window of any size. Everything is drawn in logical
+// units, which are pixels at the original 800x600 size on a normal display:
+// a high-DPI display has more pixels per unit, and a window smaller than
+// 800x600 is shrunk rather than cropped. The UI is anchored to the edges of
+// the screen, while the board is scaled to fill the space between the
+// sidebars, keeping the trunk at the bottom.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    // in logical units, at least WINDOW_WIDTH x WINDOW_HEIGHT
+    pub width: f32,
+    pub height: f32,
+    // physical pixels per logical unit
+    pub scale: f32,
+    // logical units per board unit, as used by HexPoint::to_point
+    pub board_scale: f32,
+    // where the board's (ORIGIN_X, ORIGIN_Y) ends up on the screen
+    pub board_origin: Vec2,
+}
+
+impl Layout {
+    // The size is in physical pixels, and the scale factor is the one the OS
+    // picked for the display, e.g. 2.0 on most high-DPI displays.
+    pub fn new(pixel_width: f32, pixel_height: f32, scale_factor: f32) -> Layout {
+        let scale = scale_factor
+            .min(pixel_width / WINDOW_WIDTH)
+            .min(pixel_height / WINDOW_HEIGHT)
+            .max(0.1);
+        let width = pixel_width / scale;
+        let height = pixel_height / scale;
+        let board_scale = ((width - 2.0 * SIDEBAR_WIDTH) / (WINDOW_WIDTH - 2.0 * SIDEBAR_WIDTH))
+            .min(height / WINDOW_HEIGHT);
+        Layout {
+            width,
+            height,
+            scale,
+            board_scale,
+            board_origin: Vec2::new(
+                width / 2.0,
+                height - (WINDOW_HEIGHT - ORIGIN_Y) * board_scale,
+            ),
+        }
+    }
+
+    pub fn from_window(ctx: &Context) -> Layout {
+        let (pixel_width, pixel_height) = graphics::drawable_size(ctx);
+        Layout::new(pixel_width, pixel_height, graphics::window(ctx).scale_factor() as f32)
+    }
+
+    // Draw in logical units, e.g. for the UI.
+    pub fn use_screen_coordinates(&self, ctx: &mut Context) -> GameResult<()> {
+        graphics::set_screen_coordinates(ctx, Rect::new(0.0, 0.0, self.width, self.height))
+    }
+
+    // Draw in board units, e.g. at HexPoint::to_point.
+    pub fn use_board_coordinates(&self, ctx: &mut Context) -> GameResult<()> {
+        let top_left = self.screen_to_board(Vec2::new(0.0, 0.0));
+        graphics::set_screen_coordinates(
+            ctx,
+            Rect::new(top_left.x, top_left.y, self.width / self.board_scale, self.height / self.board_scale),
+        )
+    }
+
+    // from the physical pixels of the mouse events
+    pub fn pixel_to_screen(&self, x: f32, y: f32) -> Vec2 {
+        Vec2::new(x, y) / self.scale
+    }
+
+    pub fn screen_to_board(&self, point: Vec2) -> Vec2 {
+        Vec2::new(ORIGIN_X, ORIGIN_Y) + (point - self.board_origin) / self.board_scale
+    }
+
+    pub fn board_to_screen(&self, point: Vec2) -> Vec2 {
+        self.board_origin + (point - Vec2::new(ORIGIN_X, ORIGIN_Y)) * self.board_scale
+    }
+}
+
+// The size of the screen in logical units, for the UI to anchor to.
+pub fn screen_size(ctx: &Context) -> Vec2 {
+    let rect = graphics::screen_coordinates(ctx);
+    Vec2::new(rect.w, rect.h)
+}
@@ -0,0 +1,536 @@
+use ggez::{GameResult, Context};
+use ggez::graphics::*;
+use glam::f32::Vec2;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use bindings::{Bindings, Command};
+use board::{ActionKind, Board};
+use cell::Gift;
+use layout;
+use locale::Locale;
+use palette::Palette;
+use goal::Goal;
+use hex;
+use text;
+
+
+pub const SELECT_TOP:     f32 = 120.0;
+pub const SELECT_SPACING: f32 = 30.0;
+
+
+// A puzzle: a pre-grown tree, some constraints, and a win condition. The file
+// format is one directive per line, e.g.
+//
+//     name First leaves
+//     hint Leaves grow on branch tips which have room around them
+//     shape 4 12          # columns on each side of the trunk, half-rows up
+//     bounty 5
+//     branch -1 0         # q r [thickness], attached to the tree in order
+//     gift -2 -2 leaves   # q r gift, on the tip of a branch
+//     moss 2 -2           # q r
+//     lock prune          # place, upgrade, prune or moss
+//     goal creature leaf 2
+//     max_branches 6      # at the moment the goals are reached
+//     turns 20            # lose after this many turns
+//     stars 10 6          # the turns needed for two and three stars
+#[derive(Clone, Debug)]
+pub struct Level {
+    pub id: String,
+    pub name: String,
+    pub hint: String,
+    pub shape: hex::Shape,
+    pub bounty: f32,
+    pub branches: Vec<(hex::BranchPoint, usize)>,
+    pub gifts: Vec<(hex::GiftPoint, Gift)>,
+    pub moss: Vec<hex::GiftPoint>,
+    pub locked: Vec<ActionKind>,
+    pub goals: Vec<Goal>,
+    pub max_branches: Option<usize>,
+    pub turn_limit: Option<usize>,
+    pub star_turns: Vec<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Playing,
+    Won(usize), // stars
+    Lost,
+}
+
+// as counted by `max_branches`
+pub fn branch_count(board: &Board) -> usize {
+    board.stats.branch_lv1_count + board.stats.branch_lv2_count
+}
+
+fn parse_hex_point(words: &[&str]) -> Option<hex::HexPoint> {
+    if words.len() < 2 {
+        return None;
+    }
+    Some(hex::HexPoint::new(words[0].parse().ok()?, words[1].parse().ok()?))
+}
+
+impl Level {
+    pub fn new(id: &str) -> Level {
+        Level {
+            id: id.to_string(),
+            name: id.to_string(),
+            hint: String::new(),
+            shape: hex::Shape::FULL,
+            bounty: 5.0,
+            branches: Vec::new(),
+            gifts: Vec::new(),
+            moss: Vec::new(),
+            locked: Vec::new(),
+            goals: Vec::new(),
+            max_branches: None,
+            turn_limit: None,
+            star_turns: Vec::new(),
+        }
+    }
+
+    pub fn parse(id: &str, contents: &str) -> Result<Level, String> {
+        let mut level = Level::new(id);
+        for (i, line) in contents.lines().enumerate() {
+            let line = match line.find('#') {
+                Some(j) => &line[..j],
+                None    => line,
+            }.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let rest = line[words[0].len()..].trim();
+            let error = || format!("{}, line {}: could not parse {:?}", id, i + 1, line);
+            match words[0] {
+                "name" => level.name = rest.to_string(),
+                "hint" => level.hint = rest.to_string(),
+                "shape" => {
+                    let width = words.get(1).and_then(|w| w.parse().ok()).ok_or_else(error)?;
+                    let height = words.get(2).and_then(|w| w.parse().ok()).ok_or_else(error)?;
+                    level.shape = hex::Shape {width, height};
+                },
+                "bounty" => level.bounty = rest.parse().map_err(|_| error())?,
+                "branch" => {
+                    let hex_point = parse_hex_point(&words[1..]).ok_or_else(error)?;
+                    let branch_upgrade = match words.get(3) {
+                        Some(w) => w.parse().map_err(|_| error())?,
+                        None    => 0,
+                    };
+                    level.branches.push((hex::BranchPoint::new(hex_point), branch_upgrade));
+                },
+                "gift" => {
+                    let hex_point = parse_hex_point(&words[1..]).ok_or_else(error)?;
+                    let gift = Gift::from_name(&words[3..].join(" ")).ok_or_else(error)?;
+                    level.gifts.push((hex::GiftPoint::new(hex_point), gift));
+                },
+                "moss" => {
+                    let hex_point = parse_hex_point(&words[1..]).ok_or_else(error)?;
+                    level.moss.push(hex::GiftPoint::new(hex_point));
+                },
+                "lock" => level.locked.push(ActionKind::from_name(rest).ok_or_else(error)?),
+                "goal" => level.goals.push(Goal::parse(rest).ok_or_else(error)?),
+                "max_branches" => level.max_branches = Some(rest.parse().map_err(|_| error())?),
+                "turns" => level.turn_limit = Some(rest.parse().map_err(|_| error())?),
+                "stars" => {
+                    level.star_turns = words[1..].iter()
+                        .map(|w| w.parse().map_err(|_| error()))
+                        .collect::<Result<Vec<usize>, String>>()?;
+                },
+                _ => return Err(error()),
+            }
+        }
+        Ok(level)
+    }
+
+    pub fn load(path: &Path) -> Result<Level, String> {
+        let id = path.file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", id, e))?;
+        Level::parse(&id, &contents)
+    }
+
+    pub fn to_board(&self) -> Result<Board, String> {
+        let mut board = Board::new();
+        board.shape = self.shape;
+        board.bounty_amount = self.bounty;
+        board.locked = self.locked.clone();
+        for &(branch_point, branch_upgrade) in self.branches.iter() {
+            board.insert_branch(branch_point, branch_upgrade)
+                .map_err(|_| format!("{}: branch {:?} is not attached to the tree", self.id, branch_point.hex_point))?;
+        }
+        let is_tip = |board: &Board, gift_point| board.gifts.contains_key(&gift_point) && board.gift_children(gift_point).is_empty();
+        for &(gift_point, gift) in self.gifts.iter() {
+            if !is_tip(&board, gift_point) {
+                return Err(format!("{}: gift {:?} is not on a branch tip", self.id, gift_point.hex_point));
+            }
+            board.set_gift(gift_point, Some(gift));
+        }
+        // moss also keeps empty cells from growing, but as in the game, only
+        // the tips of the tree get any
+        for &gift_point in self.moss.iter() {
+            let is_cell = matches!(self.shape.is_in_bounds(gift_point.hex_point), Some(hex::InBoundsPoint::GiftPoint(_)));
+            if !is_cell || (board.gifts.contains_key(&gift_point) && !is_tip(&board, gift_point)) {
+                return Err(format!("{}: moss {:?} is neither on an empty cell nor on a branch tip", self.id, gift_point.hex_point));
+            }
+            board.forbidden.insert(gift_point, true);
+        }
+        board.update_stats();
+        Ok(board)
+    }
+
+    // The reverse of `to_board`: captures the tree, gifts and moss of a board
+    // built in the sandbox, without any goals.
+    pub fn from_board(id: &str, board: &Board) -> Level {
+        let mut level = Level::new(id);
+        level.shape = board.shape;
+        level.bounty = board.bounty_amount.floor();
+
+        // parents must be listed before their children
+        let depth = |branch_point| {
+            let mut depth = 0;
+            let mut current = branch_point;
+            while let Some(parent) = board.branch_parent_branch(current) {
+                depth += 1;
+                current = parent;
+            }
+            depth
+        };
+        let mut branches: Vec<(hex::BranchPoint, usize)> = board.branches.iter()
+            .filter(|&(&branch_point, _)| branch_point != board.root_point)
+            .map(|(&branch_point, branch_cell)| (branch_point, branch_cell.branch_upgrade))
+            .collect();
+        branches.sort_by_key(|&(branch_point, _)| (depth(branch_point), branch_point));
+        level.branches = branches;
+
+        let mut gifts: Vec<(hex::GiftPoint, Gift)> = board.gifts.iter()
+            .filter_map(|(&gift_point, gift_cell)| gift_cell.gift.map(|gift| (gift_point, gift)))
+            .collect();
+        gifts.sort_by_key(|&(gift_point, _)| gift_point);
+        level.gifts = gifts;
+
+        let mut moss: Vec<hex::GiftPoint> = board.gifts.keys()
+            .copied()
+            .filter(|&gift_point| board.is_moss(gift_point))
+            .collect();
+        moss.sort();
+        level.moss = moss;
+
+        level.locked = board.locked.clone();
+        level
+    }
+
+    // The translations which the other languages must provide.
+    #[cfg(test)]
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys = vec!(format!("level.{}.name", self.id));
+        if !self.hint.is_empty() {
+            keys.push(format!("level.{}.hint", self.id));
+        }
+        keys
+    }
+
+    pub fn localized_name(&self, locale: &Locale) -> String {
+        locale.get_or(&format!("level.{}.name", self.id), &self.name)
+    }
+
+    pub fn localized_hint(&self, locale: &Locale) -> String {
+        locale.get_or(&format!("level.{}.hint", self.id), &self.hint)
+    }
+
+    pub fn describe(&self, locale: &Locale) -> String {
+        let mut parts: Vec<String> = self.goals.iter().map(|goal| goal.describe(locale)).collect();
+        if let Some(max_branches) = self.max_branches {
+            parts.push(locale.plural("level.max_branches", max_branches));
+        }
+        if let Some(turn_limit) = self.turn_limit {
+            parts.push(locale.plural("level.turn_limit", turn_limit));
+        }
+        parts.join(", ")
+    }
+
+    pub fn stars(&self, turns: usize) -> usize {
+        1 + self.star_turns.iter().filter(|&&limit| turns <= limit).count()
+    }
+
+    pub fn max_stars(&self) -> usize {
+        1 + self.star_turns.len()
+    }
+
+    pub fn outcome(&self, board: &Board) -> Outcome {
+        let won = self.goals.iter().all(|goal| goal.reached(board))
+            && self.max_branches.is_none_or(|max_branches| branch_count(board) <= max_branches);
+        if won {
+            Outcome::Won(self.stars(board.turn))
+        } else if self.turn_limit.is_some_and(|turn_limit| board.turn >= turn_limit) {
+            Outcome::Lost
+        } else {
+            Outcome::Playing
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_status(&self, ctx: &mut Context, font: Font, palette: &Palette, locale: &Locale, bindings: &Bindings, board: &Board, outcome: Outcome) -> GameResult<()> {
+        let screen = layout::screen_size(ctx);
+        let turns = match self.turn_limit {
+            Some(turn_limit) => locale.format("level.turn_of", &[&board.turn, &turn_limit]),
+            None             => locale.format("level.turn", &[&board.turn]),
+        };
+        text::draw_centered_message(
+            ctx,
+            font,
+            &locale.format("level.status", &[&self.localized_name(locale), &self.describe(locale), &turns]),
+            Vec2::new(screen.x / 2.0, 15.0),
+            palette.text,
+        )?;
+
+        let key = |command| bindings.describe(locale, command);
+        let (title, details, help) = match outcome {
+            Outcome::Playing    => return Ok(()),
+            Outcome::Won(stars) => (
+                locale.get("level.won"),
+                locale.format("level.stars", &[&star_string(stars, self.max_stars()), &locale.plural("turns", board.turn)]),
+                locale.format("level.won_help", &[&key(Command::NextLevel), &key(Command::Reset), &key(Command::Levels)]),
+            ),
+            Outcome::Lost       => (
+                locale.get("level.lost"),
+                self.describe(locale),
+                locale.format("level.lost_help", &[&key(Command::Reset), &key(Command::Levels)]),
+            ),
+        };
+        let center = Vec2::new(screen.x / 2.0, screen.y / 2.0);
+        Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(center.x - 180.0, center.y - 60.0, 360.0, 120.0),
+            Color::new(0.0, 0.0, 0.0, 0.75)
+        )?.draw(ctx, DrawParam::default())?;
+        text::draw_centered_message(ctx, font, &title, center + Vec2::new(0.0, -35.0), palette.accent)?;
+        text::draw_centered_message(ctx, font, &details, center, palette.text)?;
+        text::draw_centered_message(ctx, font, &help, center + Vec2::new(0.0, 35.0), palette.help)?;
+        Ok(())
+    }
+}
+
+// The same format as `Level::parse`.
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "name {}", self.name)?;
+        if !self.hint.is_empty() {
+            writeln!(f, "hint {}", self.hint)?;
+        }
+        writeln!(f, "shape {} {}", self.shape.width, self.shape.height)?;
+        writeln!(f, "bounty {}", self.bounty)?;
+        for &(branch_point, branch_upgrade) in self.branches.iter() {
+            match branch_upgrade {
+                0 => writeln!(f, "branch {} {}", branch_point.hex_point.q, branch_point.hex_point.r)?,
+                _ => writeln!(f, "branch {} {} {}", branch_point.hex_point.q, branch_point.hex_point.r, branch_upgrade)?,
+            }
+        }
+        for &(gift_point, gift) in self.gifts.iter() {
+            writeln!(f, "gift {} {} {}", gift_point.hex_point.q, gift_point.hex_point.r, gift.plural_id())?;
+        }
+        for gift_point in self.moss.iter() {
+            writeln!(f, "moss {} {}", gift_point.hex_point.q, gift_point.hex_point.r)?;
+        }
+        for kind in self.locked.iter() {
+            writeln!(f, "lock {}", kind.name())?;
+        }
+        for goal in self.goals.iter() {
+            writeln!(f, "goal {}", goal)?;
+        }
+        if let Some(max_branches) = self.max_branches {
+            writeln!(f, "max_branches {}", max_branches)?;
+        }
+        if let Some(turn_limit) = self.turn_limit {
+            writeln!(f, "turns {}", turn_limit)?;
+        }
+        if !self.star_turns.is_empty() {
+            let star_turns: Vec<String> = self.star_turns.iter().map(|turns| turns.to_string()).collect();
+            writeln!(f, "stars {}", star_turns.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+fn star_string(stars: usize, max_stars: usize) -> String {
+    (0..max_stars)
+        .map(|i| if i < stars {'*'} else {'-'})
+        .collect()
+}
+
+// Loads every level in resources/levels, in file name order.
+pub fn load_all(ctx: &mut Context) -> Vec<Level> {
+    let mut paths: Vec<PathBuf> = match ggez::filesystem::read_dir(ctx, "/levels") {
+        Ok(paths) => paths.collect(),
+        Err(_)    => Vec::new(),
+    };
+    paths.sort();
+
+    let mut levels = Vec::new();
+    for path in paths {
+        let id = path.file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or(String::new());
+        let mut contents = String::new();
+        let loaded = ggez::filesystem::open(ctx, &path)
+            .map_err(|e| e.to_string())
+            .and_then(|mut file| file.read_to_string(&mut contents).map_err(|e| e.to_string()))
+            .and_then(|_| Level::parse(&id, &contents));
+        match loaded {
+            Ok(level) => levels.push(level),
+            Err(e)    => println!("skipping level {:?}: {}", path, e),
+        }
+    }
+    levels
+}
+
+pub fn select_row_at(y: f32, level_count: usize) -> Option<usize> {
+    let row = ((y - SELECT_TOP) / SELECT_SPACING + 0.5).floor();
+    if row >= 0.0 && (row as usize) < level_count {
+        Some(row as usize)
+    } else {
+        None
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn draw_select(ctx: &mut Context, font: Font, palette: &Palette, locale: &Locale, bindings: &Bindings, levels: &[Level], progress: &Progress, selected: usize) -> GameResult<()> {
+    let screen = layout::screen_size(ctx);
+    Mesh::new_rectangle(
+        ctx,
+        DrawMode::fill(),
+        Rect::new(0.0, 0.0, screen.x, screen.y),
+        Color::new(0.0, 0.0, 0.0, 0.8)
+    )?.draw(ctx, DrawParam::default())?;
+
+    text::draw_centered_message(ctx, font, &locale.get("levels.title"), Vec2::new(screen.x / 2.0, 60.0), palette.accent)?;
+    for (i, level) in levels.iter().enumerate() {
+        let color = if i == selected {
+            palette.accent
+        } else {
+            palette.text
+        };
+        text::draw_centered_message(
+            ctx,
+            font,
+            &format!("{}. {}   {}", i + 1, level.localized_name(locale), star_string(progress.get(&level.id), level.max_stars())),
+            Vec2::new(screen.x / 2.0, SELECT_TOP + i as f32 * SELECT_SPACING),
+            color,
+        )?;
+    }
+    text::draw_centered_message(
+        ctx,
+        font,
+        &locale.format("levels.help", &[&bindings.describe(locale, Command::Back)]),
+        Vec2::new(screen.x / 2.0, screen.y - 40.0),
+        palette.help,
+    )
+}
+
+// The best number of stars obtained on each level, one "id stars" per line.
+#[derive(Debug)]
+pub struct Progress {
+    path: PathBuf,
+    pub stars: HashMap<String, usize>,
+}
+
+impl Progress {
+    pub fn load(path: &Path) -> Progress {
+        let stars = fs::read_to_string(path)
+            .map(|contents|
+                contents.lines()
+                    .filter_map(|line| {
+                        let words: Vec<&str> = line.split_whitespace().collect();
+                        Some((words.first()?.to_string(), words.get(1)?.parse().ok()?))
+                    })
+                    .collect()
+            )
+            .unwrap_or_default();
+        Progress {
+            path: path.to_path_buf(),
+            stars,
+        }
+    }
+
+    pub fn get(&self, id: &str) -> usize {
+        *self.stars.get(id).unwrap_or(&0)
+    }
+
+    pub fn record(&mut self, id: &str, stars: usize) {
+        if stars > self.get(id) {
+            self.stars.insert(id.to_string(), stars);
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        let mut ids: Vec<&String> = self.stars.keys().collect();
+        ids.sort();
+        let contents: String = ids.iter()
+            .map(|id| format!("{} {}\n", id, self.stars[*id]))
+            .collect();
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).unwrap_or(());
+        }
+        if let Err(e) = fs::write(&self.path, contents) {
+            println!("could not save the level progress to {:?}: {}", self.path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TREE: &str = "shape 6 16\nbranch -1 0\nbranch 0 -1\nbranch 1 -1\n";
+
+    fn build(extra: &str) -> Result<Board, String> {
+        Level::parse("test", &format!("{}{}", TREE, extra))?.to_board()
+    }
+
+    #[test]
+    fn every_level_loads() {
+        for entry in fs::read_dir("resources/levels").unwrap() {
+            let path = entry.unwrap().path();
+            let level = Level::load(&path).unwrap();
+            assert!(level.to_board().is_ok(), "{:?}", path);
+        }
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        assert_eq!(
+            Level::parse("test", "name Test\nshape 6\n").err(),
+            Some("test, line 2: could not parse \"shape 6\"".to_string()),
+        );
+        assert!(Level::parse("test", "bounty lots").is_err());
+        assert!(Level::parse("test", "gift 2 -2 diamonds").is_err());
+        assert!(Level::parse("test", "moss 2").is_err());
+        assert!(Level::parse("test", "teleport 0 0").is_err());
+        assert!(Level::parse("test", "# only a comment\n\n").is_ok());
+    }
+
+    #[test]
+    fn gifts_only_go_on_branch_tips() {
+        assert!(build("gift 2 -2 flowers\n").is_ok());
+        // the root cell has three branches growing out of it
+        assert!(build("gift 0 0 flowers\n").is_err());
+        // off the tree
+        assert!(build("gift 4 -4 flowers\n").is_err());
+    }
+
+    #[test]
+    fn moss_only_goes_on_cells() {
+        assert!(build("moss 2 -2\n").is_ok());
+        assert!(build("moss 2 -4\n").is_ok());
+        assert!(build("moss 0 0\n").is_err());
+        // a branch point, not a cell
+        assert!(build("moss 1 -1\n").is_err());
+        assert!(build("moss 40 -40\n").is_err());
+    }
+}
@@ -9,6 +9,7 @@ use hex;
 
 pub const BASE: f32 = 0.20;
 
+#[derive(Clone, Debug, Default)]
 pub struct Stats {
     pub leaf_count: usize,
     pub flower_count: usize,
@@ -30,6 +31,44 @@ pub struct Stats {
     pub moss_removed: bool,
 }
 
+impl Stats {
+    pub fn gift_count(&self, gift: cell::Gift) -> usize {
+        match gift {
+            Leaves   => self.leaf_count,
+            Flowers  => self.flower_count,
+            Beehive  => self.beehive_count,
+            Berries  => self.berry_count,
+            Nuts     => self.nut_count,
+            Birdnest => self.birdnest_count,
+            Squirrel => self.squirrel_count,
+        }
+    }
+
+    fn gift_count_mut(&mut self, gift: cell::Gift) -> &mut usize {
+        match gift {
+            Leaves   => &mut self.leaf_count,
+            Flowers  => &mut self.flower_count,
+            Beehive  => &mut self.beehive_count,
+            Berries  => &mut self.berry_count,
+            Nuts     => &mut self.nut_count,
+            Birdnest => &mut self.birdnest_count,
+            Squirrel => &mut self.squirrel_count,
+        }
+    }
+
+    pub fn add_gift(&mut self, gift: Option<cell::Gift>) {
+        if let Some(gift) = gift {
+            *self.gift_count_mut(gift) += 1;
+        }
+    }
+
+    pub fn remove_gift(&mut self, gift: Option<cell::Gift>) {
+        if let Some(gift) = gift {
+            *self.gift_count_mut(gift) -= 1;
+        }
+    }
+}
+
 macro_rules !get {
     ($map:expr, $value:expr) => (*$map.get(&Some($value)).unwrap_or(&0));
     ($map:expr) => (*$map.get(&None).unwrap_or(&0));
@@ -75,16 +114,7 @@ pub fn life_cycle(gifts: &mut HashMap<hex::GiftPoint, cell::GiftCell>,
         // Should be gifts_old?
         if let Some(gift_cell) = gifts.get_mut(&gift_point)
         {
-            match gift_cell.gift {
-                Some(Leaves)   => stats.leaf_count     -= 1,
-                Some(Flowers)  => stats.flower_count   -= 1,
-                Some(Beehive)  => stats.beehive_count  -= 1,
-                Some(Berries)  => stats.berry_count    -= 1,
-                Some(Nuts)     => stats.nut_count      -= 1,
-                Some(Birdnest) => stats.birdnest_count -= 1,
-                Some(Squirrel) => stats.squirrel_count -= 1,
-                _ => {},
-            };
+            stats.remove_gift(gift_cell.gift);
 
             gift_cell.gift = match gift_cell.gift {
                 None => {
@@ -179,16 +209,7 @@ pub fn life_cycle(gifts: &mut HashMap<hex::GiftPoint, cell::GiftCell>,
                 }
             };
 
-            match gift_cell.gift {
-                Some(Leaves)   => stats.leaf_count     += 1,
-                Some(Flowers)  => stats.flower_count   += 1,
-                Some(Beehive)  => stats.beehive_count  += 1,
-                Some(Berries)  => stats.berry_count    += 1,
-                Some(Nuts)     => stats.nut_count      += 1,
-                Some(Birdnest) => stats.birdnest_count += 1,
-                Some(Squirrel) => stats.squirrel_count += 1,
-                _ => {},
-            }
+            stats.add_gift(gift_cell.gift);
         }
         else {
             println!("error: gift cell vanished during update"); // cryptic error bwahaha
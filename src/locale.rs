@@ -0,0 +1,243 @@
+use ggez::Context;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+
+use cell::Gift;
+
+
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+
+// The player-facing text in one language, from resources/lang/LANGUAGE.txt.
+// The format is one "key = text" per line, where {0}, {1}... are replaced by
+// the arguments, and where plural keys come in pairs, e.g.
+//
+//     race.turns.one = {0} turn
+//     race.turns.other = {0} turns
+//
+// The tutorial, the achievements and the levels are written in English in
+// their own data files; the other languages translate them with keys such as
+// "achievement.bonsai.name" or "level.01-leaves.hint".
+#[derive(Debug)]
+pub struct Locale {
+    pub language: String,
+    strings: HashMap<String, String>,
+    // for the keys which the language lacks
+    english: HashMap<String, String>,
+}
+
+pub fn parse(contents: &str) -> Result<HashMap<String, String>, String> {
+    let mut strings = HashMap::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.find('=') {
+            Some(j) => {
+                strings.insert(line[..j].trim().to_string(), line[j + 1..].trim().to_string());
+            },
+            None => return Err(format!("line {}: expected \"key = text\", got {:?}", i + 1, line)),
+        }
+    }
+    Ok(strings)
+}
+
+fn read(ctx: &mut Context, language: &str) -> Result<HashMap<String, String>, String> {
+    let mut contents = String::new();
+    ggez::filesystem::open(ctx, format!("/lang/{}.txt", language))
+        .map_err(|e| e.to_string())
+        .and_then(|mut file| file.read_to_string(&mut contents).map_err(|e| e.to_string()))
+        .and_then(|_| parse(&contents))
+}
+
+// The languages in resources/lang, in alphabetical order.
+pub fn languages(ctx: &mut Context) -> Vec<String> {
+    let mut languages: Vec<String> = match ggez::filesystem::read_dir(ctx, "/lang") {
+        Ok(paths) => paths
+            .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    languages.sort();
+    languages
+}
+
+// e.g. "fr" for LANG=fr_CA.UTF-8
+pub fn system_language() -> Option<String> {
+    std::env::var("LANG").ok()
+        .and_then(|lang| lang.split(['_', '.']).next().map(|language| language.to_lowercase()))
+        .filter(|language| !language.is_empty() && language != "c" && language != "posix")
+}
+
+// "one" or "other", following CLDR's rules for cardinal numbers.
+fn plural_category(language: &str, count: usize) -> &'static str {
+    match language {
+        // zero is singular too
+        "fr" => if count <= 1 {"one"} else {"other"},
+        _    => if count == 1 {"one"} else {"other"},
+    }
+}
+
+// e.g. "gift.bird_nest"
+fn gift_key(gift: Gift) -> String {
+    format!("gift.{}", gift.id().replace(' ', "_"))
+}
+
+impl Locale {
+    pub fn load(ctx: &mut Context, language: &str) -> Locale {
+        let english = read(ctx, DEFAULT_LANGUAGE).unwrap_or_else(|e| {
+            println!("could not load the English text: {}", e);
+            HashMap::new()
+        });
+        let strings = if language == DEFAULT_LANGUAGE {
+            english.clone()
+        } else {
+            read(ctx, language).unwrap_or_else(|e| {
+                println!("could not load the {:?} text, using English instead: {}", language, e);
+                english.clone()
+            })
+        };
+        Locale {
+            language: language.to_string(),
+            strings,
+            english,
+        }
+    }
+
+    // The key itself if no language has it, so that it shows up on screen.
+    pub fn get(&self, key: &str) -> String {
+        self.strings.get(key)
+            .or_else(|| self.english.get(key))
+            .cloned()
+            .unwrap_or_else(|| key.to_string())
+    }
+
+    // For the text which comes from a data file.
+    pub fn get_or(&self, key: &str, english: &str) -> String {
+        self.strings.get(key).cloned().unwrap_or_else(|| english.to_string())
+    }
+
+    pub fn format(&self, key: &str, args: &[&dyn fmt::Display]) -> String {
+        let mut text = self.get(key);
+        for (i, arg) in args.iter().enumerate() {
+            text = text.replace(&format!("{{{}}}", i), &arg.to_string());
+        }
+        text
+    }
+
+    // Uses "KEY.one" or "KEY.other", with the count as {0}.
+    pub fn plural(&self, key: &str, count: usize) -> String {
+        let key = format!("{}.{}", key, plural_category(&self.language, count));
+        self.format(&key, &[&count])
+    }
+
+    // e.g. "a leaf" or "3 leaves"
+    pub fn gifts(&self, gift: Gift, count: usize) -> String {
+        self.plural(&gift_key(gift), count)
+    }
+
+    // e.g. "the leaf"
+    pub fn the_gift(&self, gift: Gift) -> String {
+        self.get(&format!("{}.the", gift_key(gift)))
+    }
+
+    // e.g. "leaves", as in the sandbox palette
+    pub fn gift_name(&self, gift: Gift) -> String {
+        self.get(&format!("{}.name", gift_key(gift)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use achievement;
+    use bindings::Command;
+    use level;
+
+    // The keys which every language other than English must define for the
+    // data files in `resources`.
+    fn data_keys(resources: &Path) -> Result<Vec<String>, String> {
+        let mut keys = Vec::new();
+
+        let contents = fs::read_to_string(resources.join("achievements.txt")).map_err(|e| e.to_string())?;
+        for entry in achievement::parse(&contents)? {
+            keys.extend(entry.keys());
+        }
+
+        let mut paths: Vec<PathBuf> = fs::read_dir(resources.join("levels"))
+            .map_err(|e| e.to_string())?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let id = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+            let contents = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+            keys.extend(level::Level::parse(&id, &contents)?.keys());
+        }
+        Ok(keys)
+    }
+
+    // Lists the keys which each language is missing: every language must have
+    // all the keys of the English file, and the translations of the data files.
+    fn check(resources: &Path) -> Result<Vec<String>, String> {
+        let lang_dir = resources.join("lang");
+        let read_language = |language: &str| -> Result<HashMap<String, String>, String> {
+            let path = lang_dir.join(format!("{}.txt", language));
+            let contents = fs::read_to_string(&path).map_err(|e| format!("{:?}: {}", path, e))?;
+            parse(&contents).map_err(|e| format!("{:?}: {}", path, e))
+        };
+
+        let english = read_language(DEFAULT_LANGUAGE)?;
+        let english_keys: BTreeSet<&String> = english.keys().collect();
+        let data_keys = data_keys(resources)?;
+
+        let mut languages: Vec<String> = fs::read_dir(&lang_dir)
+            .map_err(|e| e.to_string())?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.path().file_stem().map(|stem| stem.to_string_lossy().to_string()))
+            .collect();
+        languages.sort();
+
+        let mut problems = Vec::new();
+        for language in languages {
+            if language == DEFAULT_LANGUAGE {
+                continue;
+            }
+            let strings = read_language(&language)?;
+            for key in english_keys.iter() {
+                if !strings.contains_key(*key) {
+                    problems.push(format!("{}: missing {}", language, key));
+                }
+            }
+            for key in data_keys.iter() {
+                if !strings.contains_key(key) {
+                    problems.push(format!("{}: missing {}", language, key));
+                }
+            }
+        }
+        Ok(problems)
+    }
+
+    #[test]
+    fn every_language_is_complete() {
+        assert_eq!(check(Path::new("resources")), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn every_command_is_named() {
+        for entry in fs::read_dir("resources/lang").unwrap() {
+            let path = entry.unwrap().path();
+            let strings = parse(&fs::read_to_string(&path).unwrap()).unwrap();
+            for command in Command::all() {
+                let key = format!("command.{}", command.name());
+                assert!(strings.contains_key(&key), "{:?}: missing {}", path, key);
+            }
+        }
+    }
+}
@@ -3,30 +3,59 @@ extern crate ggez;
 extern crate rand;
 extern crate counter;
 extern crate glam;
+extern crate png;
 
 use core::time::Duration;
 use ggez::{GameResult, Context, ContextBuilder};
-use ggez::audio;
-use ggez::audio::SoundSource;
 use ggez::event::*;
 use ggez::graphics::*;
 use ggez::timer;
-use rand::seq::SliceRandom;
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::io::Read;
+use std::rc::Rc;
 
+mod achievement;
+mod alert;
 mod bg;
+mod bindings;
+mod board;
 mod cell;
 mod center;
 mod channel;
+mod cursor;
+mod daily;
+mod export;
 mod globals;
+mod goal;
 mod hex;
+mod history;
+mod layout;
+mod level;
+mod script;
 mod sidebar;
+mod solver;
+mod strategy;
 mod text;
+mod timelapse;
+mod toolbar;
 mod vector;
 mod life;
-
+mod music;
+mod locale;
+mod palette;
+mod prng;
+mod race;
+mod raster;
+mod sandbox;
+mod selection;
+mod settings;
+mod sfx;
+mod sound;
+
+use alert::AlertMessage;
+use bindings::{Command, Input};
+use board::{Action, ActionError, ActionKind, Board, Economy};
 use globals::*;
-use life::Stats;
 use glam::f32::Vec2;
 
 
@@ -35,123 +64,24 @@ struct Assets {
     bg: bg::Assets,
     cell: cell::Assets,
     dot: Mesh,
+    ring: Mesh,
     font: Font,
     hex: hex::Assets,
-    branch_place_sound: audio::Source,
-    branch_upgrade_sound: audio::Source,
-    branch_break_sounds: Vec<audio::Source>,
-    gift_release_sound: audio::Source,
     moss: Image,
 }
 
-type CellCheckFn = fn( &HashMap<hex::BranchPoint, cell::BranchCell>, &Stats,) -> bool;
-
-//#[derive(Debug)]
-struct Achievement {
-    pub achieved: bool,
-    pub message: &'static str,
-    //pub text: Text,
-    pub functor: CellCheckFn,
-}
-
-fn any_branches( _branches: &HashMap<hex::BranchPoint, cell::BranchCell>, stats: &Stats,) -> bool
-{
-    (stats.branch_lv1_count>0) | (stats.branch_lv2_count>0)
-}
-
-fn fewer_branches( _branches: &HashMap<hex::BranchPoint, cell::BranchCell>, stats: &Stats,) -> bool
-{
-    (stats.branch_lv1_count+stats.branch_lv2_count) < stats.branches_max
-}
-
-#[allow(dead_code)]
-fn any_branch_length3( _branches: &HashMap<hex::BranchPoint, cell::BranchCell>, stats: &Stats,) -> bool
-{
-    stats.branch_length3_count > 0
-}
-
-fn any_branch_length4( _branches: &HashMap<hex::BranchPoint, cell::BranchCell>, stats: &Stats,) -> bool
-{
-    stats.branch_length4_count > 0
-}
 
-fn two_leaves( _branches: &HashMap<hex::BranchPoint, cell::BranchCell>, stats: &Stats,) -> bool
-{
-    stats.leaf_count >= 2
-}
-
-fn no_foliage( _branches: &HashMap<hex::BranchPoint, cell::BranchCell>, stats: &Stats,) -> bool
-{
-    stats.moss_added
-    //(stats.leaf_count == 0) & (stats.flower_count == 0)
-}
-
-fn any_foliage( _branches: &HashMap<hex::BranchPoint, cell::BranchCell>, stats: &Stats,) -> bool
-{
-    stats.moss_removed
-    //(stats.leaf_count > 0) | (stats.flower_count>0)
-}
-
-fn any_flowers( _branches: &HashMap<hex::BranchPoint, cell::BranchCell>, stats: &Stats,) -> bool
-{
-    stats.flower_count>0
-}
-
-fn any_beehives( _branches: &HashMap<hex::BranchPoint, cell::BranchCell>, stats: &Stats,) -> bool
-{
-    stats.beehive_count>0
-}
-
-fn any_branch_lv2( _branches: &HashMap<hex::BranchPoint, cell::BranchCell>, stats: &Stats,) -> bool
-{
-    stats.branch_lv2_count>0
-}
 
-fn any_berries( _branches: &HashMap<hex::BranchPoint, cell::BranchCell>, stats: &Stats,) -> bool
-{
-    stats.berry_count>0
+enum Screen {
+    Playing,
+    LevelSelect(usize),
+    Achievements,
+    Log,
+    Stats,
+    Bindings(bindings::Rebinding),
+    Settings(settings::SettingsScreen),
 }
 
-fn any_nuts( _branches: &HashMap<hex::BranchPoint, cell::BranchCell>, stats: &Stats,) -> bool
-{
-    stats.nut_count>0
-}
-
-fn any_squirrels( _branches: &HashMap<hex::BranchPoint, cell::BranchCell>, stats: &Stats,) -> bool
-{
-    stats.squirrel_count>0
-}
-
-fn any_birds( _branches: &HashMap<hex::BranchPoint, cell::BranchCell>, stats: &Stats,) -> bool
-{
-    stats.birdnest_count>0
-}
-
-fn any_bounty_lv4( _branches: &HashMap<hex::BranchPoint, cell::BranchCell>, stats: &Stats,) -> bool
-{
-    stats.life_max>=3
-}
-
-fn any_d_presses( _branches: &HashMap<hex::BranchPoint, cell::BranchCell>, stats: &Stats,) -> bool
-{
-    stats.d_pressed
-}
-
-
-struct Alert {
-    pub message: &'static str,
-    pub until_time: Duration,
-}
-
-pub enum AlertMessage {
-    NotEnoughBounty,
-    BranchTooStrained,
-    CantUpgrade,
-    ClickForBranch,
-    ClickForMoss,
-}
-
-
 impl Assets {
     fn load_assets(ctx: &mut Context) -> GameResult<Assets> {
         let font = Font::default();
@@ -160,17 +90,9 @@ impl Assets {
             bg: bg::load_assets(ctx)?,
             cell: cell::load_assets(ctx)?,
             dot: Mesh::new_circle(ctx, DrawMode::fill(), Vec2::new(0.0, 0.0), 10.0, 3.0, Color::WHITE)?,
+            ring: Mesh::new_circle(ctx, DrawMode::stroke(2.0), Vec2::new(0.0, 0.0), 14.0, 1.0, Color::WHITE)?,
             font,
             hex: hex::load_assets(ctx)?,
-            branch_place_sound: audio::Source::new(ctx, "/branch_place.ogg")?,
-            branch_upgrade_sound: audio::Source::new(ctx, "/branch_upgrade.ogg")?,
-            branch_break_sounds: vec!(
-                audio::Source::new(ctx, "/branch_break.ogg")?,
-                audio::Source::new(ctx, "/branch_break2.ogg")?,
-                audio::Source::new(ctx, "/branch_break3.ogg")?,
-                audio::Source::new(ctx, "/branch_break4.ogg")?,
-            ),
-            gift_release_sound: audio::Source::new(ctx, "/branch_item_remove.ogg")?,
             moss: Image::new(ctx, "/moss.png")?,
         })
     }
@@ -179,632 +101,927 @@ impl Assets {
 //#[derive(Debug)]
 struct Globals {
     assets: Assets,
-    achievements: Vec<Achievement>,
-    alerts: Vec<Alert>,
-    alert_current: Option<usize>,
+    achievements: achievement::Achievements,
+    // the most recently unlocked achievement, and until when to show it
+    unlocked: Option<(String, Duration)>,
+    alerts: alert::Alerts,
+    history: history::History,
+    palette: palette::Palette,
+    locale: locale::Locale,
+    layout: layout::Layout,
+    // the player's, as saved to settings.txt
+    settings: settings::Settings,
+    // from the command line, for this session only
+    overrides: settings::Overrides,
+    // whether the window is currently full screen
+    fullscreen: bool,
+    bindings: bindings::Bindings,
+    // a destructive command waiting for the player to confirm it
+    confirming: Option<Command>,
     start_time: Duration,
     turn_time: Duration,
     turn_duration: Duration,
-    guitar_channel: channel::Channel,
-    clarinet_channel: channel::Channel,
-    high_pithed_clarinet_channel: channel::Channel,
-    dreamy_bells_channel: channel::Channel,
+    music: music::Mixer,
+    sfx: sfx::Sfx,
     bounty: sidebar::Sidebar,
     life: sidebar::Sidebar,
-    bounty_amount: f32,
-    life_amount: f32,
     hover: Option<hex::InBoundsPoint>,
-    root_point: hex::BranchPoint,
-    branches: HashMap<hex::BranchPoint, cell::BranchCell>,
-    gifts: HashMap<hex::GiftPoint, cell::GiftCell>,
-    stats: Stats,
-    forbidden: HashMap<hex::GiftPoint, bool>,
-    cost_multiplier: f32, // for debugging
+    // moved by the mouse, the arrow keys or a gamepad's d-pad
+    cursor: cursor::Cursor,
+    toolbar: toolbar::Toolbar,
+    board: Board,
+    autoplay: Option<Box<dyn strategy::Strategy>>,
+    race: Option<race::Race>,
+    screen: Screen,
+    levels: Vec<level::Level>,
+    level: Option<usize>,
+    level_outcome: level::Outcome,
+    progress: level::Progress,
+    sandbox: Option<sandbox::Sandbox>,
+    // picking whole limbs, see selection.rs
+    selection: Option<selection::Selection>,
+    daily: Option<daily::Daily>,
 }
 
 impl Globals {
-    fn new(ctx: &mut Context) -> GameResult<Globals> {
+    fn new(ctx: &mut Context, settings: settings::Settings, backend: &sound::Backend) -> GameResult<Globals> {
         let assets = Assets::load_assets(ctx)?;
         let bounty = sidebar::Sidebar::new(
             &assets.font,
-            "Life", //"Bounty", // Design decision that Bounty should be called Life in UI
-            Color::from_rgb(181, 208, 212),
-            0.0
+            "sidebar.life", //"sidebar.bounty", // Design decision that Bounty should be called Life in UI
+            sidebar::Edge::Left,
+            sidebar::Dot::Diamond,
         )?;
         let life = sidebar::Sidebar::new(
             &assets.font,
-            "Bounty", //"Life", // Design decision that Life should be called Bounty in UI
-            Color::from_rgb(242, 240, 186),
-            WINDOW_WIDTH as f32 - sidebar::SIDEBAR_WIDTH
+            "sidebar.bounty", //"sidebar.life", // Design decision that Life should be called Bounty in UI
+            sidebar::Edge::Right,
+            sidebar::Dot::Circle,
         )?;
 
+        let language = settings_language(ctx, &settings);
         let mut globals = Globals {
             assets,
-            achievements: vec!(
-                Achievement {
-                    achieved: false,
-                    message: "Click near the tree trunk to add a branch - click between two cells",
-                    functor: any_branches,
-                },
-                Achievement {
-                    achieved: false,
-                    message: "Right-click a branch to prune - right-click between two cells",
-                    functor: fewer_branches,
-                },
-                /*Achievement {
-                    achieved: false,
-                    message: "Try making a longer branch",
-                    functor: any_branch_length3,
-                },*/
-                Achievement {
-                    achieved: false,
-                    message: "Leaves and flowers grow on ends of branches - try getting two leaves",
-                    functor: two_leaves,
-                },
-                Achievement {
-                    achieved: false,
-                    message: "Right-click on foliage to replace with moss - try deleting some foliage",
-                    functor: no_foliage,
-                },
-                Achievement {
-                    achieved: false,
-                    message: "Right-click moss to allow growth again - try deleting a moss",
-                    functor: any_foliage,
-                },
-                Achievement {
-                    achieved: false,
-                    message: "Flowers reqiure two leaves nearby - they die if no leaves",
-                    functor: any_flowers,
-                },
-                Achievement {
-                    achieved: false,
-                    message: "Click a branch to grow it thicker and allow a bigger tree",
-                    functor: any_branch_lv2,
-                },
-                Achievement {
-                    achieved: false,
-                    message: "Try building a very long branch",
-                    functor: any_branch_length4,
-                },
-                Achievement {
-                    achieved: false,
-                    message: "Beehives appear when two flowers are nearby - more Bounty than flowers",
-                    functor: any_beehives,
-                },
-                Achievement {
-                    achieved: false,
-                    message: "Leaves, flowers and other life build Bounty - try getting to Bounty 4",
-                    functor: any_bounty_lv4,
-                },
-                Achievement {
-                    achieved: false,
-                    message: "Rarer life provide more Bounty. Berries grow when a beehive and two leaves are near.",
-                    functor: any_berries,
-                },
-                Achievement {
-                    achieved: false,
-                    message: "Congrats! Here's a cheat code: hold 'D' to create branches for free :)",
-                    functor: any_d_presses,
-                },
-                Achievement {
-                    achieved: false,
-                    message: "Nuts grow only on the ends of thick branches near flowers and leaves",
-                    functor: any_nuts,
-                },
-                Achievement {
-                    achieved: false,
-                    message: "Squirrel appear when two nuts are nearby",
-                    functor: any_squirrels,
-                },
-                Achievement {
-                    achieved: false,
-                    message: "Birds appear when two berries are nearby",
-                    functor: any_birds,
-                },
-            ),
-            alerts: vec!(
-                // AlertMessage::NotEnoughBounty
-                Alert {
-                    message: "NOTE: Not enough Life for this action - build Bounty to gain Life faster",
-                    until_time: Duration::from_millis(0),
-                },
-                // AlertMessage::BranchTooStrained
-                Alert {
-                    message: "NOTE: Too many thin branches in a row - click to make thicker",
-                    until_time: Duration::from_millis(0),
-                },
-                // AlertMessage::CantUpgrade
-                Alert {
-                    message: "NOTE: Can't make a branch thicker unless all supporting branches are at least as thick",
-                    until_time: Duration::from_millis(0),
-                },
-                // AlertMessage::ClickForBranch
-                Alert {
-                    message: "NOTE: Click between two cells from a branch to create another branch",
-                    until_time: Duration::from_millis(0),
-                },
-                // AlertMessage::ClickForMoss
-                Alert {
-                    message: "NOTE: Right-click foliage to replace with moss - right-click again to remove",
-                    until_time: Duration::from_millis(0),
-                },
-            ),
-            alert_current: None,
+            achievements: achievement::Achievements::load(ctx, &ggez::filesystem::user_data_dir(ctx).join("achievements.txt")),
+            unlocked: None,
+            alerts: alert::Alerts::new(),
+            history: history::History::new(),
+            palette: palette::Palette::new(settings.palette),
+            locale: locale::Locale::load(ctx, &language),
+            layout: layout::Layout::from_window(ctx),
+            settings,
+            overrides: settings::Overrides::default(),
+            fullscreen: false,
+            bindings: bindings::Bindings::load(&ggez::filesystem::user_config_dir(ctx).join("bindings.txt")),
+            confirming: None,
             start_time: get_current_time(ctx),
             turn_time: get_current_time(ctx),
-            turn_duration: Duration::from_millis(2000),
-            // Pick one set and comment out the other.
-            /*guitar_channel: channel::Channel::new(ctx, "/guitar.ogg")?,
-            clarinet_channel: channel::Channel::new(ctx, "/clarinet.ogg")?,
-            high_pithed_clarinet_channel: channel::Channel::new(ctx, "/high-pitched clarinet.ogg")?,
-            dreamy_bells_channel: channel::Channel::new(ctx, "/dreamy-bells.ogg")?,*/
-            guitar_channel: channel::Channel::new(ctx, "/guitar.ogg")?,
-            clarinet_channel: channel::Channel::new(ctx, "/birds.ogg")?,
-            high_pithed_clarinet_channel: channel::Channel::new(ctx, "/high-pitched clarinet.ogg")?,
-            dreamy_bells_channel: channel::Channel::new(ctx, "/midi-glock-squirrel.ogg")?,
+            turn_duration: TURN_DURATION,
+            music: music::Mixer::load(ctx, backend),
+            sfx: sfx::Sfx::load(ctx, backend),
             bounty,
             life,
-            bounty_amount: 0.0,
-            life_amount: 0.0,
             hover: None,
-            root_point: hex::BranchPoint::new(hex::HexPoint::new(0, 1)),
-            branches: HashMap::with_capacity(100),
-            gifts: HashMap::with_capacity(100),
-            stats: Stats{
-                leaf_count: 0,
-                flower_count: 0,
-                beehive_count: 0,
-                berry_count: 0,
-                nut_count: 0,
-                birdnest_count: 0,
-                squirrel_count: 0,
-                moss_count: 0,
-                branch_lv1_count: 0,
-                branch_lv2_count: 0,
-                branch_length3_count: 0,
-                branch_length4_count: 0,
-                branch_length5_count: 0,
-                branches_max: 0,
-                life_max: 0,
-                d_pressed: false,
-                moss_added: false,
-                moss_removed: false,
-            },
-            forbidden: HashMap::with_capacity(100),
-            cost_multiplier: 1.0,
+            cursor: cursor::Cursor::new(),
+            toolbar: toolbar::Toolbar::new(),
+            board: Board::new(),
+            autoplay: None,
+            race: None,
+            screen: Screen::Playing,
+            levels: level::load_all(ctx),
+            level: None,
+            level_outcome: level::Outcome::Playing,
+            progress: level::Progress::load(&ggez::filesystem::user_data_dir(ctx).join("levels.txt")),
+            sandbox: None,
+            selection: None,
+            daily: None,
         };
+        globals.layout.use_screen_coordinates(ctx)?;
+        globals.apply_settings(ctx);
         globals.reset(ctx);
         Ok(globals)
     }
 
+    // Brings everything in line with `self.settings` and the overrides, after
+    // they were loaded or changed.
+    fn apply_settings(&mut self, ctx: &mut Context) {
+        let settings = self.overrides.apply(&self.settings);
+        self.music.set_gain(settings.music_gain());
+
+        self.sfx.set_gain(settings.sfx_gain());
+
+        self.turn_duration = TURN_DURATION.div_f32(settings.turn_speed);
+        self.board.economy = self.economy();
+        if self.palette.preset != settings.palette {
+            self.palette = palette::Palette::new(settings.palette);
+        }
+        let language = settings_language(ctx, &settings);
+        if self.locale.language != language {
+            self.locale = locale::Locale::load(ctx, &language);
+        }
+        if self.fullscreen != settings.fullscreen {
+            let fullscreen_type = if settings.fullscreen {
+                ggez::conf::FullscreenType::Desktop
+            } else {
+                ggez::conf::FullscreenType::Windowed
+            };
+            // the layout is updated by resize_event
+            match set_fullscreen(ctx, fullscreen_type) {
+                Ok(())  => self.fullscreen = settings.fullscreen,
+                Err(e)  => println!("could not toggle full screen: {}", e),
+            }
+        }
+    }
+
+    // Races, daily challenges and levels always use the fixed economy, so that
+    // their leaderboard, history and stars compare like with like.
+    fn economy(&self) -> Economy {
+        if self.race.is_some() || self.daily.is_some() || self.level.is_some() {
+            Economy::Fixed
+        } else {
+            self.overrides.economy.unwrap_or(self.settings.economy)
+        }
+    }
+
     fn reset(&mut self, ctx: &mut Context) {
         self.start_time = get_current_time(ctx);
         self.turn_time = get_current_time(ctx);
-        self.bounty_amount = 5.0;
-        self.life_amount = 0.0;
-
-        self.branches.clear();
-        self.root_point = hex::BranchPoint::new(hex::HexPoint::new(0, 1));
-        let root_gift_point = hex::GiftPoint::new(hex::HexPoint::new(0, 0));
-        let mut root_cell = cell::BranchCell::new(None);
-        root_cell.branch_upgrade = 3;
-        self.branches.insert(self.root_point, root_cell);
+        self.level_outcome = level::Outcome::Playing;
+        let level = match self.daily {
+            Some(ref daily) => Some(&daily.level),
+            None            => self.level.and_then(|i| self.levels.get(i)),
+        };
+        match level.map(|level| level.to_board()) {
+            Some(Ok(board)) => self.board = board,
+            Some(Err(e)) => {
+                println!("{}", e);
+                self.level = None;
+                self.board = Board::new();
+            },
+            None => self.board.reset(),
+        }
+        if let Some(ref race) = self.race {
+            race.scatter_moss(&mut self.board);
+        }
+        self.board.economy = self.economy();
+        if let Some(ref mut selection) = self.selection {
+            selection.deselect();
+        }
+        if let Some(ref mut race) = self.race {
+            race.restart();
+        }
+        if let Some(ref mut daily) = self.daily {
+            daily.restart();
+        }
+        self.sfx.forget_gifts(&self.board);
+        self.history.clear();
+        self.history.record(&self.board);
+    }
 
-        self.forbidden.clear();
-        self.forbidden.insert(root_gift_point, true);
+    fn start_level(&mut self, ctx: &mut Context, i: usize) {
+        self.race = None;
+        self.sandbox = None;
+        self.daily = None;
+        self.screen = Screen::Playing;
+        self.level = if i < self.levels.len() {Some(i)} else {None};
+        self.reset(ctx);
+    }
 
-        self.gifts.clear();
-        let origin_point = hex::GiftPoint::new(hex::HexPoint::new(0, 0));
-        let origin_cell = cell::GiftCell::new(self.root_point);
-        self.gifts.insert(origin_point, origin_cell);
+    fn start_race(&mut self, ctx: &mut Context, goal: goal::Goal, seed: u64) {
+        self.level = None;
+        self.sandbox = None;
+        self.daily = None;
+        let path = ggez::filesystem::user_data_dir(ctx).join("leaderboard.txt");
+        self.race = Some(race::Race::new(goal, seed, race::Leaderboard::load(&path)));
+        self.reset(ctx);
     }
 
-    fn branch_parent_branch(&self, branch_point: hex::BranchPoint) -> Option<hex::BranchPoint> {
-        let branch_cell = self.branches.get(&branch_point)?;
-        let gift_point = branch_cell.parent?;
-        let gift_cell = self.gifts.get(&gift_point)?;
-        Some(gift_cell.parent)
+    // cycles through the preset goals, then back to the endless game
+    fn next_race(&mut self, ctx: &mut Context) {
+        let presets = goal::Goal::presets();
+        let next = match self.race {
+            None => presets.first().map(|goal| (*goal, 0)),
+            Some(ref race) => presets.iter()
+                .position(|goal| *goal == race.goal)
+                .and_then(|i| presets.get(i + 1))
+                .map(|goal| (*goal, race.seed)),
+        };
+        match next {
+            Some((goal, seed)) => self.start_race(ctx, goal, seed),
+            None => {
+                self.race = None;
+                self.reset(ctx);
+            },
+        }
     }
 
-    fn branch_nth_parent_branch_cell(&self, branch_point: hex::BranchPoint, n: u8) -> Option<cell::BranchCell> {
-        if n == 0 {
-            self.branches.get(&branch_point).map(|b| *b)
-        } else {
-            let parent_point = self.branch_parent_branch(branch_point)?;
-            self.branch_nth_parent_branch_cell(parent_point, n-1)
+    fn toggle_daily(&mut self, ctx: &mut Context) {
+        match self.daily {
+            None => {
+                let path = ggez::filesystem::user_data_dir(ctx).join("daily.txt");
+                self.race = None;
+                self.level = None;
+                self.sandbox = None;
+                self.daily = Some(daily::Daily::new(daily::today(), daily::History::load(&path)));
+            },
+            Some(_) => self.daily = None,
         }
+        self.reset(ctx);
     }
 
-    fn branch_nth_parent_branch_cell_or_root(&self, branch_point: hex::BranchPoint, n: u8) -> cell::BranchCell {
-        match self.branch_nth_parent_branch_cell(branch_point, n) {
-            Some(branch_cell) => branch_cell,
+    // Keeps the current board, so that a level or a game in progress can be
+    // tweaked, but drops the goals which would otherwise end it.
+    fn toggle_sandbox(&mut self, ctx: &mut Context) {
+        match self.sandbox {
             None => {
-                *self.branches.get(&self.root_point).unwrap()
+                self.race = None;
+                self.level = None;
+                self.daily = None;
+                self.level_outcome = level::Outcome::Playing;
+                self.autoplay = None;
+                self.selection = None;
+                self.sandbox = Some(sandbox::Sandbox::new());
+                self.board.economy = self.economy();
+            },
+            Some(_) => {
+                self.sandbox = None;
+                self.reset(ctx);
             },
         }
     }
 
-    #[allow(dead_code)]
-    fn gift_parent_gift(&self, gift_point: hex::GiftPoint) -> Option<hex::GiftPoint> {
-        let gift_cell = self.gifts.get(&gift_point)?;
-        let branch_point = gift_cell.parent;
-        let branch_cell = self.branches.get(&branch_point)?;
-        branch_cell.parent
+    fn export_sandbox(&self, ctx: &mut Context) {
+        let path = ggez::filesystem::user_data_dir(ctx).join("sandbox-level.txt");
+        let level = level::Level::from_board("sandbox", &self.board);
+        let contents = format!("{}# add some goals, e.g.\n# goal creature beehive 1\n", level);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).unwrap_or(());
+        }
+        match std::fs::write(&path, contents) {
+            Ok(())  => println!("level exported to {:?}", path),
+            Err(e)  => println!("could not export the level to {:?}: {}", path, e),
+        }
+    }
+
+    // Saves the tree as tree-<time>.svg in the user data directory.
+    fn export_image(&mut self, ctx: &mut Context) {
+        let mut read_file = |path: &str| -> Option<Vec<u8>> {
+            let mut bytes = Vec::new();
+            ggez::filesystem::open(ctx, path).ok()?.read_to_end(&mut bytes).ok()?;
+            Some(bytes)
+        };
+        let seconds = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let path = ggez::filesystem::user_data_dir(ctx).join(format!("tree-{}.svg", seconds));
+        let result = export::to_svg(&self.board, &self.palette, &mut read_file).and_then(|svg| {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).unwrap_or(());
+            }
+            std::fs::write(&path, svg).map_err(|e| e.to_string())
+        });
+        let (message, severity) = match result {
+            Ok(())  => (self.locale.format("screenshot.saved", &[&path.display().to_string()]), alert::Severity::Info),
+            Err(e)  => (self.locale.format("screenshot.failed", &[&e]), alert::Severity::Error),
+        };
+        println!("{}", message);
+        self.alerts.push(get_current_time(ctx), self.board.turn, &message, severity, None);
     }
 
-    fn branch_children(&self, branch_point: hex::BranchPoint) -> Vec<hex::GiftPoint> {
-        branch_point.gift_neighbours()
-            .iter()
-            .map(|g| *g)
-            .filter(|g|
-                match self.gifts.get(g) {
-                    None => false,
-                    Some(gift_cell) => gift_cell.parent == branch_point,
+    // Saves the history of the game as stats-<time>.csv in the user data
+    // directory.
+    fn export_stats(&mut self, ctx: &mut Context) {
+        let seconds = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let path = ggez::filesystem::user_data_dir(ctx).join(format!("stats-{}.csv", seconds));
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).unwrap_or(());
+        }
+        let (message, severity) = match std::fs::write(&path, self.history.to_csv()) {
+            Ok(())  => (self.locale.format("stats.exported", &[&path.display().to_string()]), alert::Severity::Info),
+            Err(e)  => (self.locale.format("stats.export_failed", &[&e]), alert::Severity::Error),
+        };
+        println!("{}", message);
+        self.alerts.push(get_current_time(ctx), self.board.turn, &message, severity, None);
+    }
+
+    // Shows why an action was rejected, near the hex which was clicked.
+    fn display_alert(&mut self, ctx: &mut Context, alert_message: AlertMessage, position: Option<Vec2>) {
+        self.alerts.push_alert(&self.locale, get_current_time(ctx), self.board.turn, alert_message, position);
+    }
+
+    // In selection mode its commands win over the others bound to the same
+    // input; outside of it they are left alone.
+    fn command_for(&self, input: Input, keymod: KeyMods) -> Option<Command> {
+        let selection_command = match self.selection {
+            Some(_) => self.bindings.command_among(input, keymod, Command::in_selection),
+            None    => None,
+        };
+        selection_command.or_else(|| self.bindings.command_among(input, keymod, |command| {
+            !command.in_selection() && !command.on_screen() && !command.in_sandbox()
+        }))
+    }
+
+    // Destructive commands only ask for confirmation here, see `execute`.
+    fn run_command(&mut self, ctx: &mut Context, command: Command) {
+        if command.needs_confirmation() {
+            self.confirming = Some(command);
+        } else {
+            self.execute(ctx, command);
+        }
+    }
+
+    fn execute(&mut self, ctx: &mut Context, command: Command) {
+        match command {
+            Command::Grow | Command::PruneOrMoss | Command::Prune | Command::Moss => {
+                if let Some(in_bounds_point) = self.hover {
+                    self.hex_command(ctx, command, in_bounds_point);
                 }
-            )
-            .collect()
-    }
-
-    fn gift_children(&self, gift_point: hex::GiftPoint) -> Vec<hex::BranchPoint> {
-        gift_point.branch_neighbours()
-            .iter()
-            .map(|b| *b)
-            .filter(|b|
-                match self.branches.get(b) {
-                    None => false,
-                    Some(branch_cell) => branch_cell.parent == Some(gift_point),
+            },
+            Command::CursorUp     => self.hover = self.cursor.step(cursor::Move::Up, self.board.shape),
+            Command::CursorDown   => self.hover = self.cursor.step(cursor::Move::Down, self.board.shape),
+            Command::CursorLeft   => self.hover = self.cursor.step(cursor::Move::Left, self.board.shape),
+            Command::CursorRight  => self.hover = self.cursor.step(cursor::Move::Right, self.board.shape),
+            Command::ToolBranch   => self.toolbar.select(ActionKind::Place),
+            Command::ToolUpgrade  => self.toolbar.select(ActionKind::Upgrade),
+            Command::ToolPrune    => self.toolbar.select(ActionKind::Prune),
+            Command::ToolMoss     => self.toolbar.select(ActionKind::Moss),
+            Command::FreeBuild    => {
+                self.board.cost_multiplier = 0.0;
+                self.board.stats.d_pressed = true;
+            },
+            Command::Reset        => self.reset(ctx),
+            Command::Quit         => quit(ctx),
+            Command::Autoplay     => {
+                self.autoplay = match self.autoplay {
+                    None => strategy::from_name("lookahead", 0),
+                    Some(_) => None,
+                };
+            },
+            Command::NextRace     => self.next_race(ctx),
+            Command::Levels       => self.screen = Screen::LevelSelect(self.level.unwrap_or(0)),
+            Command::NextLevel    => {
+                if let (Some(i), level::Outcome::Won(_)) = (self.level, self.level_outcome) {
+                    self.start_level(ctx, i + 1);
                 }
-            )
-            .collect()
+            },
+            Command::Achievements => self.screen = Screen::Achievements,
+            Command::Log          => {
+                self.alerts.log_scroll = 0;
+                self.screen = Screen::Log;
+            },
+            Command::Sandbox      => self.toggle_sandbox(ctx),
+            Command::ExportLevel  => if self.sandbox.is_some() {
+                self.export_sandbox(ctx);
+            },
+            Command::Select       => if self.sandbox.is_none() {
+                self.selection = match self.selection {
+                    None    => Some(selection::Selection::new()),
+                    Some(_) => None,
+                };
+            },
+            Command::Daily        => self.toggle_daily(ctx),
+            Command::Bindings     => self.screen = Screen::Bindings(bindings::Rebinding::new()),
+            Command::Palette      => {
+                self.change_setting(ctx, settings::Setting::Palette);
+                let message = self.locale.format("palette.changed", &[&self.locale.get(&format!("palette.{}", self.palette.preset.name()))]);
+                self.alerts.push(get_current_time(ctx), self.board.turn, &message, alert::Severity::Info, None);
+            },
+            Command::Language     => {
+                self.change_setting(ctx, settings::Setting::Language);
+                let message = self.locale.format("language.changed", &[&self.locale.get("language.name")]);
+                self.alerts.push(get_current_time(ctx), self.board.turn, &message, alert::Severity::Info, None);
+            },
+            Command::Fullscreen   => self.change_setting(ctx, settings::Setting::Fullscreen),
+            Command::Settings     => self.screen = Screen::Settings(settings::SettingsScreen::new()),
+            Command::Screenshot   => self.export_image(ctx),
+            Command::Stats        => self.screen = Screen::Stats,
+            // looked up on their own screens and in the sandbox instead
+            Command::Confirm | Command::Back | Command::LogUp | Command::LogDown
+            | Command::LogPageUp | Command::LogPageDown | Command::ExportStats
+            | Command::SandboxFreeze | Command::SandboxStep => (),
+            Command::SelectionUpgrade0 | Command::SelectionUpgrade1 | Command::SelectionUpgrade2
+            | Command::SelectionUpgrade3 | Command::SelectionPrune | Command::SelectionMoss
+            | Command::SelectionCopy | Command::SelectionPaste | Command::SelectionRotate
+            | Command::SelectionMirror | Command::SelectionConfirm | Command::SelectionCancel => {
+                let response = match self.selection {
+                    Some(ref mut selection) => selection.run(&self.board, command),
+                    None                    => return,
+                };
+                self.selection_response(ctx, response);
+            },
+        }
+    }
+
+    // Steps a setting forward, as on the settings screen.
+    fn change_setting(&mut self, ctx: &mut Context, setting: settings::Setting) {
+        self.overrides.take(setting, &mut self.settings);
+        self.settings.adjust(setting, true, &self.locale.language, &locale::languages(ctx));
+        self.apply_settings(ctx);
+        self.settings.save();
     }
 
-    fn prune_branch(&mut self, branch_point: hex::BranchPoint) {
-        if let Some(_) = self.branches.get(&branch_point) {
-            for gift_point in self.branch_children(branch_point) {
-                self.prune_gift(gift_point);
+    fn settings_response(&mut self, ctx: &mut Context, response: settings::Response) {
+        match response {
+            settings::Response::Nothing      => (),
+            settings::Response::Changed      => {
+                self.apply_settings(ctx);
+                self.settings.save();
+            },
+            settings::Response::OpenBindings => self.screen = Screen::Bindings(bindings::Rebinding::new()),
+            settings::Response::Close        => self.screen = Screen::Playing,
+        }
+    }
+
+    fn end_free_build(&mut self) {
+        self.board.cost_multiplier = 1.0;
+        self.board.stats.d_pressed = false;
+    }
+
+    fn hex_command(&mut self, ctx: &mut Context, command: Command, in_bounds_point: hex::InBoundsPoint) {
+        if self.level_outcome != level::Outcome::Playing {
+            return;
+        }
+        if self.daily.as_ref().is_some_and(|daily| daily.finished.is_some()) {
+            return;
+        }
+        // where the alerts are shown, and where the sounds come from
+        let position = Some(self.layout.board_to_screen(in_bounds_point.to_point()));
+        let x = in_bounds_point.to_point().x;
+
+        if let Some(ref sandbox) = self.sandbox {
+            if command == Command::Grow {
+                if let Err(alert_message) = sandbox.paint(&mut self.board, in_bounds_point) {
+                    self.display_alert(ctx, alert_message, position);
+                }
+            } else {
+                sandbox.erase(&mut self.board, in_bounds_point);
             }
+            return;
+        }
 
-            if let Some(branch_cell) = self.branches.remove(&branch_point) {
-                match branch_cell.branch_upgrade {
-                    0 => self.stats.branch_lv1_count -= 1,
-                    _ => self.stats.branch_lv2_count -= 1,
-                };
+        if let Some(ref mut selection) = self.selection {
+            let now = get_current_time(ctx);
+            match (command, in_bounds_point) {
+                (Command::Grow, hex::InBoundsPoint::GiftPoint(gift_point)) if selection.pasting => {
+                    selection.pending = Some(selection::Operation::Paste(gift_point));
+                },
+                (Command::Grow, hex::InBoundsPoint::BranchPoint(_)) if selection.pasting => {
+                    self.alerts.push(now, self.board.turn, &self.locale.get("selection.paste_on_cell"), alert::Severity::Info, position);
+                },
+                (Command::Grow, hex::InBoundsPoint::BranchPoint(branch_point)) => {
+                    if self.board.branches.contains_key(&branch_point) {
+                        selection.select(branch_point);
+                    } else {
+                        self.alerts.push_alert(&self.locale, now, self.board.turn, AlertMessage::Rejected(ActionError::NoBranch), position);
+                    }
+                },
+                // a cell picks the branch it grows from
+                (Command::Grow, hex::InBoundsPoint::GiftPoint(gift_point)) => {
+                    match self.board.gifts.get(&gift_point) {
+                        Some(gift_cell) => selection.select(gift_cell.parent),
+                        None            => self.alerts.push_alert(&self.locale, now, self.board.turn, AlertMessage::Rejected(ActionError::NoBranch), position),
+                    }
+                },
+                _ => selection.deselect(),
             }
+            return;
         }
-    }
 
-    fn prune_gift(&mut self, gift_point: hex::GiftPoint) {
-        if let Some(_) = self.gifts.get(&gift_point) {
-            for branch_point in self.gift_children(gift_point) {
-                self.prune_branch(branch_point);
+        // with a tool from the toolbar, the click does exactly that
+        if let (Command::Grow, Some(tool)) = (command, self.toolbar.tool) {
+            let alert_option = match toolbar::tool_action(tool, in_bounds_point) {
+                Some(action) => self.apply_action(ctx, action, x),
+                None => match in_bounds_point {
+                    hex::InBoundsPoint::BranchPoint(_) => Some(AlertMessage::ClickForMoss),
+                    hex::InBoundsPoint::GiftPoint(_)   => Some(AlertMessage::ClickForBranch),
+                },
+            };
+            if let Some(alert_message) = alert_option {
+                self.display_alert(ctx, alert_message, position);
             }
-            if let Some(gift_cell) = self.gifts.remove(&gift_point) {
-                match gift_cell.gift {
-                    Some(cell::Gift::Leaves)   => self.stats.leaf_count     -= 1,
-                    Some(cell::Gift::Flowers)  => self.stats.flower_count   -= 1,
-                    Some(cell::Gift::Beehive)  => self.stats.beehive_count  -= 1,
-                    Some(cell::Gift::Berries)  => self.stats.berry_count    -= 1,
-                    Some(cell::Gift::Nuts)     => self.stats.nut_count      -= 1,
-                    Some(cell::Gift::Birdnest) => self.stats.birdnest_count -= 1,
-                    Some(cell::Gift::Squirrel) => self.stats.squirrel_count -= 1,
-                    _ => {},
+            return;
+        }
+
+        let mut alert_option: Option<AlertMessage> = None;
+        match (command, in_bounds_point) {
+            (Command::Grow, hex::InBoundsPoint::BranchPoint(branch_point)) => {
+                let action = match self.board.branches.get(&branch_point) {
+                    None    => Action::Place(branch_point),
+                    Some(_) => Action::Upgrade(branch_point),
                 };
-            }
+                alert_option = self.apply_action(ctx, action, x);
+            },
+            (Command::Grow, hex::InBoundsPoint::GiftPoint(gift_point)) => {
+                match self.board.gifts.get(&gift_point) {
+                    None => {
+                        alert_option = Some(AlertMessage::ClickForBranch);
+                        //println!("you cannot place a branch on a cell, only in-between two cells");
+                    },
+                    Some(gift_cell) => {
+                        match gift_cell.gift {
+                            None => {
+                                alert_option = Some(AlertMessage::ClickForMoss);
+                                //println!("you cannot place leaves, you have to let them grow");
+                            },
+                            Some(gift) => {
+                                let message = self.locale.format("alert.release", &[&self.bindings.describe(&self.locale, Command::PruneOrMoss), &self.locale.the_gift(gift)]);
+                                self.alerts.push(get_current_time(ctx), self.board.turn, &message, alert::Severity::Info, position);
+                            },
+                        }
+                    },
+                }
+            },
+            (Command::PruneOrMoss, hex::InBoundsPoint::BranchPoint(branch_point))
+            | (Command::Prune, hex::InBoundsPoint::BranchPoint(branch_point)) => {
+                alert_option = self.apply_action(ctx, Action::Prune(branch_point), x);
+            },
+            (Command::PruneOrMoss, hex::InBoundsPoint::GiftPoint(gift_point))
+            | (Command::Moss, hex::InBoundsPoint::GiftPoint(gift_point)) => {
+                alert_option = self.apply_action(ctx, Action::Moss(gift_point), x);
+            },
+            _ => (),
         }
-        if let Some(_) = self.forbidden.get(&gift_point) {
-            self.forbidden.remove(&gift_point);
+        if let Some(alert_message) = alert_option {
+            self.display_alert(ctx, alert_message, position);
         }
     }
 
-    fn remove_gift(&mut self, gift_point: hex::GiftPoint) {
-        if let Some(gift_cell) = self.gifts.get(&gift_point) {
-            match gift_cell.gift {
-                Some(cell::Gift::Leaves)   => self.stats.leaf_count     -= 1,
-                Some(cell::Gift::Flowers)  => self.stats.flower_count   -= 1,
-                Some(cell::Gift::Beehive)  => self.stats.beehive_count  -= 1,
-                Some(cell::Gift::Berries)  => self.stats.berry_count    -= 1,
-                Some(cell::Gift::Nuts)     => self.stats.nut_count      -= 1,
-                Some(cell::Gift::Birdnest) => self.stats.birdnest_count -= 1,
-                Some(cell::Gift::Squirrel) => self.stats.squirrel_count -= 1,
-                _ => {},
-            };
-        }
+    fn selection_response(&mut self, ctx: &mut Context, response: selection::Response) {
+        let message = match response {
+            selection::Response::Nothing       => return,
+            selection::Response::Confirm       => return self.confirm_selection(ctx),
+            selection::Response::Leave         => {
+                self.selection = None;
+                return;
+            },
+            selection::Response::Copied        => self.locale.format("selection.copied", &[&self.bindings.describe(&self.locale, Command::SelectionPaste)]),
+            selection::Response::NothingToCopy => self.locale.get("selection.nothing_to_copy"),
+        };
+        self.alerts.push(get_current_time(ctx), self.board.turn, &message, alert::Severity::Info, None);
+    }
 
-        self.gifts
-            .entry(gift_point)
-            .and_modify(|g| g.gift = None);
-        if !self.gifts.get(&gift_point).is_none() && self.gift_children(gift_point).len() == 0 {
-            self.forbidden
-                .entry(gift_point)
-                .and_modify(|b| *b ^= true)
-                .or_insert(true);
-            if *self.forbidden.get(&gift_point).unwrap() {
-                self.stats.moss_added = true;
-            } else {
-                self.stats.moss_removed = true;
+    // Performs the pending bulk operation all at once, or not at all if the
+    // player can't afford it or the rules reject any of its actions: they are
+    // tried on a copy of the board first.
+    fn confirm_selection(&mut self, ctx: &mut Context) {
+        if self.level_outcome != level::Outcome::Playing {
+            return;
+        }
+        if self.daily.as_ref().is_some_and(|daily| daily.finished.is_some()) {
+            return;
+        }
+        let plan = match self.selection {
+            Some(ref selection) => match selection.pending {
+                Some(operation) => selection.plan(&self.board, operation),
+                None            => return,
+            },
+            None => return,
+        };
+        if plan.actions.is_empty() {
+            let message = self.locale.get("selection.nothing_to_do");
+            self.alerts.push(get_current_time(ctx), self.board.turn, &message, alert::Severity::Info, None);
+            return;
+        } else if plan.cost > self.board.bounty_amount {
+            self.display_alert(ctx, AlertMessage::Rejected(ActionError::NotEnoughBounty {need: plan.cost, have: self.board.bounty_amount}), None);
+            return;
+        }
+        let mut board = self.board.clone();
+        for &action in plan.actions.iter() {
+            if let Err(action_error) = board.apply(action) {
+                self.display_alert(ctx, AlertMessage::Rejected(action_error), None);
+                return;
             }
         }
+        self.board = board;
+        for action in plan.actions {
+            let x = action.to_point().map_or(0.0, |point| point.x);
+            self.action_applied(ctx, action, x);
+        }
+        if let Some(ref mut selection) = self.selection {
+            selection.pending = None;
+            selection.pasting = false;
+        }
     }
 
-    fn display_alert(&mut self, ctx: &mut Context, alert_message: AlertMessage )
-    {
-        let i: usize = match alert_message {
-            AlertMessage::NotEnoughBounty => 0,
-            AlertMessage::BranchTooStrained => 1,
-            AlertMessage::CantUpgrade => 2,
-            AlertMessage::ClickForBranch => 3,
-            AlertMessage::ClickForMoss => 4,
-        };
-        self.alert_current = Some(i);
-        self.alerts[i].until_time = get_current_time(ctx) + Duration::from_millis(2000);
+    // Plays the action's sound if the rules accept it, and returns why not
+    // otherwise. `x` is where it happens, for panning.
+    fn apply_action(&mut self, ctx: &mut Context, action: Action, x: f32) -> Option<AlertMessage> {
+        match self.board.apply(action) {
+            Ok(_) => {
+                self.action_applied(ctx, action, x);
+                None
+            },
+            Err(action_error) => Some(AlertMessage::Rejected(action_error)),
+        }
+    }
+
+    // Records an action which the board has just accepted, and plays its sound.
+    fn action_applied(&mut self, ctx: &mut Context, action: Action, x: f32) {
+        if let Some(action_kind) = action.kind() {
+            self.history.record_action(self.board.turn, action_kind);
+        }
+        if let Some(name) = sfx::action_effect(action) {
+            self.sfx.play(ctx, name, x);
+        }
     }
 }
 
 impl EventHandler for Globals {
     fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
-        self.guitar_channel.update(ctx);
-        self.clarinet_channel.update(ctx);
-        self.high_pithed_clarinet_channel.update(ctx);
-        self.dreamy_bells_channel.update(ctx);
-        self.bounty.update(ctx, self.bounty_amount, 0.0f32);
-        self.life.update(ctx, 0.0f32, self.life_amount+1.0);
+        // the life produced each turn is added to the bounty
+        self.bounty.update(ctx, self.board.bounty_amount, self.board.life_amount, Some(self.board.bounty_cap()));
+        self.life.update(ctx, self.board.life_amount, 0.0f32, None);
 
         let now = get_current_time(ctx);
+        self.alerts.update(now);
+        let mut frozen = false;
+        if let Some(ref mut selection) = self.selection {
+            selection.update(&self.board, self.hover);
+        }
+        if let Some(ref mut sandbox) = self.sandbox {
+            sandbox.run_pending_steps(&mut self.board);
+            frozen = sandbox.frozen;
+        }
         while (now - self.turn_time) > self.turn_duration { // while loop in case of large discrepancy
-            // let basic_amount = 0.1f32; // get this amount even if no life
-            // self.bounty_amount = (self.bounty_amount+self.life_amount+basic_amount).min(30.0);
-            self.life_amount = life::life_production(&self.gifts);
-            self.bounty_amount = (self.bounty_amount + self.life_amount).min(MAX_BOUNTY);
+            let daily_finished = self.daily.as_ref().is_some_and(|daily| daily.finished.is_some());
+            if frozen || daily_finished || self.level_outcome != level::Outcome::Playing {
+                self.turn_time = now;
+                break;
+            }
+            if let Some(ref mut strategy) = self.autoplay {
+                strategy::play_turn(strategy.as_mut(), &mut self.board);
+            } else {
+                self.board.next_turn();
+            }
+            self.history.record(&self.board);
             self.turn_time = self.turn_time + self.turn_duration;
-
-            life::life_cycle(
-                &mut self.gifts, &self.branches, &self.forbidden, &mut self.stats
-            );
         }
 
-        self.stats.life_max = self.stats.life_max.max(self.life_amount.floor() as usize);
-        self.stats.branches_max = self.stats.branches_max.max(self.stats.branch_lv1_count + self.stats.branch_lv2_count);
+        self.board.update_stats();
+        self.history.record(&self.board);
+        self.sfx.watch_gifts(ctx, &self.board);
 
+        let assisted = self.board.stats.d_pressed || self.autoplay.is_some() || self.sandbox.is_some();
+        if let Some(ref mut race) = self.race {
+            race.update(&self.board, now - self.start_time, assisted);
+        }
 
-        // calculate the moss count
-        // Need to skip non-tips. Check that children is [] when we get those!
-        self.stats.moss_count = 0;
-        for (&gift_point, &b) in self.forbidden.iter() {
-            if b && self.gift_children(gift_point).len() == 0 {
-                self.stats.moss_count += 1;
-            }
+        if let Some(ref mut daily) = self.daily {
+            daily.update(&self.board, assisted);
         }
 
-        for mut achievement in self.achievements.iter_mut() {
-            if !achievement.achieved {
-                if (achievement.functor)(&self.branches,&self.stats) {
-                    achievement.achieved = true;
+        if let Some(i) = self.level {
+            if self.level_outcome == level::Outcome::Playing {
+                self.level_outcome = self.levels[i].outcome(&self.board);
+                if let level::Outcome::Won(stars) = self.level_outcome {
+                    self.progress.record(&self.levels[i].id, stars);
                 }
-                break // don't mark an achievment when its hint was never displayed yet
             }
         }
 
-        self.guitar_channel.enable(ctx, self.stats.leaf_count > 0);
-        self.clarinet_channel.enable(ctx, self.stats.birdnest_count > 0);
-        self.high_pithed_clarinet_channel.enable(ctx, self.stats.beehive_count > 0);
-        self.dreamy_bells_channel.enable(ctx, self.stats.squirrel_count > 0);
+        // painting in the sandbox doesn't count
+        if self.sandbox.is_none() {
+            for entry in self.achievements.update(&self.board.stats) {
+                println!("achievement unlocked: {}", entry.name);
+                self.unlocked = Some((entry.localized_name(&self.locale), now + Duration::from_millis(4000)));
+            }
+        }
+
+        self.music.update(ctx, &self.board);
 
         ggez::timer::sleep(Duration::from_millis(50));
         Ok(())
     }
 
-    fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, _keymod: KeyMods, _repeat: bool) {
-        match keycode {
-            KeyCode::D     => {
-                self.cost_multiplier = 0.0;
-                self.stats.d_pressed = true;
-            },
-            KeyCode::Escape => quit(ctx),
-            _               => (),
+    fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, keymod: KeyMods, repeat: bool) {
+        if let Some(command) = self.confirming {
+            // holding the key down doesn't confirm it
+            if repeat {
+                return;
+            }
+            self.confirming = None;
+            if self.bindings.command_among(Input::Key(keycode), keymod, |other| other == Command::Confirm || other == command).is_some() {
+                self.execute(ctx, command);
+            }
+            return;
+        }
+        if let Screen::Bindings(ref mut rebinding) = self.screen {
+            if !rebinding.key_down(&mut self.bindings, keycode, keymod) {
+                self.screen = Screen::Playing;
+            }
+            return;
+        }
+        if let Screen::Settings(ref mut settings_screen) = self.screen {
+            let response = settings_screen.key_down(&mut self.settings, &mut self.overrides, keycode, &self.locale.language, &locale::languages(ctx));
+            self.settings_response(ctx, response);
+            return;
+        }
+        let command = self.command_for(Input::Key(keycode), keymod);
+        // the screen's own commands first, then the one which opened it
+        let screen_command = self.bindings.command_among(Input::Key(keycode), keymod, Command::on_screen).or(command);
+        if let Screen::Log = self.screen {
+            match screen_command {
+                Some(Command::LogUp)                    => self.alerts.scroll(1),
+                Some(Command::LogDown)                  => self.alerts.scroll(-1),
+                Some(Command::LogPageUp)                => self.alerts.scroll(alert::LOG_ROWS as isize),
+                Some(Command::LogPageDown)              => self.alerts.scroll(-(alert::LOG_ROWS as isize)),
+                Some(Command::Back) | Some(Command::Log) => self.screen = Screen::Playing,
+                _                                       => (),
+            }
+            return;
+        }
+        if let Screen::Stats = self.screen {
+            match screen_command {
+                Some(Command::ExportStats)                => self.export_stats(ctx),
+                Some(Command::Back) | Some(Command::Stats) => self.screen = Screen::Playing,
+                _                                         => (),
+            }
+            return;
+        }
+        if let Screen::Achievements = self.screen {
+            if let Some(Command::Back) | Some(Command::Achievements) = screen_command {
+                self.screen = Screen::Playing;
+            }
+            return;
+        }
+        if let Screen::LevelSelect(selected) = self.screen {
+            let level_count = self.levels.len();
+            match keycode {
+                KeyCode::Up     => self.screen = Screen::LevelSelect((selected + level_count.max(1) - 1) % level_count.max(1)),
+                KeyCode::Down   => self.screen = Screen::LevelSelect((selected + 1) % level_count.max(1)),
+                KeyCode::Return => self.start_level(ctx, selected),
+                _ if screen_command == Some(Command::Back) => self.screen = Screen::Playing,
+                _               => (),
+            }
+            return;
+        }
+
+        if let Some(ref mut sandbox) = self.sandbox {
+            if let Some(brush) = sandbox::Brush::from_keycode(keycode) {
+                sandbox.brush = brush;
+                return;
+            }
+            match self.bindings.command_among(Input::Key(keycode), keymod, Command::in_sandbox) {
+                Some(Command::SandboxFreeze) => {
+                    sandbox.frozen = !sandbox.frozen;
+                    return;
+                },
+                Some(Command::SandboxStep)   => {
+                    sandbox.pending_steps += 1;
+                    return;
+                },
+                _                            => (),
+            }
+        }
+
+        if let Some(command) = command {
+            if !(repeat && command.needs_confirmation()) {
+                self.run_command(ctx, command);
+            }
         }
     }
 
-    fn key_up_event(&mut self, ctx: &mut Context, keycode: KeyCode, _keymod: KeyMods) {
-        match keycode {
-            KeyCode::D     => {
-                self.cost_multiplier = 1.0;
-                self.stats.d_pressed = false;
-            },
-            KeyCode::R     => self.reset(ctx),
-            _              => (),
+    fn key_up_event(&mut self, _ctx: &mut Context, keycode: KeyCode, _keymod: KeyMods) {
+        if self.bindings.is_bound_to(Input::Key(keycode), Command::FreeBuild) {
+            self.end_free_build();
         }
     }
 
     fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
-        let point = Vec2::new(x, y);
-        let mut alert_option: Option<AlertMessage> = None;
-        if let Some(in_bounds_point) = hex::HexPoint::from_point(point).is_in_bounds() {
-            match button {
-                MouseButton::Left => {
-                    match in_bounds_point {
-                        hex::InBoundsPoint::BranchPoint(branch_point) => {
-
-                            match self.branches.get(&branch_point) {
-                                None => {
-                                    let gift_neighbours = branch_point.gift_neighbours();
-                                    let empty_neighbours: Vec<hex::GiftPoint> = gift_neighbours
-                                        .iter()
-                                        .map(|g| *g)
-                                        .filter(|g| self.gifts.get(g).is_none())
-                                        .collect();
-                                    let full_neighbours: Vec<hex::GiftPoint> = gift_neighbours
-                                        .iter()
-                                        .map(|g| *g)
-                                        .filter(|g| self.gifts.get(g).is_some())
-                                        .collect();
-                                    if empty_neighbours.len() == 1 && full_neighbours.len() == 1 {
-                                        let empty_neighbour = empty_neighbours[0];
-                                        let full_gift_point = full_neighbours[0];
-                                        let full_gift_cell = *self.gifts.get(&full_gift_point).unwrap();
-                                        let grandparent_cell = self.branch_nth_parent_branch_cell_or_root(full_gift_cell.parent, 2);
-
-                                        if grandparent_cell.branch_upgrade > 0 {
-                                            let cost = self.cost_multiplier * life::BASE * 5.0;
-                                            if self.bounty_amount >= cost {
-                                                // place a new branch
-                                                self.assets.branch_place_sound.play(ctx).unwrap_or(());
-                                                self.bounty_amount -= cost;
-                                                self.stats.branch_lv1_count += 1;
-                                                let branch_cell = cell::BranchCell::new(Some(full_gift_point));
-                                                let gift_cell = cell::GiftCell::new(branch_point);
-                                                self.branches.insert(branch_point, branch_cell);
-                                                self.gifts.insert(empty_neighbour, gift_cell);
-                                                self.forbidden.insert(full_gift_point, true);
-                                                if full_gift_cell.gift.is_some() {
-                                                    self.remove_gift(full_gift_point);
-                                                }
-
-                                                if self.branch_nth_parent_branch_cell(full_gift_cell.parent, 2).is_some() {
-                                                    self.stats.branch_length3_count += 1;
-                                                }
-                                                if self.branch_nth_parent_branch_cell(full_gift_cell.parent, 3).is_some() {
-                                                    self.stats.branch_length4_count += 1;
-                                                }
-                                                if self.branch_nth_parent_branch_cell(full_gift_cell.parent, 4).is_some() {
-                                                    self.stats.branch_length5_count += 1;
-                                                }
-                                            } else {
-                                                alert_option = Some(AlertMessage::NotEnoughBounty);
-                                                //println!("not enough Bounty");
-                                            }
-                                        } else {
-                                            alert_option = Some(AlertMessage::BranchTooStrained);
-                                            //println!("branches are too thin to hold more branches!");
-                                        }
-                                    } else if empty_neighbours.len() == 2 {
-                                        alert_option = Some(AlertMessage::ClickForBranch);
-                                        //println!("new branches must be attached to the tree");
-                                    } else if full_neighbours.len() == 2 {
-                                        println!("branches cannot form a cycle");
-                                    }
-                                },
-                                Some(_) => {
-                                    let parent_cell = self.branch_nth_parent_branch_cell_or_root(branch_point, 1);
-                                    let grandparent_cell = self.branch_nth_parent_branch_cell_or_root(branch_point, 3);
-                                    if let Some(branch_cell) = self.branches.get_mut(&branch_point) {
-                                        let bounty_amount_ = &mut self.bounty_amount;
-                                        if branch_cell.branch_upgrade < parent_cell.branch_upgrade {
-                                            if (branch_cell.branch_upgrade+1 < grandparent_cell.branch_upgrade) |
-                                               (grandparent_cell.branch_upgrade >= 3)
-                                            {
-                                                match branch_cell.branch_upgrade {
-                                                    0 => {
-                                                        let cost = self.cost_multiplier * life::BASE * 25.0;
-                                                        if *bounty_amount_ >= cost {
-                                                            // upgrade a branch to level 1
-                                                            self.assets.branch_upgrade_sound.play(ctx).unwrap_or(());
-                                                            *bounty_amount_ -= cost;
-                                                            branch_cell.branch_upgrade = 1;
-                                                            self.stats.branch_lv2_count += 1;
-                                                        } else {
-                                                            alert_option = Some(AlertMessage::NotEnoughBounty);
-                                                            //println!("not enough Bounty");
-                                                        }
-                                                    },
-                                                    1 => {
-                                                        let cost = self.cost_multiplier * life::BASE * 125.0;
-                                                        if *bounty_amount_ >= cost {
-                                                            // upgrade a branch to level 2
-                                                            self.assets.branch_upgrade_sound.play(ctx).unwrap_or(());
-                                                            *bounty_amount_ -= cost;
-                                                            branch_cell.branch_upgrade = 2;
-                                                        } else {
-                                                            alert_option = Some(AlertMessage::NotEnoughBounty);
-                                                            //println!("not enough Bounty");
-                                                        }
-                                                    },
-                                                    2 => {
-                                                        let cost = self.cost_multiplier * life::BASE * 625.0;
-                                                        if *bounty_amount_ >= cost {
-                                                            // upgrade a branch to level 3
-                                                            self.assets.branch_upgrade_sound.play(ctx).unwrap_or(());
-                                                            *bounty_amount_ -= cost;
-                                                            branch_cell.branch_upgrade = 3;
-                                                        } else {
-                                                            alert_option = Some(AlertMessage::NotEnoughBounty);
-                                                            //println!("not enough Bounty");
-                                                        }
-                                                    },
-                                                    _ => {
-                                                        println!("this branch has already reached its maximum growth");
-                                                    },
-                                                }
-                                            } else {
-                                                alert_option = Some(AlertMessage::BranchTooStrained);
-                                                //println!("branches are too thin to hold more branches!");
-                                            }
-                                        } else {
-                                            alert_option = Some(AlertMessage::CantUpgrade);
-                                            //println!("you have to grow the parent branch first!");
-                                        }
-                                    }
-                                },
-                            }
-                        },
-                        hex::InBoundsPoint::GiftPoint(gift_point) => {
-                            match self.gifts.get(&gift_point) {
-                                None => {
-                                    alert_option = Some(AlertMessage::ClickForBranch);
-                                    //println!("you cannot place a branch on a cell, only in-between two cells");
-                                },
-                                Some(gift_cell) => {
-                                    match gift_cell.gift {
-                                        None => {
-                                            alert_option = Some(AlertMessage::ClickForMoss);
-                                            //println!("you cannot place leaves, you have to let them grow");
-                                        },
-                                        Some(gift) => {
-                                            println!("right-click to release the {:}", gift.singular());
-                                        },
-                                    }
-                                },
-                            }
-                        },
-                    }
-                },
-                MouseButton::Right => {
-                    match in_bounds_point {
-                        hex::InBoundsPoint::BranchPoint(branch_point) => {
-                            if self.branches.get(&branch_point).is_some() {
-                                self.assets.branch_break_sounds.choose_mut(&mut rand::thread_rng()).unwrap().play(ctx).unwrap_or(());
-                                self.prune_branch(branch_point);
-                            }
-                        },
-                        hex::InBoundsPoint::GiftPoint(gift_point) => {
-                            self.assets.gift_release_sound.play(ctx).unwrap_or(());
-                            self.remove_gift(gift_point);
-                        },
-                    }
+        let keymod = ggez::input::keyboard::active_mods(ctx);
+        let point = self.layout.pixel_to_screen(x, y);
+        if self.confirming.take().is_some() {
+            return;
+        }
+        if let Screen::Bindings(ref mut rebinding) = self.screen {
+            rebinding.mouse_down(&mut self.bindings, button, keymod, point.y);
+            return;
+        }
+        if let Screen::Settings(ref mut settings_screen) = self.screen {
+            let response = settings_screen.mouse_down(&mut self.settings, &mut self.overrides, button, point.y, &self.locale.language, &locale::languages(ctx));
+            self.settings_response(ctx, response);
+            return;
+        }
+        match self.screen {
+            Screen::Achievements | Screen::Log | Screen::Stats => {
+                self.screen = Screen::Playing;
+                return;
+            },
+            _ => (),
+        }
+        if let Screen::LevelSelect(_) = self.screen {
+            if let Some(i) = level::select_row_at(point.y, self.levels.len()) {
+                self.start_level(ctx, i);
+            }
+            return;
+        }
+
+        match self.sandbox {
+            Some(ref mut sandbox) => if let Some(brush) = sandbox::palette_brush_at(point) {
+                sandbox.brush = brush;
+                return;
+            },
+            None => if let Some(tool) = toolbar::toolbar_tool_at(point, Vec2::new(self.layout.width, self.layout.height)) {
+                self.toolbar.select(tool);
+                return;
+            },
+        }
+        match self.command_for(Input::Mouse(button), keymod) {
+            Some(command) if command.targets_hex() => {
+                if let Some(in_bounds_point) = hex::HexPoint::from_point(self.layout.screen_to_board(point)).is_in_bounds() {
+                    self.hex_command(ctx, command, in_bounds_point);
                 }
-                _ => {}
+            },
+            Some(command) => self.run_command(ctx, command),
+            None          => (),
+        }
+    }
+
+    fn mouse_button_up_event(&mut self, _ctx: &mut Context, button: MouseButton, _x: f32, _y: f32) {
+        if self.bindings.is_bound_to(Input::Mouse(button), Command::FreeBuild) {
+            self.end_free_build();
+        }
+    }
+
+    fn gamepad_button_down_event(&mut self, ctx: &mut Context, button: Button, _id: GamepadId) {
+        let command = self.command_for(Input::Gamepad(button), KeyMods::NONE);
+        if let Some(confirming) = self.confirming.take() {
+            if self.bindings.command_among(Input::Gamepad(button), KeyMods::NONE, |other| other == Command::Confirm || other == confirming).is_some() {
+                self.execute(ctx, confirming);
             }
+            return;
         }
-        if let Some(alert_message) = alert_option {
-            self.display_alert(ctx,alert_message);
+        if let Screen::Bindings(ref mut rebinding) = self.screen {
+            if !rebinding.button_down(&mut self.bindings, button) {
+                self.screen = Screen::Playing;
+            }
+            return;
+        }
+        if let Screen::Playing = self.screen {
+            if let Some(command) = command {
+                self.run_command(ctx, command);
+            }
+            return;
+        }
+
+        // the menus are navigated as if with the keyboard
+        let keycode = match button {
+            Button::DPadUp    => KeyCode::Up,
+            Button::DPadDown  => KeyCode::Down,
+            Button::DPadLeft  => KeyCode::Left,
+            Button::DPadRight => KeyCode::Right,
+            Button::South     => KeyCode::Return,
+            Button::East      => KeyCode::Escape,
+            _                 => return,
+        };
+        self.key_down_event(ctx, keycode, KeyMods::NONE, false);
+    }
+
+    fn gamepad_button_up_event(&mut self, _ctx: &mut Context, button: Button, _id: GamepadId) {
+        if self.bindings.is_bound_to(Input::Gamepad(button), Command::FreeBuild) {
+            self.end_free_build();
+        }
+    }
+
+    fn mouse_wheel_event(&mut self, _ctx: &mut Context, _x: f32, y: f32) {
+        if let Screen::Log = self.screen {
+            self.alerts.scroll(y.signum() as isize);
         }
     }
 
     fn mouse_motion_event(&mut self, _ctx: &mut Context, x: f32, y: f32, _xrel: f32, _yrel: f32) {
-        let hex_point = hex::HexPoint::from_point(Vec2::new(x as f32, y as f32));
-        self.hover = hex_point.is_in_bounds()
+        let point = self.layout.screen_to_board(self.layout.pixel_to_screen(x, y));
+        let hex_point = hex::HexPoint::from_point(point);
+        self.hover = hex_point.is_in_bounds();
+        if let Some(in_bounds_point) = self.hover {
+            self.cursor.jump_to(in_bounds_point);
+        }
     }
 
 
+    fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
+        self.layout = layout::Layout::new(width, height, window(ctx).scale_factor() as f32);
+        self.layout.use_screen_coordinates(ctx).unwrap_or(());
+    }
+
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
 //        // must use white for drawing images, otherwise they get tinted
 //        set_color(ctx, Color::from_rgb(255, 255, 255))?;
 
         bg::draw_bg(ctx, &self.assets.bg)?;
-        hex::draw_hex_grid(ctx, &self.assets.hex)?;
-        self.bounty.draw(ctx)?;
-        self.life.draw(ctx)?;
+        self.layout.use_board_coordinates(ctx)?;
+        hex::draw_hex_grid(ctx, &self.assets.hex, self.board.shape, &self.palette)?;
+        self.layout.use_screen_coordinates(ctx)?;
+        self.bounty.draw(ctx, &self.palette, &self.locale, self.palette.life_sidebar, self.palette.meter_bounty)?;
+        self.life.draw(ctx, &self.palette, &self.locale, self.palette.bounty_sidebar, self.palette.meter_life)?;
+
+        self.layout.use_board_coordinates(ctx)?;
 
-        for (&branch_point, branch_cell) in self.branches.iter() {
+        for (&branch_point, branch_cell) in self.board.branches.iter() {
             branch_cell.draw(
                 ctx,
                 &self.assets.cell,
@@ -813,7 +1030,7 @@ impl EventHandler for Globals {
                     .color(Color::from_rgb(0, 0, 0)) // fix white artifacts around the branches
             )?;
         }
-        for (&gift_point, gift_cell) in self.gifts.iter() {
+        for (&gift_point, gift_cell) in self.board.gifts.iter() {
             gift_cell.draw(
                 ctx,
                 &self.assets.cell,
@@ -823,9 +1040,9 @@ impl EventHandler for Globals {
             )?;
         }
         // Need to skip non-tips. Check that children is [] when we get those!
-        for (&gift_point, &b) in self.forbidden.iter() {
-            //println!("{:?}", self.gift_children(gift_point).len());
-            if b && self.gift_children(gift_point).len() == 0 {
+        for (&gift_point, &b) in self.board.forbidden.iter() {
+            //println!("{:?}", self.board.gift_children(gift_point).len());
+            if b && self.board.gift_children(gift_point).len() == 0 {
                 let image = &self.assets.moss;
                 center::draw_centered_image(
                     ctx,
@@ -833,7 +1050,13 @@ impl EventHandler for Globals {
                     gift_point.to_point(),
                     0.0,
                     DrawParam::default()
-                        .color(Color::from_rgb(128, 255, 128))
+                        .color(self.palette.moss)
+                )?;
+                self.assets.ring.draw(
+                    ctx,
+                    DrawParam::default()
+                        .dest(gift_point.to_point())
+                        .color(self.palette.moss)
                 )?;
                 //self.assets.dot.draw(ctx, gift_point.to_point(), 0.0)?;
             }
@@ -843,7 +1066,7 @@ impl EventHandler for Globals {
                 ctx,
                 DrawParam::default()
                     .dest(in_bounds_point.to_point())
-                    .color(Color::from_rgb(255, 128, 128))
+                    .color(self.palette.hover)
             )?;
 
             // neighbour-debugging code; uncomment me, it's fun!
@@ -864,66 +1087,104 @@ impl EventHandler for Globals {
             //    },
             //}
         }
+        self.layout.use_screen_coordinates(ctx)?;
 
-        if let Some(alert_current) = self.alert_current {
+        if self.alerts.draw(ctx, self.assets.font, &self.palette)? {
+            // the toast takes the place of the hints
+        }
+        else if let Some((name, until_time)) = self.unlocked.clone() {
             let center = Vec2::new(
-                WINDOW_WIDTH as f32 / 2.0,
-                WINDOW_HEIGHT as f32 - 20.0,
-            );
-            let text = Text::new(
-                TextFragment::new(self.alerts[alert_current].message)
-                    .color(Color::from_rgb(255, 0, 0))
-                    .font(self.assets.font)
+                self.layout.width / 2.0,
+                self.layout.height - 20.0,
             );
-            text::draw_centered_text(ctx, &text, center, 0.0, DrawParam::default())?;
-            if self.alerts[alert_current].until_time < get_current_time(ctx) {
-                self.alert_current = None
+            text::draw_centered_message(ctx, self.assets.font, &self.locale.get("achievements.unlocked"), center + Vec2::new(-200.0, -15.0), self.palette.accent)?;
+            text::draw_centered_message(ctx, self.assets.font, &name, center + Vec2::new(0.0, 5.0), self.palette.text)?;
+            if until_time < get_current_time(ctx) {
+                self.unlocked = None;
             }
         }
-        else {
-            for achievement in self.achievements.iter() {
-                if !achievement.achieved {
-                    let center = Vec2::new(
-                        WINDOW_WIDTH as f32 / 2.0,
-                        WINDOW_HEIGHT as f32 - 20.0,
-                    );
-
-                    {
-                        let text = Text::new(
-                            TextFragment::new("CHALLENGE")
-                                .font(self.assets.font)
-                        );
-                        text::draw_centered_text(
-                            ctx,
-                            &text,
-                            center + Vec2::new(-200.0, -15.0),
-                            0.0,
-                            DrawParam::default()
-                                .color(Color::from_rgb(247, 148, 30))
-                        )?;
-                    }
+        else if let Some(level) = self.level.and_then(|i| self.levels.get(i)) {
+            let center = Vec2::new(
+                self.layout.width / 2.0,
+                self.layout.height - 20.0,
+            );
+            text::draw_centered_message(ctx, self.assets.font, &self.locale.get("hint.title"), center + Vec2::new(-200.0, -15.0), self.palette.accent)?;
+            text::draw_centered_message(ctx, self.assets.font, &level.localized_hint(&self.locale), center + Vec2::new(0.0, 5.0), self.palette.text)?;
+        }
+        else if let Some(achievement) = self.achievements.current_tutorial().filter(|_| self.settings.show_tutorial) {
+            let center = Vec2::new(
+                self.layout.width / 2.0,
+                self.layout.height - 20.0,
+            );
 
-                    {
-                        let text = Text::new(
-                            TextFragment::new(achievement.message)
-                                .font(self.assets.font)
-                        );
-                        text::draw_centered_text(
-                            ctx,
-                            &text,
-                            center + Vec2::new(0.0, 5.0),
-                            0.0,
-                            DrawParam::default()
-                                .color(Color::from_rgb(255, 255, 255))
-                        )?;
-                    }
+            {
+                let text = Text::new(
+                    TextFragment::new(self.locale.get("hint.challenge"))
+                        .font(self.assets.font)
+                );
+                text::draw_centered_text(
+                    ctx,
+                    &text,
+                    center + Vec2::new(-200.0, -15.0),
+                    0.0,
+                    DrawParam::default()
+                        .color(self.palette.accent)
+                )?;
+            }
 
-                    break;
-                }
+            {
+                let text = Text::new(
+                    TextFragment::new(achievement.localized_message(&self.locale))
+                        .font(self.assets.font)
+                );
+                text::draw_centered_text(
+                    ctx,
+                    &text,
+                    center + Vec2::new(0.0, 5.0),
+                    0.0,
+                    DrawParam::default()
+                        .color(self.palette.text)
+                )?;
             }
         }
 
 
+        if let Some(ref race) = self.race {
+            let elapsed = get_current_time(ctx) - self.start_time;
+            race.draw(ctx, self.assets.font, &self.palette, &self.locale, &self.bindings, elapsed, self.board.turn)?;
+        }
+        if let Some(level) = self.level.and_then(|i| self.levels.get(i)) {
+            level.draw_status(ctx, self.assets.font, &self.palette, &self.locale, &self.bindings, &self.board, self.level_outcome)?;
+        }
+        if let Some(ref daily) = self.daily {
+            daily.draw(ctx, self.assets.font, &self.palette, &self.locale, &self.bindings, self.board.turn)?;
+        }
+        match self.sandbox {
+            Some(ref sandbox) => sandbox.draw(ctx, self.assets.font, &self.palette, &self.locale, &self.bindings)?,
+            None              => {
+                self.toolbar.draw(ctx, self.assets.font, &self.palette, &self.locale, &self.bindings, &self.board)?;
+                match self.selection {
+                    Some(ref selection) => {
+                        selection.draw(ctx, &self.palette, &self.layout, &self.board)?;
+                        selection.draw_status(ctx, self.assets.font, &self.palette, &self.locale, &self.bindings, &self.board)?;
+                    },
+                    None                => self.toolbar.draw_targets(ctx, self.assets.font, &self.palette, &self.layout, &self.board)?,
+                }
+            },
+        }
+        match self.screen {
+            Screen::Playing               => (),
+            Screen::LevelSelect(selected) => level::draw_select(ctx, self.assets.font, &self.palette, &self.locale, &self.bindings, &self.levels, &self.progress, selected)?,
+            Screen::Achievements          => self.achievements.draw_screen(ctx, self.assets.font, &self.palette, &self.locale, &self.bindings)?,
+            Screen::Log                   => self.alerts.draw_log(ctx, self.assets.font, &self.palette, &self.locale, &self.bindings)?,
+            Screen::Stats                 => self.history.draw(ctx, self.assets.font, &self.palette, &self.locale, &self.bindings)?,
+            Screen::Bindings(rebinding)   => rebinding.draw(ctx, self.assets.font, &self.palette, &self.locale, &self.bindings)?,
+            Screen::Settings(settings_screen) => settings_screen.draw(ctx, self.assets.font, &self.palette, &self.locale, &self.overrides.apply(&self.settings))?,
+        }
+        if let Some(command) = self.confirming {
+            bindings::draw_confirmation(ctx, self.assets.font, &self.palette, &self.locale, command, &self.bindings)?;
+        }
+
         //if get_current_time(ctx) - self.start_time > Duration::from_millis(1000) {
         //    self.start_time = get_current_time(ctx);
         //    println!("FPS: {}", ggez::timer::get_fps(ctx));
@@ -935,7 +1196,251 @@ impl EventHandler for Globals {
     }
 }
 
+// The language picked in the settings, or else the system's if there is a
+// file for it.
+fn settings_language(ctx: &mut Context, settings: &settings::Settings) -> String {
+    settings.language.clone()
+        .or_else(|| locale::system_language().filter(|language| locale::languages(ctx).contains(language)))
+        .unwrap_or_else(|| locale::DEFAULT_LANGUAGE.to_string())
+}
+
+fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|i| args.get(i + 1))
+        .map(|value| value.as_str())
+}
+
+// The rules to play the headless modes with, to compare the economies.
+// usage: ludum-dare-44 --bot|--solve|--replay|--timelapse ... [--economy fixed|life|size]
+fn economy_or_exit(args: &[String]) -> Economy {
+    match arg_value(args, "--economy") {
+        None => Economy::Fixed,
+        Some(name) => match Economy::from_name(name) {
+            Some(economy) => economy,
+            None => {
+                println!("unknown economy {:?}, expected fixed, life or size", name);
+                std::process::exit(2);
+            },
+        },
+    }
+}
+
+// usage: ludum-dare-44 --bot greedy|random|lookahead [--target 5] [--turns 500] [--seed 0]
+fn run_bot(args: &[String]) {
+    // the name is optional, so the first flag isn't one
+    let name = args.first().map(|name| name.as_str()).filter(|name| !name.starts_with("--")).unwrap_or("greedy");
+    let economy = economy_or_exit(args);
+    let target_life: usize = arg_value(args, "--target").and_then(|v| v.parse().ok()).unwrap_or(5);
+    let max_turns: usize = arg_value(args, "--turns").and_then(|v| v.parse().ok()).unwrap_or(500);
+    let seed: u64 = arg_value(args, "--seed").and_then(|v| v.parse().ok()).unwrap_or(0);
+
+    match strategy::from_name(name, seed) {
+        None => {
+            println!("unknown strategy {:?}, expected greedy, random or lookahead", name);
+            std::process::exit(2);
+        },
+        Some(mut strategy) => {
+            match strategy::run_headless(strategy.as_mut(), economy, target_life, max_turns) {
+                Some(turns) => println!("{}: reached life {} in {} turns", strategy.name(), target_life, turns),
+                None => {
+                    println!("{}: did not reach life {} within {} turns", strategy.name(), target_life, max_turns);
+                    std::process::exit(1);
+                },
+            }
+        },
+    }
+}
+
+fn load_level_or_exit(path: &str) -> level::Level {
+    match level::Level::load(std::path::Path::new(path)) {
+        Ok(level) => level,
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(2);
+        },
+    }
+}
+
+fn start_board_or_exit(level: Option<&level::Level>, economy: Economy) -> Board {
+    let mut board = match level.map(|level| level.to_board()) {
+        None => Board::new(),
+        Some(Ok(board)) => board,
+        Some(Err(e)) => {
+            println!("{}", e);
+            std::process::exit(2);
+        },
+    };
+    board.economy = economy;
+    board
+}
+
+// usage: ludum-dare-44 --solve [--target 5|max] [--level level.txt] [--beam 20] [--turns 200] [--actions 3] [--out plan.txt]
+fn run_solver(args: &[String]) {
+    let level = arg_value(args, "--level").map(load_level_or_exit);
+    let start = start_board_or_exit(level.as_ref(), economy_or_exit(args));
+    let target = match (level, arg_value(args, "--target")) {
+        (Some(level), _) => solver::Target::Level(Box::new(level)),
+        (None, Some("max")) => solver::Target::MaximumLife,
+        (None, value) => solver::Target::LifeLevel(value.and_then(|v| v.parse().ok()).unwrap_or(5)),
+    };
+    let options = solver::Options {
+        target,
+        beam_width: arg_value(args, "--beam").and_then(|v| v.parse().ok()).unwrap_or(20),
+        max_turns: arg_value(args, "--turns").and_then(|v| v.parse().ok()).unwrap_or(200),
+        actions_per_turn: arg_value(args, "--actions").and_then(|v| v.parse().ok()).unwrap_or(3),
+    };
+
+    match solver::solve(&start, &options) {
+        None => {
+            println!("no solution found within {} turns", options.max_turns);
+            std::process::exit(1);
+        },
+        Some(solution) => {
+            // double-check that the script really is replayable
+            let replayed = solver::replay(&start, &solution.script, solution.turns);
+            assert_eq!(replayed.stats.life_max, solution.life_max);
+
+            println!("# par time: {} turns, life {}", solution.turns, solution.life_max);
+            match arg_value(args, "--out") {
+                Some(path) => solution.script.save(path).unwrap(),
+                None => print!("{}", solution.script),
+            }
+        },
+    }
+}
+
+// usage: ludum-dare-44 --replay plan.txt [--level level.txt] [--turns 200]
+fn run_replay(args: &[String]) {
+    let level = arg_value(args, "--level").map(load_level_or_exit);
+    let path = args.first().map(|path| path.as_str()).unwrap_or("plan.txt");
+    let script = match script::Script::load(path) {
+        Ok(script) => script,
+        Err(e) => {
+            println!("could not load {}: {}", path, e);
+            std::process::exit(2);
+        },
+    };
+    let turns: usize = arg_value(args, "--turns").and_then(|v| v.parse().ok()).unwrap_or(script.last_turn() + 1);
+
+    let mut board = start_board_or_exit(level.as_ref(), economy_or_exit(args));
+    while board.turn < turns {
+        for step in script.play_turn(&mut board) {
+            println!("rejected: {}", step);
+        }
+    }
+    println!("turn {}: bounty {:.1}, life {:.1}, best life {}",
+             board.turn, board.bounty_amount, board.life_amount, board.stats.life_max);
+}
+
+// Writes one PNG per turn of a replay into a directory, without opening a
+// window, e.g. to turn into a video.
+// usage: ludum-dare-44 --timelapse plan.txt [--level level.txt] [--turns 50] [--out timelapse] [--scale 2] [--graph] [--palette high_contrast]
+fn run_timelapse(args: &[String]) {
+    let level = arg_value(args, "--level").map(load_level_or_exit);
+    let path = args.first().map(|path| path.as_str()).unwrap_or("plan.txt");
+    let script = match script::Script::load(path) {
+        Ok(script) => script,
+        Err(e) => {
+            println!("could not load {}: {}", path, e);
+            std::process::exit(2);
+        },
+    };
+    let turns: usize = arg_value(args, "--turns").and_then(|v| v.parse().ok()).unwrap_or(script.last_turn() + 1);
+    let out = std::path::Path::new(arg_value(args, "--out").unwrap_or("timelapse"));
+    let scale: f32 = arg_value(args, "--scale").and_then(|v| v.parse().ok()).filter(|&scale| scale > 0.0).unwrap_or(2.0);
+    let graph = args.iter().any(|arg| arg == "--graph");
+    let preset = arg_value(args, "--palette").and_then(palette::Preset::from_name).unwrap_or(palette::Preset::Default);
+
+    let mut board = start_board_or_exit(level.as_ref(), economy_or_exit(args));
+    let mut boards = vec!(board.clone());
+    while board.turn < turns {
+        for step in script.play_turn(&mut board) {
+            println!("rejected: {}", step);
+        }
+        boards.push(board.clone());
+    }
+
+    if let Err(e) = std::fs::create_dir_all(out) {
+        println!("could not create {:?}: {}", out, e);
+        std::process::exit(2);
+    }
+    let palette = palette::Palette::new(preset);
+    let timelapse = timelapse::Timelapse::new(&boards, &palette, scale, graph);
+    for i in 0..boards.len() {
+        let path = out.join(format!("turn-{:04}.png", i));
+        let written = timelapse.frame(i).to_png()
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| std::fs::write(&path, bytes).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            println!("could not write {:?}: {}", path, e);
+            std::process::exit(2);
+        }
+    }
+    println!("{} frames of {}x{} written to {:?}", boards.len(), timelapse.width, timelapse.height, out);
+}
+
+// Prints a daily puzzle in the level file format, e.g. to solve it.
+// usage: ludum-dare-44 --daily [--day 18000]
+fn run_daily(args: &[String]) {
+    let day: u64 = arg_value(args, "--day").and_then(|v| v.parse().ok()).unwrap_or(daily::today());
+    print!("{}", daily::generate(day));
+}
+
+// Renders a level file, e.g. one exported from the sandbox, without opening
+// a window.
+// usage: ludum-dare-44 --export-image level.txt [--out tree.svg] [--resources resources]
+fn run_export_image(args: &[String]) {
+    let level = load_level_or_exit(args.first().map(|path| path.as_str()).unwrap_or("sandbox-level.txt"));
+    let board = start_board_or_exit(Some(&level), Economy::Fixed);
+    let out = arg_value(args, "--out").unwrap_or("tree.svg");
+    let resources = std::path::Path::new(arg_value(args, "--resources").unwrap_or("resources"));
+    let mut read_file = |path: &str| std::fs::read(resources.join(path.trim_start_matches('/'))).ok();
+    match export::to_svg(&board, &palette::Palette::new(palette::Preset::Default), &mut read_file) {
+        Ok(svg) => match std::fs::write(out, svg) {
+            Ok(())  => println!("tree exported to {:?}", out),
+            Err(e)  => {
+                println!("could not write {:?}: {}", out, e);
+                std::process::exit(2);
+            },
+        },
+        Err(e) => {
+            println!("could not export the tree: {}", e);
+            std::process::exit(1);
+        },
+    }
+}
+
+// Fails if a language lacks some of the text, e.g. before a release.
 pub fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(|arg| arg.as_str()) {
+        Some("--bot") => return run_bot(&args[1..]),
+        Some("--solve") => return run_solver(&args[1..]),
+        Some("--replay") => return run_replay(&args[1..]),
+        Some("--daily") => return run_daily(&args[1..]),
+        Some("--export-image") => return run_export_image(&args[1..]),
+        Some("--timelapse") => return run_timelapse(&args[1..]),
+        _ => (),
+    }
+
+    // without a sound card, e.g. on a build machine, the sounds can go
+    // nowhere or into a log instead
+    // usage: ludum-dare-44 [--no-audio] [--record-audio sounds.log]
+    let backend = if let Some(path) = arg_value(&args, "--record-audio") {
+        match std::fs::File::create(path) {
+            Ok(file) => sound::Backend::Recording(Rc::new(RefCell::new(sound::Recording::new(Box::new(file))))),
+            Err(e) => {
+                println!("could not create {:?}: {}", path, e);
+                std::process::exit(1);
+            },
+        }
+    } else if args.iter().any(|arg| arg == "--no-audio") {
+        sound::Backend::Null
+    } else {
+        sound::Backend::Device
+    };
+
     let (mut ctx, event_loop) = ContextBuilder::new(
         GAME_NAME,
         "Michaelson Britt, Samuel Gélineau, Dylan Khor, Zhentao Li, Kyla Squires, and Farren Wang",
@@ -944,21 +1449,67 @@ pub fn main() {
             window_mode: ggez::conf::WindowMode {
                 width:  WINDOW_WIDTH,
                 height: WINDOW_HEIGHT,
+                min_width:  WINDOW_WIDTH / 2.0,
+                min_height: WINDOW_HEIGHT / 2.0,
+                resizable: true,
+                resize_on_scale_factor_change: true,
                 .. Default::default()
             },
             window_setup: ggez::conf::WindowSetup {
                 title: GAME_NAME.to_owned(),
                 .. Default::default()
             },
+            modules: ggez::conf::ModuleConf {
+                audio: matches!(backend, sound::Backend::Device),
+                .. Default::default()
+            },
             .. Default::default()
         },
     ).build().unwrap();
+    // the window size is in physical pixels, so it needs to grow on a
+    // high-DPI display
+    let scale_factor = window(&ctx).scale_factor() as f32;
+    if scale_factor > 1.0 {
+        set_drawable_size(&mut ctx, WINDOW_WIDTH * scale_factor, WINDOW_HEIGHT * scale_factor).unwrap_or(());
+    }
 
-    let mut globals = Globals::new(&mut ctx).unwrap();
-    globals.guitar_channel.source.play(&mut ctx).unwrap_or(());
-    globals.clarinet_channel.source.play(&mut ctx).unwrap_or(());
-    globals.high_pithed_clarinet_channel.source.play(&mut ctx).unwrap_or(());
-    globals.dreamy_bells_channel.source.play(&mut ctx).unwrap_or(());
+    let settings = settings::Settings::load(&ggez::filesystem::user_config_dir(&ctx).join("settings.txt"));
+    let mut globals = Globals::new(&mut ctx, settings, &backend).unwrap();
+    // these override the settings without saving them
+    // usage: ludum-dare-44 [--lang fr]
+    if let Some(language) = arg_value(&args, "--lang") {
+        globals.overrides.language = Some(language.to_string());
+    }
+    // usage: ludum-dare-44 [--fullscreen]
+    if args.iter().any(|arg| arg == "--fullscreen") {
+        globals.overrides.fullscreen = Some(true);
+    }
+    // usage: ludum-dare-44 [--palette deuteranopia|protanopia|tritanopia|high_contrast]
+    if let Some(name) = arg_value(&args, "--palette") {
+        match palette::Preset::from_name(name) {
+            Some(preset) => globals.overrides.palette = Some(preset),
+            None => println!("unknown palette {:?}, expected default, deuteranopia, protanopia, tritanopia or high_contrast", name),
+        }
+    }
+    // usage: ludum-dare-44 [--economy fixed|life|size]
+    if let Some(name) = arg_value(&args, "--economy") {
+        match Economy::from_name(name) {
+            Some(economy) => globals.overrides.economy = Some(economy),
+            None => println!("unknown economy {:?}, expected fixed, life or size", name),
+        }
+    }
+    // usage: ludum-dare-44 [--race "life 5"] [--seed 0]
+    if let Some(goal) = arg_value(&args, "--race") {
+        match goal::Goal::parse(goal) {
+            Some(goal) => {
+                let seed: u64 = arg_value(&args, "--seed").and_then(|v| v.parse().ok()).unwrap_or(0);
+                globals.start_race(&mut ctx, goal, seed);
+            },
+            None => println!("unknown goal {:?}, expected e.g. \"life 5\", \"bounty 300\" or \"creature squirrel\"", goal),
+        }
+    }
+    globals.apply_settings(&mut ctx);
+    globals.music.play(&ctx);
 
     ggez::event::run(ctx, event_loop, globals);
 }
@@ -0,0 +1,313 @@
+use core::time::Duration;
+use ggez::Context;
+use std::io::Read;
+
+use achievement;
+use board::Board;
+use channel::{Channel, Easing};
+use globals::*;
+use life::Stats;
+use sound::{Audio, Backend};
+
+
+// One stem of the soundtrack, see resources/music.txt.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layer {
+    pub name: String,
+    pub file: String,
+    // the fields which are added up to drive the volume
+    pub fields: Vec<String>,
+    // silent at `from` or below, at full volume at `to` or above
+    pub from: f32,
+    pub to: f32,
+    pub volume: f32,
+    pub fade: Duration,
+    pub easing: Easing,
+    // the other layers which this one makes quieter, and by how much
+    pub ducks: Vec<(String, f32)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mix {
+    // beats per minute and beats per bar; layers only come in on a new bar
+    pub tempo: Option<(f32, usize)>,
+    pub layers: Vec<Layer>,
+}
+
+// Plays every layer in a loop from the start, so that they stay in step, and
+// only changes their volumes.
+#[derive(Debug)]
+pub struct Mixer {
+    tempo: Option<(f32, usize)>,
+    layers: Vec<Layer>,
+    channels: Vec<Channel>,
+    start_time: Duration,
+    // the music volume from the settings
+    gain: f32,
+}
+
+// The fields of achievement::field_value, plus the amounts in the sidebars.
+fn field_value(board: &Board, field: &str) -> Option<f32> {
+    match field {
+        // called Bounty in the UI
+        "life_amount"   => Some(board.life_amount),
+        // called Life in the UI
+        "bounty_amount" => Some(board.bounty_amount),
+        _               => achievement::field_value(&board.stats, field),
+    }
+}
+
+fn is_field(field: &str) -> bool {
+    field == "life_amount" || field == "bounty_amount" || achievement::field_value(&Stats::default(), field).is_some()
+}
+
+// e.g. "leaves + flowers"
+fn parse_fields(s: &str) -> Option<Vec<String>> {
+    let fields: Vec<String> = s.split('+').map(|field| field.trim().to_string()).collect();
+    if fields.iter().all(|field| is_field(field)) {
+        Some(fields)
+    } else {
+        None
+    }
+}
+
+// A "tempo" line, then blocks which start with "layer NAME".
+pub fn parse(contents: &str) -> Result<Mix, String> {
+    let mut mix = Mix {
+        tempo: None,
+        layers: Vec::new(),
+    };
+    for (i, line) in contents.lines().enumerate() {
+        let line = match line.find('#') {
+            Some(j) => &line[..j],
+            None    => line,
+        }.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (keyword, rest) = match line.find(' ') {
+            Some(j) => (&line[..j], line[j..].trim()),
+            None    => (line, ""),
+        };
+        let error = || format!("line {}: could not parse {:?}", i + 1, line);
+        let words: Vec<&str> = rest.split_whitespace().collect();
+        match keyword {
+            "tempo" => {
+                let bpm: f32 = words.first().and_then(|word| word.parse().ok()).filter(|&bpm| bpm > 0.0).ok_or_else(error)?;
+                let beats: usize = words.get(1).and_then(|word| word.parse().ok()).filter(|&beats| beats > 0).ok_or_else(error)?;
+                mix.tempo = Some((bpm, beats));
+                continue;
+            },
+            "layer" => {
+                mix.layers.push(Layer {
+                    name: rest.to_string(),
+                    file: String::new(),
+                    fields: Vec::new(),
+                    from: 0.0,
+                    to: 1.0,
+                    volume: 1.0,
+                    fade: Duration::from_millis(1000),
+                    easing: Easing::Linear,
+                    ducks: Vec::new(),
+                });
+                continue;
+            },
+            _ => (),
+        }
+        let layer = mix.layers.last_mut().ok_or_else(error)?;
+        match keyword {
+            "file"   => layer.file = rest.to_string(),
+            "level"  => layer.fields = parse_fields(rest).ok_or_else(error)?,
+            "curve"  => {
+                layer.from = words.first().and_then(|word| word.parse().ok()).ok_or_else(error)?;
+                layer.to = words.get(1).and_then(|word| word.parse().ok()).ok_or_else(error)?;
+            },
+            "volume" => layer.volume = rest.parse().ok().filter(|volume| (0.0..=1.0).contains(volume)).ok_or_else(error)?,
+            "fade"   => {
+                let seconds: f32 = words.first().and_then(|word| word.parse().ok()).filter(|&seconds| seconds >= 0.0).ok_or_else(error)?;
+                layer.fade = Duration::from_secs_f32(seconds);
+                if let Some(easing) = words.get(1) {
+                    layer.easing = Easing::from_name(easing).ok_or_else(error)?;
+                }
+            },
+            "duck"   => {
+                let amount: f32 = words.get(1).and_then(|word| word.parse().ok()).filter(|amount| (0.0..=1.0).contains(amount)).ok_or_else(error)?;
+                layer.ducks.push((words[0].to_string(), amount));
+            },
+            _        => return Err(error()),
+        }
+    }
+    for layer in mix.layers.iter() {
+        if layer.file.is_empty() || layer.fields.is_empty() {
+            return Err(format!("layer {:?} needs a file and a level", layer.name));
+        }
+        for (other, _) in layer.ducks.iter() {
+            if !mix.layers.iter().any(|layer| layer.name == *other) {
+                return Err(format!("layer {:?} ducks {:?}, which is not a layer", layer.name, other));
+            }
+        }
+    }
+    Ok(mix)
+}
+
+impl Layer {
+    // from 0.0 to 1.0, following the curve
+    fn level(&self, board: &Board) -> f32 {
+        let value: f32 = self.fields.iter()
+            .map(|field| field_value(board, field).unwrap_or(0.0))
+            .sum();
+        if self.to == self.from {
+            if value >= self.to {1.0} else {0.0}
+        } else {
+            ((value - self.from) / (self.to - self.from)).clamp(0.0, 1.0)
+        }
+    }
+}
+
+impl Mixer {
+    // from resources/music.txt; a broken file or stem means less music, not
+    // no game
+    pub fn load(ctx: &mut Context, backend: &Backend) -> Mixer {
+        let mut contents = String::new();
+        let mix = ggez::filesystem::open(ctx, "/music.txt")
+            .map_err(|e| e.to_string())
+            .and_then(|mut file| file.read_to_string(&mut contents).map_err(|e| e.to_string()))
+            .and_then(|_| parse(&contents))
+            .unwrap_or_else(|e| {
+                println!("could not load the music: {}", e);
+                Mix {
+                    tempo: None,
+                    layers: Vec::new(),
+                }
+            });
+        Mixer::new(ctx, backend, mix)
+    }
+
+    pub fn new(audio: &mut dyn Audio, backend: &Backend, mix: Mix) -> Mixer {
+        let mut layers = Vec::new();
+        let mut channels = Vec::new();
+        for layer in mix.layers {
+            match Channel::new(audio, backend, &layer.file) {
+                Ok(mut channel) => {
+                    channel.easing = layer.easing;
+                    layers.push(layer);
+                    channels.push(channel);
+                },
+                Err(e) => println!("could not load the music layer {:?}: {}", layer.name, e),
+            }
+        }
+        Mixer {
+            tempo: mix.tempo,
+            layers,
+            channels,
+            start_time: audio.now(),
+            gain: 1.0,
+        }
+    }
+
+    pub fn play(&mut self, audio: &dyn Audio) {
+        self.start_time = audio.now();
+        for channel in self.channels.iter_mut() {
+            channel.source.play(audio);
+        }
+    }
+
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain;
+    }
+
+    // The start of the next bar, or now if there is no tempo.
+    fn next_bar(&self, now: Duration) -> Duration {
+        match self.tempo {
+            Some((bpm, beats)) => {
+                let bar = 60.0 / bpm * beats as f32;
+                let elapsed = duration_to_f32(now - self.start_time);
+                self.start_time + Duration::from_secs_f32((elapsed / bar).ceil() * bar)
+            },
+            None => now,
+        }
+    }
+
+    pub fn update(&mut self, audio: &dyn Audio, board: &Board) {
+        let levels: Vec<f32> = self.layers.iter().map(|layer| layer.level(board)).collect();
+        let mut targets: Vec<f32> = self.layers.iter().zip(levels.iter())
+            .map(|(layer, &level)| layer.volume * level)
+            .collect();
+        for (layer, &level) in self.layers.iter().zip(levels.iter()) {
+            for &(ref other, amount) in layer.ducks.iter() {
+                if let Some(j) = self.layers.iter().position(|layer| layer.name == *other) {
+                    targets[j] *= 1.0 - amount * level;
+                }
+            }
+        }
+
+        let now = audio.now();
+        let next_bar = self.next_bar(now);
+        for ((layer, channel), &target) in self.layers.iter().zip(self.channels.iter_mut()).zip(targets.iter()) {
+            if (target - channel.target_volume).abs() > 0.01 {
+                // coming in waits for the beat, anything else starts now;
+                // a layer which is still silent keeps waiting even if its
+                // level changes again before the bar
+                let start_time = if channel.volume(audio) == 0.0 {
+                    next_bar
+                } else {
+                    now
+                };
+                channel.set_future_volume(audio, start_time, layer.fade, target);
+            }
+            channel.gain = self.gain;
+            channel.update(audio);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAYER: &str = "layer guitar\nfile /guitar.ogg\nlevel leaves + flowers\n";
+
+    #[test]
+    fn the_shipped_mix_parses() {
+        let mix = parse(include_str!("../resources/music.txt")).unwrap();
+        assert_eq!(mix.tempo, Some((120.0, 2)));
+        assert_eq!(mix.layers[0].fields, vec!("leaves", "flowers"));
+        assert_eq!(mix.layers[0].easing, Easing::EaseInOut);
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        assert_eq!(parse("file /guitar.ogg").err(), Some("line 1: could not parse \"file /guitar.ogg\"".to_string()));
+        assert!(parse("tempo 0 2").is_err());
+        assert!(parse("tempo 120").is_err());
+        assert!(parse(&format!("{}level leafs", LAYER)).is_err());
+        assert!(parse(&format!("{}volume 1.5", LAYER)).is_err());
+        assert!(parse(&format!("{}fade 1 bouncy", LAYER)).is_err());
+        assert!(parse(&format!("{}fade -1", LAYER)).is_err());
+        assert!(parse(&format!("{}pan left", LAYER)).is_err());
+    }
+
+    #[test]
+    fn layers_need_a_file_a_level_and_real_ducks() {
+        assert_eq!(
+            parse("layer guitar\nlevel leaves").err(),
+            Some("layer \"guitar\" needs a file and a level".to_string()),
+        );
+        assert_eq!(
+            parse(&format!("{}duck drums 0.5", LAYER)).err(),
+            Some("layer \"guitar\" ducks \"drums\", which is not a layer".to_string()),
+        );
+        assert!(parse(&format!("{}duck guitar 1.5", LAYER)).is_err());
+    }
+
+    #[test]
+    fn the_level_follows_the_curve() {
+        let layer = parse(&format!("{}curve 1 3", LAYER)).unwrap().layers.remove(0);
+        let mut board = Board::new();
+        for &(leaves, flowers, level) in &[(0, 0, 0.0), (1, 0, 0.0), (1, 1, 0.5), (2, 1, 1.0), (5, 5, 1.0)] {
+            board.stats.leaf_count = leaves;
+            board.stats.flower_count = flowers;
+            assert_eq!(layer.level(&board), level, "{} leaves and {} flowers", leaves, flowers);
+        }
+    }
+}
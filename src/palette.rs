@@ -0,0 +1,259 @@
+use ggez::graphics::Color;
+use glam::f32::Vec2;
+
+use cell::Gift;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    Default,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+    HighContrast,
+}
+
+// Every colour which carries information. The most important ones are also
+// paired with a shape cue: the two meters use circles and diamonds, moss gets
+// an outline, the gifts have a marker each in the graphs, and warnings and
+// errors are prefixed with "[!]" and "[X]".
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Marker {
+    Circle,
+    Square,
+    Diamond,
+    TriangleUp,
+    TriangleDown,
+    Cross,
+    Plus,
+}
+
+// Okabe-Ito has exactly seven colours besides black, which stay apart for
+// all three kinds of colour blindness.
+fn okabe_ito_gifts() -> [Color; 7] {
+    [
+        Color::from_rgb(0, 158, 115),
+        Color::from_rgb(204, 121, 167),
+        Color::from_rgb(213, 94, 0),
+        Color::from_rgb(0, 114, 178),
+        Color::from_rgb(240, 228, 66),
+        Color::from_rgb(86, 180, 233),
+        Color::from_rgb(230, 159, 0),
+    ]
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    pub preset: Preset,
+    // the sidebars, named after their titles in the UI
+    pub life_sidebar: Color,
+    pub bounty_sidebar: Color,
+    pub sidebar_title: Color,
+    // the dots in the sidebars, named after the fields in Board
+    pub meter_bounty: Color,
+    pub meter_life: Color,
+    pub hex_grid: Color,
+    pub moss: Color,
+    // in the order of Gift::all(), where their images can't be used
+    pub gifts: [Color; 7],
+    pub hover: Color,
+    // titles and highlighted rows
+    pub accent: Color,
+    pub text: Color,
+    // key reminders at the bottom of the screens
+    pub help: Color,
+    pub disabled: Color,
+    pub debug: Color,
+    pub warning: Color,
+    pub error: Color,
+}
+
+impl Preset {
+    pub fn all() -> Vec<Preset> {
+        vec!(
+            Preset::Default,
+            Preset::Deuteranopia,
+            Preset::Protanopia,
+            Preset::Tritanopia,
+            Preset::HighContrast,
+        )
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Default      => "default",
+            Preset::Deuteranopia => "deuteranopia",
+            Preset::Protanopia   => "protanopia",
+            Preset::Tritanopia   => "tritanopia",
+            Preset::HighContrast => "high_contrast",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Preset> {
+        Preset::all().into_iter().find(|preset| preset.name() == name)
+    }
+}
+
+impl Palette {
+    // The colour-blind presets replace the colours which the corresponding
+    // deficiency confuses with ones from the Okabe-Ito palette.
+    pub fn new(preset: Preset) -> Palette {
+        let default = Palette {
+            preset,
+            life_sidebar: Color::from_rgb(181, 208, 212),
+            bounty_sidebar: Color::from_rgb(242, 240, 186),
+            sidebar_title: Color::from_rgb(0, 0, 0),
+            meter_bounty: Color::from_rgb(15, 117, 188),
+            meter_life: Color::from_rgb(247, 148, 30),
+            hex_grid: Color::from_rgb(163, 186, 188),
+            moss: Color::from_rgb(128, 255, 128),
+            gifts: [
+                Color::from_rgb(76, 153, 0),
+                Color::from_rgb(255, 130, 200),
+                Color::from_rgb(180, 0, 60),
+                Color::from_rgb(150, 100, 40),
+                Color::from_rgb(240, 200, 0),
+                Color::from_rgb(200, 160, 110),
+                Color::from_rgb(220, 110, 30),
+            ],
+            hover: Color::from_rgb(255, 128, 128),
+            accent: Color::from_rgb(247, 148, 30),
+            text: Color::from_rgb(255, 255, 255),
+            help: Color::from_rgb(181, 208, 212),
+            disabled: Color::from_rgb(128, 128, 128),
+            debug: Color::from_rgb(181, 208, 212),
+            warning: Color::from_rgb(247, 148, 30),
+            error: Color::from_rgb(255, 0, 0),
+        };
+        match preset {
+            Preset::Default => default,
+            // red and green look alike
+            Preset::Deuteranopia => Palette {
+                meter_bounty: Color::from_rgb(0, 114, 178),
+                meter_life: Color::from_rgb(230, 159, 0),
+                moss: Color::from_rgb(86, 180, 233),
+                gifts: okabe_ito_gifts(),
+                hover: Color::from_rgb(240, 228, 66),
+                warning: Color::from_rgb(230, 159, 0),
+                error: Color::from_rgb(204, 121, 167),
+                .. default
+            },
+            // the same, and red also looks darker
+            Preset::Protanopia => Palette {
+                meter_bounty: Color::from_rgb(0, 114, 178),
+                meter_life: Color::from_rgb(240, 228, 66),
+                moss: Color::from_rgb(86, 180, 233),
+                gifts: okabe_ito_gifts(),
+                hover: Color::from_rgb(255, 255, 255),
+                accent: Color::from_rgb(240, 228, 66),
+                warning: Color::from_rgb(240, 228, 66),
+                error: Color::from_rgb(86, 180, 233),
+                .. default
+            },
+            // blue and green look alike, and so do yellow and violet
+            Preset::Tritanopia => Palette {
+                bounty_sidebar: Color::from_rgb(240, 240, 240),
+                meter_bounty: Color::from_rgb(0, 158, 115),
+                meter_life: Color::from_rgb(213, 94, 0),
+                moss: Color::from_rgb(204, 121, 167),
+                gifts: okabe_ito_gifts(),
+                hover: Color::from_rgb(213, 94, 0),
+                accent: Color::from_rgb(213, 94, 0),
+                warning: Color::from_rgb(213, 94, 0),
+                error: Color::from_rgb(255, 0, 0),
+                .. default
+            },
+            Preset::HighContrast => Palette {
+                life_sidebar: Color::from_rgb(255, 255, 255),
+                bounty_sidebar: Color::from_rgb(255, 255, 255),
+                sidebar_title: Color::from_rgb(0, 0, 0),
+                meter_bounty: Color::from_rgb(0, 0, 0),
+                meter_life: Color::from_rgb(0, 0, 255),
+                hex_grid: Color::from_rgb(255, 255, 255),
+                moss: Color::from_rgb(0, 255, 0),
+                gifts: [
+                    Color::from_rgb(0, 255, 0),
+                    Color::from_rgb(255, 0, 255),
+                    Color::from_rgb(255, 0, 0),
+                    Color::from_rgb(0, 160, 255),
+                    Color::from_rgb(255, 255, 0),
+                    Color::from_rgb(128, 128, 255),
+                    Color::from_rgb(255, 128, 0),
+                ],
+                hover: Color::from_rgb(0, 255, 255),
+                accent: Color::from_rgb(255, 255, 0),
+                help: Color::from_rgb(255, 255, 255),
+                disabled: Color::from_rgb(160, 160, 160),
+                debug: Color::from_rgb(255, 255, 255),
+                warning: Color::from_rgb(255, 255, 0),
+                error: Color::from_rgb(255, 64, 255),
+                .. default
+            },
+        }
+    }
+
+    pub fn gift(&self, gift: Gift) -> Color {
+        let i = Gift::all().into_iter().position(|other| other == gift).unwrap_or(0);
+        self.gifts[i]
+    }
+}
+
+impl Marker {
+    // Leaves and Berries, and Nuts, Birdnest and Squirrel, have similar hues
+    // in some presets, so their shapes are the most different.
+    pub fn for_gift(gift: Gift) -> Marker {
+        match gift {
+            Gift::Leaves   => Marker::Circle,
+            Gift::Flowers  => Marker::Plus,
+            Gift::Berries  => Marker::Cross,
+            Gift::Nuts     => Marker::Square,
+            Gift::Beehive  => Marker::TriangleUp,
+            Gift::Birdnest => Marker::TriangleDown,
+            Gift::Squirrel => Marker::Diamond,
+        }
+    }
+
+    // The lines to draw, around the origin and within a radius of 1.0.
+    pub fn strokes(self) -> Vec<Vec<Vec2>> {
+        let points = |points: &[(f32, f32)]| points.iter().map(|&(x, y)| Vec2::new(x, y)).collect::<Vec<Vec2>>();
+        match self {
+            Marker::Circle       => vec!(
+                (0..=12).map(|i| i as f32 * std::f32::consts::PI / 6.0).map(|angle| Vec2::new(angle.sin(), -angle.cos())).collect()
+            ),
+            Marker::Square       => vec!(points(&[(-0.7, -0.7), (0.7, -0.7), (0.7, 0.7), (-0.7, 0.7), (-0.7, -0.7)])),
+            Marker::Diamond      => vec!(points(&[(0.0, -1.0), (1.0, 0.0), (0.0, 1.0), (-1.0, 0.0), (0.0, -1.0)])),
+            Marker::TriangleUp   => vec!(points(&[(0.0, -1.0), (0.9, 0.6), (-0.9, 0.6), (0.0, -1.0)])),
+            Marker::TriangleDown => vec!(points(&[(0.0, 1.0), (0.9, -0.6), (-0.9, -0.6), (0.0, 1.0)])),
+            Marker::Cross        => vec!(points(&[(-0.7, -0.7), (0.7, 0.7)]), points(&[(-0.7, 0.7), (0.7, -0.7)])),
+            Marker::Plus         => vec!(points(&[(0.0, -1.0), (0.0, 1.0)]), points(&[(-1.0, 0.0), (1.0, 0.0)])),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_are_named() {
+        for preset in Preset::all() {
+            assert_eq!(Preset::from_name(preset.name()), Some(preset));
+        }
+        assert_eq!(Preset::from_name("high_contrast"), Some(Preset::HighContrast));
+        assert_eq!(Preset::from_name("High Contrast"), None);
+        assert_eq!(Preset::from_name(""), None);
+    }
+
+    #[test]
+    fn every_preset_tells_the_gifts_apart() {
+        for preset in Preset::all() {
+            let palette = Palette::new(preset);
+            for (i, &a) in Gift::all().iter().enumerate() {
+                for &b in &Gift::all()[i + 1..] {
+                    assert_ne!(palette.gift(a), palette.gift(b), "{:?} {:?} {:?}", preset, a, b);
+                    assert_ne!(Marker::for_gift(a), Marker::for_gift(b), "{:?} {:?}", a, b);
+                }
+            }
+        }
+    }
+}
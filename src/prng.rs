@@ -0,0 +1,80 @@
+use std::ops::Range;
+
+
+// SplitMix64, written out here rather than taken from rand: rand's StdRng
+// and its ranges may change from one version or platform to the next, and
+// the seeded boards must be the same for everyone, forever.
+#[derive(Clone, Debug)]
+pub struct Prng {
+    state: u64,
+}
+
+impl Prng {
+    pub fn new(seed: u64) -> Prng {
+        Prng {state: seed}
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Lemire's multiply-shift; the tiny bias doesn't matter for a puzzle.
+    fn below(&mut self, n: usize) -> usize {
+        (((self.next_u64() >> 32) * n as u64) >> 32) as usize
+    }
+
+    pub fn range(&mut self, range: Range<i32>) -> i32 {
+        range.start + self.below((range.end - range.start).max(1) as usize) as i32
+    }
+
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            None
+        } else {
+            items.get(self.below(items.len()))
+        }
+    }
+
+    // `count` different items, in the order picked.
+    pub fn sample<T: Clone>(&mut self, items: &[T], count: usize) -> Vec<T> {
+        let mut items = items.to_vec();
+        let count = count.min(items.len());
+        for i in 0..count {
+            let j = i + self.below(items.len() - i);
+            items.swap(i, j);
+        }
+        items.truncate(count);
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_reference_splitmix64() {
+        let mut prng = Prng::new(0);
+        assert_eq!(prng.next_u64(), 0xe220_a839_7b1d_cdaf);
+        assert_eq!(prng.next_u64(), 0x6e78_9e6a_a1b9_65f4);
+    }
+
+    #[test]
+    fn stays_within_bounds() {
+        let mut prng = Prng::new(7);
+        for _ in 0..1000 {
+            let value = prng.range(-3..4);
+            assert!((-3..4).contains(&value));
+        }
+        let mut sample = prng.sample(&[1, 2, 3, 4, 5], 3);
+        assert_eq!(sample.len(), 3);
+        sample.sort();
+        sample.dedup();
+        assert_eq!(sample.len(), 3);
+        assert_eq!(prng.choose::<u8>(&[]), None);
+    }
+}
@@ -0,0 +1,295 @@
+use core::time::Duration;
+use ggez::{GameResult, Context};
+use ggez::graphics::*;
+use glam::f32::Vec2;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bindings::{Bindings, Command};
+use board::Board;
+use daily;
+use layout;
+use locale::Locale;
+use palette::Palette;
+use prng::Prng;
+use goal::Goal;
+use text;
+
+
+// how many times to keep per goal and seed
+pub const LEADERBOARD_SIZE: usize = 5;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Finish {
+    pub elapsed: Duration,
+    pub turns: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub goal: String,
+    pub seed: u64,
+    pub finish: Finish,
+}
+
+// One entry per line: goal, seed, milliseconds and turns, separated by tabs.
+#[derive(Debug)]
+pub struct Leaderboard {
+    path: PathBuf,
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug)]
+pub struct Race {
+    pub goal: Goal,
+    pub seed: u64,
+    pub finish: Option<Finish>,
+    // the rank of the finish in the leaderboard, if it made it in
+    pub rank: Option<usize>,
+    // free building, the bot or the sandbox were used, so the finish isn't
+    // kept in the leaderboard
+    pub unranked: bool,
+    pub leaderboard: Leaderboard,
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let tenths = duration.as_millis() / 100;
+    format!("{}:{:02}.{}", tenths / 600, tenths / 10 % 60, tenths % 10)
+}
+
+fn parse_entry(line: &str) -> Option<Entry> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() != 4 {
+        return None;
+    }
+    Some(Entry {
+        goal: fields[0].to_string(),
+        seed: fields[1].parse().ok()?,
+        finish: Finish {
+            elapsed: Duration::from_millis(fields[2].parse().ok()?),
+            turns: fields[3].parse().ok()?,
+        },
+    })
+}
+
+impl Leaderboard {
+    pub fn load(path: &Path) -> Leaderboard {
+        let entries = fs::read_to_string(path)
+            .map(|contents| contents.lines().filter_map(parse_entry).collect())
+            .unwrap_or_default();
+        Leaderboard {
+            path: path.to_path_buf(),
+            entries,
+        }
+    }
+
+    pub fn save(&self) {
+        let contents: String = self.entries.iter()
+            .map(|entry| format!(
+                "{}\t{}\t{}\t{}\n",
+                entry.goal,
+                entry.seed,
+                entry.finish.elapsed.as_millis(),
+                entry.finish.turns,
+            ))
+            .collect();
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).unwrap_or(());
+        }
+        if let Err(e) = fs::write(&self.path, contents) {
+            println!("could not save the leaderboard to {:?}: {}", self.path, e);
+        }
+    }
+
+    // fastest first
+    pub fn top(&self, goal: Goal, seed: u64) -> Vec<Finish> {
+        let goal = goal.to_string();
+        let mut finishes: Vec<Finish> = self.entries.iter()
+            .filter(|entry| entry.goal == goal && entry.seed == seed)
+            .map(|entry| entry.finish)
+            .collect();
+        finishes.sort_by_key(|finish| finish.elapsed);
+        finishes
+    }
+
+    // Returns the rank of the new time, or None if it is too slow to be kept.
+    pub fn insert(&mut self, goal: Goal, seed: u64, finish: Finish) -> Option<usize> {
+        let rank = self.top(goal, seed)
+            .iter()
+            .filter(|other| other.elapsed <= finish.elapsed)
+            .count();
+        if rank >= LEADERBOARD_SIZE {
+            return None;
+        }
+
+        self.entries.push(Entry {
+            goal: goal.to_string(),
+            seed,
+            finish,
+        });
+        let slowest_kept = self.top(goal, seed).get(LEADERBOARD_SIZE).map(|finish| finish.elapsed);
+        if let Some(slowest_kept) = slowest_kept {
+            let goal = goal.to_string();
+            self.entries.retain(|entry|
+                !(entry.goal == goal && entry.seed == seed && entry.finish.elapsed >= slowest_kept)
+            );
+        }
+        Some(rank)
+    }
+}
+
+impl Race {
+    pub fn new(goal: Goal, seed: u64, leaderboard: Leaderboard) -> Race {
+        Race {
+            goal,
+            seed,
+            finish: None,
+            rank: None,
+            unranked: false,
+            leaderboard,
+        }
+    }
+
+    // Each seed is a different race on the usual board: it scatters a few
+    // patches of moss, as the daily challenge does. Seed 0 is the bare board.
+    pub fn scatter_moss(&self, board: &mut Board) {
+        if self.seed == 0 {
+            return;
+        }
+        for gift_point in daily::random_moss(&mut Prng::new(self.seed), board.shape) {
+            board.forbidden.insert(gift_point, true);
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.finish = None;
+        self.rank = None;
+        self.unranked = false;
+    }
+
+    // Call once per frame; records the finish the first time the goal is met.
+    // `assisted` is whether the player is getting help right now.
+    pub fn update(&mut self, board: &Board, elapsed: Duration, assisted: bool) {
+        if self.finish.is_none() && assisted {
+            self.unranked = true;
+        }
+        if self.finish.is_none() && self.goal.reached(board) {
+            let finish = Finish {
+                elapsed,
+                turns: board.turn,
+            };
+            self.finish = Some(finish);
+            if !self.unranked {
+                self.rank = self.leaderboard.insert(self.goal, self.seed, finish);
+                self.leaderboard.save();
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(&self, ctx: &mut Context, font: Font, palette: &Palette, locale: &Locale, bindings: &Bindings, elapsed: Duration, turn: usize) -> GameResult<()> {
+        let screen = layout::screen_size(ctx);
+        let top = Vec2::new(screen.x / 2.0, 15.0);
+        let (elapsed, turn) = match self.finish {
+            Some(finish) => (finish.elapsed, finish.turns),
+            None         => (elapsed, turn),
+        };
+        text::draw_centered_message(
+            ctx,
+            font,
+            &locale.format("race.status", &[&format_duration(elapsed), &turn, &self.goal.describe(locale), &self.seed]),
+            top,
+            palette.text,
+        )?;
+
+        if let Some(finish) = self.finish {
+            let center = Vec2::new(screen.x / 2.0, screen.y / 2.0);
+            Mesh::new_rectangle(
+                ctx,
+                DrawMode::fill(),
+                Rect::new(center.x - 150.0, center.y - 100.0, 300.0, 200.0),
+                Color::new(0.0, 0.0, 0.0, 0.75)
+            )?.draw(ctx, DrawParam::default())?;
+
+            text::draw_centered_message(ctx, font, &locale.get("race.finished"), center + Vec2::new(0.0, -80.0), palette.accent)?;
+            text::draw_centered_message(
+                ctx,
+                font,
+                &locale.format("race.time", &[&format_duration(finish.elapsed), &locale.plural("turns", finish.turns)]),
+                center + Vec2::new(0.0, -55.0),
+                palette.text,
+            )?;
+
+            let mut y = -25.0;
+            for (i, best) in self.leaderboard.top(self.goal, self.seed).iter().enumerate() {
+                let color = if Some(i) == self.rank {
+                    palette.accent
+                } else {
+                    palette.text
+                };
+                text::draw_centered_message(
+                    ctx,
+                    font,
+                    &locale.format("race.rank", &[&(i + 1), &format_duration(best.elapsed), &locale.plural("turns", best.turns)]),
+                    center + Vec2::new(0.0, y),
+                    color,
+                )?;
+                y += 18.0;
+            }
+            if self.unranked {
+                text::draw_centered_message(ctx, font, &locale.get("race.unranked"), center + Vec2::new(0.0, 65.0), palette.warning)?;
+            }
+
+            text::draw_centered_message(ctx, font, &locale.format("race.help", &[&bindings.describe(locale, Command::Reset)]), center + Vec2::new(0.0, 85.0), palette.help)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn race(seed: u64) -> Race {
+        Race::new(Goal::LifeLevel(5), seed, Leaderboard::load(Path::new("/nonexistent/leaderboard.txt")))
+    }
+
+    fn moss(seed: u64) -> Vec<(i32, i32)> {
+        let mut board = Board::new();
+        board.reset();
+        let bare = board.forbidden.clone();
+        race(seed).scatter_moss(&mut board);
+        let mut moss: Vec<(i32, i32)> = board.forbidden.keys()
+            .filter(|gift_point| !bare.contains_key(gift_point))
+            .map(|gift_point| (gift_point.hex_point.q, gift_point.hex_point.r))
+            .collect();
+        moss.sort();
+        moss
+    }
+
+    #[test]
+    fn each_seed_is_a_different_race() {
+        assert!(moss(0).is_empty());
+        assert!(!moss(1).is_empty());
+        assert_eq!(moss(1), moss(1));
+        assert_ne!(moss(1), moss(2));
+    }
+
+    #[test]
+    fn the_leaderboard_ranks_per_goal_and_seed() {
+        let mut leaderboard = Leaderboard::load(Path::new("/nonexistent/leaderboard.txt"));
+        let finish = |seconds| Finish {elapsed: Duration::from_secs(seconds), turns: 10};
+        let goal = Goal::LifeLevel(5);
+        assert_eq!(leaderboard.insert(goal, 1, finish(30)), Some(0));
+        assert_eq!(leaderboard.insert(goal, 1, finish(20)), Some(0));
+        assert_eq!(leaderboard.insert(goal, 1, finish(40)), Some(2));
+        assert_eq!(leaderboard.insert(goal, 2, finish(50)), Some(0));
+        for seconds in 0..LEADERBOARD_SIZE as u64 {
+            leaderboard.insert(goal, 1, finish(seconds));
+        }
+        assert_eq!(leaderboard.insert(goal, 1, finish(60)), None);
+        assert_eq!(leaderboard.top(goal, 1).len(), LEADERBOARD_SIZE);
+        assert_eq!(leaderboard.top(goal, 2).len(), 1);
+    }
+}
@@ -0,0 +1,105 @@
+use ggez::graphics::Color;
+use glam::f32::Vec2;
+use png;
+
+
+// A picture drawn in memory, one pixel at a time, for the exports which must
+// work without a window or a GPU. Shapes are antialiased by covering each
+// pixel with a fraction of the colour.
+#[derive(Clone, Debug)]
+pub struct Canvas {
+    pub width: usize,
+    pub height: usize,
+    // RGB, row by row from the top
+    pixels: Vec<[u8; 3]>,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize, background: Color) -> Canvas {
+        let (r, g, b) = background.to_rgb();
+        Canvas {
+            width,
+            height,
+            pixels: vec!([r, g, b]; width * height),
+        }
+    }
+
+    // `coverage` from 0.0 for untouched to 1.0 for replaced
+    fn blend(&mut self, x: usize, y: usize, color: Color, coverage: f32) {
+        let alpha = (coverage * color.a).clamp(0.0, 1.0);
+        let pixel = &mut self.pixels[y * self.width + x];
+        for (channel, value) in pixel.iter_mut().zip([color.r, color.g, color.b].iter()) {
+            *channel = (*channel as f32 * (1.0 - alpha) + value * 255.0 * alpha).round() as u8;
+        }
+    }
+
+    // Covers every pixel whose centre is closer than `radius` to the shape,
+    // given the distance from a point to the shape.
+    fn fill_near(&mut self, min: Vec2, max: Vec2, radius: f32, color: Color, distance: impl Fn(Vec2) -> f32) {
+        let x0 = (min.x - radius - 1.0).floor().max(0.0) as usize;
+        let y0 = (min.y - radius - 1.0).floor().max(0.0) as usize;
+        let x1 = ((max.x + radius + 1.0).ceil().max(0.0) as usize).min(self.width);
+        let y1 = ((max.y + radius + 1.0).ceil().max(0.0) as usize).min(self.height);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let d = distance(Vec2::new(x as f32 + 0.5, y as f32 + 0.5));
+                let coverage = (radius - d + 0.5).clamp(0.0, 1.0);
+                if coverage > 0.0 {
+                    self.blend(x, y, color, coverage);
+                }
+            }
+        }
+    }
+
+    pub fn circle(&mut self, center: Vec2, radius: f32, color: Color) {
+        self.fill_near(center, center, radius, color, |point| point.distance(center));
+    }
+
+    // with round ends
+    pub fn line(&mut self, a: Vec2, b: Vec2, thickness: f32, color: Color) {
+        let ab = b - a;
+        let length_squared = ab.length_squared().max(f32::EPSILON);
+        self.fill_near(a.min(b), a.max(b), thickness / 2.0, color, |point| {
+            let t = ((point - a).dot(ab) / length_squared).clamp(0.0, 1.0);
+            point.distance(a + ab * t)
+        });
+    }
+
+    pub fn rectangle(&mut self, min: Vec2, max: Vec2, color: Color) {
+        // the pixels whose centres are on the edge are fully covered
+        self.fill_near(min, max, 0.5, color, |point| {
+            let outside = (min - point).max(point - max).max(Vec2::ZERO);
+            outside.length()
+        });
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels.concat())?;
+        writer.finish()?;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_round_trip() {
+        let mut canvas = Canvas::new(3, 2, Color::new(1.0, 0.0, 0.0, 1.0));
+        canvas.rectangle(Vec2::new(2.0, 1.0), Vec2::new(3.0, 2.0), Color::new(0.0, 0.0, 1.0, 1.0));
+        let bytes = canvas.to_png().unwrap();
+
+        let mut reader = png::Decoder::new(&bytes[..]).read_info().unwrap();
+        let mut pixels = vec!(0; reader.output_buffer_size());
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height, info.color_type), (3, 2, png::ColorType::Rgb));
+        assert_eq!(&pixels[..3], &[255, 0, 0]);
+        assert_eq!(&pixels[15..18], &[0, 0, 255]);
+    }
+}
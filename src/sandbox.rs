@@ -0,0 +1,210 @@
+use ggez::{GameResult, Context};
+use ggez::graphics::*;
+use ggez::input::keyboard::KeyCode;
+use glam::f32::Vec2;
+
+use alert::AlertMessage;
+use bindings::{Bindings, Command};
+use board::{ActionError, Board};
+use cell::Gift;
+use layout;
+use locale::Locale;
+use palette::Palette;
+use hex;
+use sidebar::SIDEBAR_WIDTH;
+use text;
+
+
+pub const PALETTE_TOP:     f32 = 40.0;
+pub const PALETTE_SPACING: f32 = 20.0;
+pub const PALETTE_WIDTH:   f32 = 140.0;
+
+
+// What a left-click paints in the sandbox.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Brush {
+    Branch(usize), // thickness
+    Gift(Option<Gift>),
+    Moss,
+}
+
+// A creative mode in which the rules are suspended: the board can be painted
+// directly, for free, and the simulation only advances when asked to.
+#[derive(Debug)]
+pub struct Sandbox {
+    pub brush: Brush,
+    pub frozen: bool,
+    // how many turns to advance at once while frozen
+    pub pending_steps: usize,
+}
+
+impl Brush {
+    pub fn all() -> Vec<Brush> {
+        let mut brushes = vec!(
+            Brush::Branch(0),
+            Brush::Branch(1),
+            Brush::Branch(2),
+            Brush::Branch(3),
+            Brush::Gift(None),
+        );
+        brushes.extend(Gift::all().into_iter().map(|gift| Brush::Gift(Some(gift))));
+        brushes.push(Brush::Moss);
+        brushes
+    }
+
+    pub fn name(self, locale: &Locale) -> String {
+        match self {
+            Brush::Branch(0)         => locale.get("brush.thin_branch"),
+            Brush::Branch(upgrade)   => locale.format("brush.branch", &[&(upgrade + 1)]),
+            Brush::Gift(None)        => locale.get("brush.erase"),
+            Brush::Gift(Some(gift))  => locale.gift_name(gift),
+            Brush::Moss              => locale.get("brush.moss"),
+        }
+    }
+
+    // 1-4: branches, 0: erase, then one letter per gift, M: moss
+    pub fn from_keycode(keycode: KeyCode) -> Option<Brush> {
+        match keycode {
+            KeyCode::Key1 => Some(Brush::Branch(0)),
+            KeyCode::Key2 => Some(Brush::Branch(1)),
+            KeyCode::Key3 => Some(Brush::Branch(2)),
+            KeyCode::Key4 => Some(Brush::Branch(3)),
+            KeyCode::Key0 => Some(Brush::Gift(None)),
+            KeyCode::Q    => Some(Brush::Gift(Some(Gift::Leaves))),
+            KeyCode::W    => Some(Brush::Gift(Some(Gift::Flowers))),
+            KeyCode::E    => Some(Brush::Gift(Some(Gift::Berries))),
+            KeyCode::T    => Some(Brush::Gift(Some(Gift::Nuts))),
+            KeyCode::Y    => Some(Brush::Gift(Some(Gift::Beehive))),
+            KeyCode::U    => Some(Brush::Gift(Some(Gift::Birdnest))),
+            KeyCode::I    => Some(Brush::Gift(Some(Gift::Squirrel))),
+            KeyCode::M    => Some(Brush::Moss),
+            _             => None,
+        }
+    }
+
+    fn key_name(self) -> &'static str {
+        match self {
+            Brush::Branch(0)                  => "1",
+            Brush::Branch(1)                  => "2",
+            Brush::Branch(2)                  => "3",
+            Brush::Branch(_)                  => "4",
+            Brush::Gift(None)                 => "0",
+            Brush::Gift(Some(Gift::Leaves))   => "Q",
+            Brush::Gift(Some(Gift::Flowers))  => "W",
+            Brush::Gift(Some(Gift::Berries))  => "E",
+            Brush::Gift(Some(Gift::Nuts))     => "T",
+            Brush::Gift(Some(Gift::Beehive))  => "Y",
+            Brush::Gift(Some(Gift::Birdnest)) => "U",
+            Brush::Gift(Some(Gift::Squirrel)) => "I",
+            Brush::Moss                       => "M",
+        }
+    }
+}
+
+impl Sandbox {
+    pub fn new() -> Sandbox {
+        Sandbox {
+            brush: Brush::Branch(0),
+            frozen: true,
+            pending_steps: 0,
+        }
+    }
+
+    // Runs the turns requested with N since the last frame.
+    pub fn run_pending_steps(&mut self, board: &mut Board) {
+        for _ in 0..self.pending_steps {
+            board.update_stats();
+            board.next_turn();
+        }
+        self.pending_steps = 0;
+    }
+
+    pub fn paint(&self, board: &mut Board, in_bounds_point: hex::InBoundsPoint) -> Result<(), AlertMessage> {
+        match (self.brush, in_bounds_point) {
+            (Brush::Branch(upgrade), hex::InBoundsPoint::BranchPoint(branch_point)) => {
+                board.insert_branch(branch_point, upgrade).map_err(AlertMessage::Rejected)
+            },
+            (Brush::Gift(gift), hex::InBoundsPoint::GiftPoint(gift_point)) => {
+                if !board.gifts.contains_key(&gift_point) || !board.gift_children(gift_point).is_empty() {
+                    return Err(AlertMessage::Rejected(ActionError::NotATip));
+                }
+                // painting over moss scrapes it off
+                board.forbidden.remove(&gift_point);
+                board.set_gift(gift_point, gift);
+                Ok(())
+            },
+            (Brush::Moss, hex::InBoundsPoint::GiftPoint(gift_point)) => {
+                if !board.gifts.contains_key(&gift_point) || !board.gift_children(gift_point).is_empty() {
+                    return Err(AlertMessage::Rejected(ActionError::NotATip));
+                }
+                board.remove_gift(gift_point);
+                Ok(())
+            },
+            (_, hex::InBoundsPoint::BranchPoint(_)) => Err(AlertMessage::ClickForMoss),
+            (_, hex::InBoundsPoint::GiftPoint(_))   => Err(AlertMessage::ClickForBranch),
+        }
+    }
+
+    // Right-click: remove whatever is under the cursor.
+    pub fn erase(&self, board: &mut Board, in_bounds_point: hex::InBoundsPoint) {
+        match in_bounds_point {
+            hex::InBoundsPoint::BranchPoint(branch_point) => {
+                if branch_point != board.root_point {
+                    board.prune_branch(branch_point);
+                }
+            },
+            hex::InBoundsPoint::GiftPoint(gift_point) => {
+                board.set_gift(gift_point, None);
+                if board.is_moss(gift_point) {
+                    board.forbidden.remove(&gift_point);
+                }
+            },
+        }
+    }
+
+    pub fn draw(&self, ctx: &mut Context, font: Font, palette: &Palette, locale: &Locale, bindings: &Bindings) -> GameResult<()> {
+        let screen = layout::screen_size(ctx);
+        let brushes = Brush::all();
+        Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(SIDEBAR_WIDTH, PALETTE_TOP - PALETTE_SPACING, PALETTE_WIDTH, (brushes.len() + 1) as f32 * PALETTE_SPACING),
+            Color::new(0.0, 0.0, 0.0, 0.6)
+        )?.draw(ctx, DrawParam::default())?;
+        for (i, &brush) in brushes.iter().enumerate() {
+            let color = if brush == self.brush {
+                palette.accent
+            } else {
+                palette.text
+            };
+            text::draw_centered_message(
+                ctx,
+                font,
+                &format!("{}  {}", brush.key_name(), brush.name(locale)),
+                Vec2::new(SIDEBAR_WIDTH + PALETTE_WIDTH / 2.0, PALETTE_TOP + i as f32 * PALETTE_SPACING),
+                color,
+            )?;
+        }
+
+        let key = |command| bindings.describe(locale, command);
+        let status = if self.frozen {
+            locale.format("sandbox.frozen", &[&key(Command::SandboxFreeze), &key(Command::SandboxStep), &key(Command::ExportLevel), &key(Command::Sandbox)])
+        } else {
+            locale.format("sandbox.running", &[&key(Command::SandboxFreeze), &key(Command::ExportLevel), &key(Command::Sandbox)])
+        };
+        text::draw_centered_message(ctx, font, &status, Vec2::new(screen.x / 2.0, 15.0), palette.text)
+    }
+}
+
+pub fn palette_brush_at(point: Vec2) -> Option<Brush> {
+    if point.x < SIDEBAR_WIDTH || point.x > SIDEBAR_WIDTH + PALETTE_WIDTH {
+        return None;
+    }
+    let brushes = Brush::all();
+    let row = ((point.y - PALETTE_TOP) / PALETTE_SPACING + 0.5).floor();
+    if row >= 0.0 && (row as usize) < brushes.len() {
+        Some(brushes[row as usize])
+    } else {
+        None
+    }
+}
@@ -0,0 +1,163 @@
+use std::fmt;
+use std::fs;
+use std::io;
+
+use board::{Action, Board};
+use hex;
+
+
+// A replayable list of actions, each tagged with the turn during which it
+// was played. The file format is one action per line:
+//
+//     # comment
+//     0 place -1 0
+//     4 upgrade -1 0
+//     7 moss -2 -2
+//     9 prune 1 -1
+//
+// where the two numbers after the action's name are the q and r coordinates
+// of the branch or gift cell it applies to.
+#[derive(Clone, Debug, Default)]
+pub struct Script {
+    pub steps: Vec<Step>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Step {
+    pub turn: usize,
+    pub action: Action,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.action {
+            Action::Place(p)   => write!(f, "{} place {} {}",   self.turn, p.hex_point.q, p.hex_point.r),
+            Action::Upgrade(p) => write!(f, "{} upgrade {} {}", self.turn, p.hex_point.q, p.hex_point.r),
+            Action::Prune(p)   => write!(f, "{} prune {} {}",   self.turn, p.hex_point.q, p.hex_point.r),
+            Action::Moss(p)    => write!(f, "{} moss {} {}",    self.turn, p.hex_point.q, p.hex_point.r),
+            Action::Wait       => write!(f, "{} wait",          self.turn),
+        }
+    }
+}
+
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for step in self.steps.iter() {
+            writeln!(f, "{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn parse_action(words: &[&str]) -> Option<Action> {
+    if words == ["wait"] {
+        return Some(Action::Wait);
+    }
+    if words.len() != 3 {
+        return None;
+    }
+    let hex_point = hex::HexPoint::new(words[1].parse().ok()?, words[2].parse().ok()?);
+    match words[0] {
+        "place"   => Some(Action::Place(hex::BranchPoint::new(hex_point))),
+        "upgrade" => Some(Action::Upgrade(hex::BranchPoint::new(hex_point))),
+        "prune"   => Some(Action::Prune(hex::BranchPoint::new(hex_point))),
+        "moss"    => Some(Action::Moss(hex::GiftPoint::new(hex_point))),
+        _         => None,
+    }
+}
+
+impl Script {
+    pub fn new() -> Script {
+        Script::default()
+    }
+
+    pub fn push(&mut self, turn: usize, action: Action) {
+        self.steps.push(Step {turn, action});
+    }
+
+    pub fn last_turn(&self) -> usize {
+        self.steps.iter().map(|step| step.turn).max().unwrap_or(0)
+    }
+
+    pub fn parse(contents: &str) -> Result<Script, String> {
+        let mut script = Script::new();
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let turn = words[0].parse().map_err(|_| format!("line {}: expected a turn number", i + 1))?;
+            let action = parse_action(&words[1..]).ok_or(format!("line {}: unrecognized action {:?}", i + 1, line))?;
+            script.push(turn, action);
+        }
+        Ok(script)
+    }
+
+    pub fn load(path: &str) -> io::Result<Script> {
+        let contents = fs::read_to_string(path)?;
+        Script::parse(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    // Plays the actions scheduled for the board's current turn, then advances
+    // it to the next turn. Returns the actions which the rules rejected.
+    pub fn play_turn(&self, board: &mut Board) -> Vec<Step> {
+        let mut rejected = Vec::new();
+        let turn = board.turn;
+        for step in self.steps.iter().filter(|step| step.turn == turn) {
+            if board.apply(step.action).is_err() {
+                rejected.push(*step);
+            }
+        }
+        board.update_stats();
+        board.next_turn();
+        board.update_stats();
+        rejected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strategy;
+
+    const PLAN: &str = "# comment\n0 place -1 0\n4 upgrade -1 0\n7 moss -2 -2\n9 prune 1 -1\n12 wait\n";
+
+    #[test]
+    fn parse_and_display_round_trip() {
+        let script = Script::parse(PLAN).unwrap();
+        assert_eq!(script.steps.len(), 5);
+        assert_eq!(script.steps[1], Step {turn: 4, action: Action::Upgrade(hex::BranchPoint::new(hex::HexPoint::new(-1, 0)))});
+        assert_eq!(script.last_turn(), 12);
+        let printed = script.to_string();
+        assert_eq!(Script::parse(&printed).unwrap().steps, script.steps);
+        assert_eq!(printed, PLAN.trim_start_matches("# comment\n"));
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        assert_eq!(Script::parse("0 place -1 0\nsoon place -1 0").unwrap_err(), "line 2: expected a turn number");
+        assert!(Script::parse("3 grow -1 0").unwrap_err().starts_with("line 1: unrecognized action"));
+        assert!(Script::parse("3 place -1").is_err());
+    }
+
+    #[test]
+    fn play_turn_applies_the_turns_actions_and_reports_the_rejected_ones() {
+        let mut board = Board::new();
+        let action = strategy::valid_actions(&board)[0];
+        let mut script = Script::new();
+        script.push(0, action);
+        script.push(0, action);
+        script.push(1, Action::Wait);
+
+        let rejected = script.play_turn(&mut board);
+        assert_eq!(rejected, vec!(Step {turn: 0, action}));
+        assert_eq!(board.turn, 1);
+        assert!(strategy::valid_actions(&board).iter().all(|&other| other != action));
+        assert!(script.play_turn(&mut board).is_empty());
+    }
+}
@@ -0,0 +1,429 @@
+use ggez::{GameResult, Context};
+use ggez::graphics::*;
+use glam::f32::Vec2;
+
+use alert::{self, AlertMessage};
+use bindings::{Bindings, Command};
+use board::{Action, ActionError, Board, Cost};
+use hex;
+use layout::{self, Layout};
+use locale::Locale;
+use palette::Palette;
+use sidebar;
+use text;
+
+
+const MARK_RADIUS: f32 = 5.0;
+
+
+// A limb copied from the tree: where each branch is relative to the cell the
+// limb grows from, and how thick it is, parents first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Limb {
+    pub branches: Vec<(hex::HexVector, usize)>,
+}
+
+// What can be done to a whole limb at once, once the player has seen what it
+// would cost.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operation {
+    // to this thickness, 0 to 3
+    Upgrade(usize),
+    Prune,
+    // moss on every tip, or on none if they all have some already
+    Moss,
+    // the clipboard, growing from this cell
+    Paste(hex::GiftPoint),
+}
+
+// What an operation would do to the current board.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plan {
+    pub actions: Vec<Action>,
+    pub cost: Cost,
+    // the first action which the rules rejected; the others still happen
+    pub rejected: Option<ActionError>,
+}
+
+// What a key asks of the game in selection mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Response {
+    Nothing,
+    Confirm,
+    Copied,
+    NothingToCopy,
+    Leave,
+}
+
+// A mode in which a click picks a branch along with everything growing from
+// it, to upgrade, prune, moss or copy the whole limb at once.
+#[derive(Clone, Debug)]
+pub struct Selection {
+    // the branch at the base of the limb
+    pub root: Option<hex::BranchPoint>,
+    pub clipboard: Option<Limb>,
+    // whether the next click picks where to paste the clipboard
+    pub pasting: bool,
+    // sixths of a turn, applied after the mirroring
+    pub rotation: i32,
+    pub mirrored: bool,
+    // waiting for the player to confirm it
+    pub pending: Option<Operation>,
+    // the cell under the mouse, where the clipboard would be pasted
+    pub hover: Option<hex::GiftPoint>,
+}
+
+// Tries actions on a copy of the board which can afford anything, so that the
+// total cost can be shown even when it is too high.
+struct Simulation {
+    board: Board,
+    plan: Plan,
+}
+
+impl Limb {
+    pub fn copy(board: &Board, root: hex::BranchPoint) -> Option<Limb> {
+        let base = board.branches.get(&root)?.parent?;
+        let (branch_points, _) = board.subtree(root);
+        Some(Limb {
+            branches: branch_points.into_iter()
+                .map(|branch_point| (branch_point.hex_point - base.hex_point, board.branches[&branch_point].branch_upgrade))
+                .collect(),
+        })
+    }
+
+    // The branches, if the limb grew from `base` instead.
+    pub fn placed(&self, base: hex::GiftPoint, rotation: i32, mirrored: bool) -> Vec<(hex::BranchPoint, usize)> {
+        self.branches.iter()
+            .map(|&(offset, branch_upgrade)| {
+                let mut offset = if mirrored {offset.mirror()} else {offset};
+                for _ in 0..rotation {
+                    offset = offset.rotate();
+                }
+                (hex::BranchPoint::new(base.hex_point + offset), branch_upgrade)
+            })
+            .collect()
+    }
+}
+
+impl Simulation {
+    fn new(board: &Board) -> Simulation {
+        let mut board = board.clone();
+        board.bounty_amount = f32::INFINITY;
+        Simulation {
+            board,
+            plan: Plan {
+                actions: Vec::new(),
+                cost: 0.0,
+                rejected: None,
+            },
+        }
+    }
+
+    // Whether the rules accepted it.
+    fn try_action(&mut self, action: Action) -> bool {
+        match self.board.apply(action) {
+            Ok(cost) => {
+                self.plan.actions.push(action);
+                self.plan.cost += cost;
+                true
+            },
+            Err(action_error) => {
+                self.plan.rejected = self.plan.rejected.or(Some(action_error));
+                false
+            },
+        }
+    }
+
+    fn upgrade_to(&mut self, branch_point: hex::BranchPoint, branch_upgrade: usize) {
+        while self.board.branches.get(&branch_point).is_some_and(|branch_cell| branch_cell.branch_upgrade < branch_upgrade) {
+            if !self.try_action(Action::Upgrade(branch_point)) {
+                break;
+            }
+        }
+    }
+}
+
+impl Selection {
+    pub fn new() -> Selection {
+        Selection {
+            root: None,
+            clipboard: None,
+            pasting: false,
+            rotation: 0,
+            mirrored: false,
+            pending: None,
+            hover: None,
+        }
+    }
+
+    // Forgets a limb which is no longer there, e.g. after it was pruned.
+    pub fn update(&mut self, board: &Board, hover: Option<hex::InBoundsPoint>) {
+        if self.root.is_some_and(|root| !board.branches.contains_key(&root)) {
+            self.deselect();
+        }
+        self.hover = match hover {
+            Some(hex::InBoundsPoint::GiftPoint(gift_point)) => Some(gift_point),
+            _                                               => None,
+        };
+    }
+
+    pub fn select(&mut self, root: hex::BranchPoint) {
+        self.root = Some(root);
+        self.pasting = false;
+        self.pending = None;
+    }
+
+    pub fn deselect(&mut self) {
+        self.root = None;
+        self.pasting = false;
+        self.pending = None;
+    }
+
+    // Runs one of the commands for which `Command::in_selection` holds.
+    pub fn run(&mut self, board: &Board, command: Command) -> Response {
+        let operation = match command {
+            Command::SelectionUpgrade0 => Some(Operation::Upgrade(0)),
+            Command::SelectionUpgrade1 => Some(Operation::Upgrade(1)),
+            Command::SelectionUpgrade2 => Some(Operation::Upgrade(2)),
+            Command::SelectionUpgrade3 => Some(Operation::Upgrade(3)),
+            Command::SelectionPrune    => Some(Operation::Prune),
+            Command::SelectionMoss     => Some(Operation::Moss),
+            _                          => None,
+        };
+        if let Some(operation) = operation {
+            if self.root.is_some() {
+                self.pasting = false;
+                self.pending = Some(operation);
+            }
+            return Response::Nothing;
+        }
+        match command {
+            Command::SelectionCopy    => {
+                self.clipboard = self.root.and_then(|root| Limb::copy(board, root));
+                if self.clipboard.is_some() {Response::Copied} else {Response::NothingToCopy}
+            },
+            Command::SelectionPaste   => {
+                self.pasting = self.clipboard.is_some();
+                self.pending = None;
+                Response::Nothing
+            },
+            Command::SelectionRotate  => {
+                self.rotation = (self.rotation + 1) % 6;
+                Response::Nothing
+            },
+            Command::SelectionMirror  => {
+                self.mirrored = !self.mirrored;
+                Response::Nothing
+            },
+            Command::SelectionConfirm => if self.pending.is_some() {Response::Confirm} else {Response::Nothing},
+            Command::SelectionCancel  => {
+                if self.pending.is_some() || self.pasting {
+                    self.pending = None;
+                    self.pasting = false;
+                    Response::Nothing
+                } else {
+                    Response::Leave
+                }
+            },
+            _                         => Response::Nothing,
+        }
+    }
+
+    // What an operation would do to the board as it is now, which changes
+    // from turn to turn, so it is planned again until confirmed.
+    pub fn plan(&self, board: &Board, operation: Operation) -> Plan {
+        let mut simulation = Simulation::new(board);
+        match (operation, self.root) {
+            (Operation::Upgrade(branch_upgrade), Some(root)) => {
+                for branch_point in board.subtree(root).0 {
+                    simulation.upgrade_to(branch_point, branch_upgrade);
+                }
+            },
+            (Operation::Prune, Some(root)) => {
+                simulation.try_action(Action::Prune(root));
+            },
+            (Operation::Moss, Some(root)) => {
+                let tips: Vec<hex::GiftPoint> = board.subtree(root).1.into_iter()
+                    .filter(|&gift_point| board.gift_children(gift_point).is_empty())
+                    .collect();
+                let moss = !tips.iter().all(|&gift_point| board.is_moss(gift_point));
+                for gift_point in tips {
+                    if board.is_moss(gift_point) != moss {
+                        simulation.try_action(Action::Moss(gift_point));
+                    }
+                }
+            },
+            (Operation::Paste(base), _) => {
+                if let Some(ref limb) = self.clipboard {
+                    for (branch_point, branch_upgrade) in limb.placed(base, self.rotation, self.mirrored) {
+                        if simulation.try_action(Action::Place(branch_point)) {
+                            simulation.upgrade_to(branch_point, branch_upgrade);
+                        }
+                    }
+                }
+            },
+            _ => (),
+        }
+        simulation.plan
+    }
+
+    fn describe(&self, locale: &Locale, board: &Board, operation: Operation, plan: &Plan) -> String {
+        let cost = sidebar::format_amount(plan.cost);
+        match operation {
+            Operation::Upgrade(branch_upgrade) => locale.format("selection.upgrade", &[&(branch_upgrade + 1), &plan.actions.len(), &cost]),
+            Operation::Prune                   => locale.format("selection.prune", &[&self.root.map_or(0, |root| board.subtree(root).0.len())]),
+            Operation::Moss                    => locale.format("selection.moss", &[&plan.actions.len()]),
+            Operation::Paste(_)                => {
+                let placed = plan.actions.iter().filter(|action| matches!(action, Action::Place(_))).count();
+                locale.format("selection.paste", &[&placed, &cost])
+            },
+        }
+    }
+
+    // The marks on the selected limb, and on where the copy would go.
+    pub fn draw(&self, ctx: &mut Context, palette: &Palette, layout: &Layout, board: &Board) -> GameResult<()> {
+        let mut builder = MeshBuilder::new();
+        let mut empty = true;
+        if let Some(root) = self.root {
+            let (branch_points, gift_points) = board.subtree(root);
+            for branch_point in branch_points {
+                builder.circle(DrawMode::fill(), layout.board_to_screen(branch_point.to_point()), MARK_RADIUS, 1.0, palette.accent)?;
+            }
+            for gift_point in gift_points {
+                builder.circle(DrawMode::stroke(2.0), layout.board_to_screen(gift_point.to_point()), MARK_RADIUS, 1.0, palette.accent)?;
+            }
+            empty = false;
+        }
+        // the limb as it would be pasted, bright where it fits
+        let paste_base = match (self.pending, self.hover) {
+            (Some(Operation::Paste(base)), _)  => Some(base),
+            (None, Some(base)) if self.pasting => Some(base),
+            _                                  => None,
+        };
+        if let (Some(base), Some(ref limb)) = (paste_base, &self.clipboard) {
+            let plan = self.plan(board, Operation::Paste(base));
+            let dimmed = Color::new(palette.error.r, palette.error.g, palette.error.b, 0.6);
+            for (branch_point, _) in limb.placed(base, self.rotation, self.mirrored) {
+                let color = if plan.actions.contains(&Action::Place(branch_point)) {palette.accent} else {dimmed};
+                builder.circle(DrawMode::stroke(2.0), layout.board_to_screen(branch_point.to_point()), MARK_RADIUS + 2.0, 1.0, color)?;
+            }
+            empty = false;
+        }
+        if !empty {
+            builder.build(ctx)?.draw(ctx, DrawParam::default())?;
+        }
+        Ok(())
+    }
+
+    // The keys at the top of the screen, and the pending operation underneath.
+    pub fn draw_status(&self, ctx: &mut Context, font: Font, palette: &Palette, locale: &Locale, bindings: &Bindings, board: &Board) -> GameResult<()> {
+        let screen = layout::screen_size(ctx);
+        let keys = |commands: &[Command]| -> Vec<String> {
+            commands.iter().map(|&command| bindings.describe(locale, command)).collect()
+        };
+        let help = keys(&[
+            Command::SelectionUpgrade0, Command::SelectionUpgrade3, Command::SelectionPrune, Command::SelectionMoss,
+            Command::SelectionCopy, Command::SelectionPaste, Command::Select,
+        ]);
+        text::draw_centered_message(ctx, font, &locale.format("selection.help", &[&help[0], &help[1], &help[2], &help[3], &help[4], &help[5], &help[6]]), Vec2::new(screen.x / 2.0, 15.0), palette.text)?;
+        let cancel = bindings.describe(locale, Command::SelectionCancel);
+        let status = Vec2::new(screen.x / 2.0, 40.0);
+        match self.pending {
+            Some(operation) => {
+                let plan = self.plan(board, operation);
+                let affordable = plan.cost <= board.bounty_amount;
+                text::draw_centered_message(
+                    ctx,
+                    font,
+                    &format!("{}   {}", self.describe(locale, board, operation, &plan), locale.format("selection.confirm", &[&bindings.describe(locale, Command::SelectionConfirm), &cancel])),
+                    status,
+                    if affordable {palette.accent} else {palette.warning},
+                )?;
+                let problem = if !affordable {
+                    Some(ActionError::NotEnoughBounty {need: plan.cost, have: board.bounty_amount})
+                } else {
+                    plan.rejected
+                };
+                if let Some(action_error) = problem {
+                    text::draw_centered_message(ctx, font, &alert::describe(locale, AlertMessage::Rejected(action_error)), status + Vec2::new(0.0, 20.0), palette.warning)?;
+                }
+            },
+            None if self.pasting => {
+                let pasting = keys(&[Command::SelectionRotate, Command::SelectionMirror]);
+                text::draw_centered_message(ctx, font, &locale.format("selection.pasting", &[&pasting[0], &pasting[1], &cancel]), status, palette.text)?
+            },
+            None if self.root.is_none() => text::draw_centered_message(ctx, font, &locale.get("selection.empty"), status, palette.help)?,
+            None => (),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use life;
+
+    fn branch(q: i32, r: i32) -> hex::BranchPoint {
+        hex::BranchPoint::new(hex::HexPoint::new(q, r))
+    }
+
+    fn gift(q: i32, r: i32) -> hex::GiftPoint {
+        hex::GiftPoint::new(hex::HexPoint::new(q, r))
+    }
+
+    // The trunk (0, -1) with a thin branch (0, -3) on top, selected.
+    fn selected() -> (Board, Selection) {
+        let mut board = Board::new();
+        board.bounty_amount = 1000.0;
+        board.insert_branch(branch(0, -1), 0).unwrap();
+        board.insert_branch(branch(0, -3), 0).unwrap();
+        let mut selection = Selection::new();
+        selection.select(branch(0, -1));
+        (board, selection)
+    }
+
+    #[test]
+    fn upgrades_parents_first_and_adds_up_the_cost() {
+        let (board, selection) = selected();
+        let plan = selection.plan(&board, Operation::Upgrade(2));
+        assert_eq!(plan.actions, vec!(
+            Action::Upgrade(branch(0, -1)),
+            Action::Upgrade(branch(0, -1)),
+            Action::Upgrade(branch(0, -3)),
+            Action::Upgrade(branch(0, -3)),
+        ));
+        assert_eq!(plan.cost, life::BASE * (25.0 + 125.0) * 2.0);
+        assert_eq!(plan.rejected, None);
+    }
+
+    #[test]
+    fn plans_what_the_player_cannot_afford_yet() {
+        let (mut board, selection) = selected();
+        board.bounty_amount = 0.0;
+        let plan = selection.plan(&board, Operation::Upgrade(1));
+        assert_eq!(plan.actions.len(), 2);
+        assert_eq!(plan.cost, life::BASE * 25.0 * 2.0);
+        assert_eq!(board.branches[&branch(0, -1)].branch_upgrade, 0);
+    }
+
+    #[test]
+    fn mosses_the_tips_then_clears_them() {
+        let (mut board, selection) = selected();
+        let plan = selection.plan(&board, Operation::Moss);
+        assert_eq!(plan.actions, vec!(Action::Moss(gift(0, -4))));
+        assert_eq!(plan.cost, 0.0);
+
+        board.apply(Action::Moss(gift(0, -4))).unwrap();
+        let plan = selection.plan(&board, Operation::Moss);
+        assert_eq!(plan.actions, vec!(Action::Moss(gift(0, -4))));
+    }
+
+    #[test]
+    fn nothing_to_do_without_a_limb() {
+        let (board, mut selection) = selected();
+        selection.deselect();
+        assert!(selection.plan(&board, Operation::Prune).actions.is_empty());
+        assert!(selection.plan(&board, Operation::Paste(gift(0, -4))).actions.is_empty());
+    }
+}
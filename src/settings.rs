@@ -0,0 +1,478 @@
+use ggez::{GameResult, Context};
+use ggez::event::MouseButton;
+use ggez::graphics::*;
+use ggez::input::keyboard::KeyCode;
+use glam::f32::Vec2;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use board::Economy;
+use layout;
+use locale::Locale;
+use palette::{Palette, Preset};
+use text;
+
+
+pub const SCREEN_TOP:     f32 = 100.0;
+pub const SCREEN_SPACING: f32 = 30.0;
+
+pub const VOLUME_STEP: u32 = 10;
+// how many times faster than the original two seconds per turn
+pub const TURN_SPEEDS: [f32; 6] = [0.5, 0.75, 1.0, 1.5, 2.0, 3.0];
+
+
+// The rows of the settings screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Setting {
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    Fullscreen,
+    TurnSpeed,
+    Language,
+    Palette,
+    Tutorial,
+    Economy,
+    Bindings,
+}
+
+// The player's options, saved to settings.txt in the user config directory
+// as one "name value" per line, e.g. "music_volume 80". The key bindings have
+// a row on the settings screen but are saved to their own file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    path: PathBuf,
+    // percentages
+    pub master_volume: u32,
+    pub music_volume: u32,
+    pub sfx_volume: u32,
+    pub fullscreen: bool,
+    pub turn_speed: f32,
+    // None follows the LANG environment variable
+    pub language: Option<String>,
+    pub palette: Preset,
+    // the challenges at the bottom of the screen
+    pub show_tutorial: bool,
+    // how the bounty is capped, see board::Economy
+    pub economy: Economy,
+}
+
+// Values given on the command line, which apply on top of the player's
+// settings for one session and are never saved, unless the player changes
+// them in the game.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Overrides {
+    pub language: Option<String>,
+    pub fullscreen: Option<bool>,
+    pub palette: Option<Preset>,
+    pub economy: Option<Economy>,
+}
+
+// What the settings screen asks of the game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Response {
+    Nothing,
+    // to be applied and saved
+    Changed,
+    OpenBindings,
+    Close,
+}
+
+// The settings screen: just the selected row, the values live in Settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SettingsScreen {
+    pub selected: usize,
+}
+
+impl Setting {
+    pub fn all() -> Vec<Setting> {
+        vec!(
+            Setting::MasterVolume,
+            Setting::MusicVolume,
+            Setting::SfxVolume,
+            Setting::Fullscreen,
+            Setting::TurnSpeed,
+            Setting::Language,
+            Setting::Palette,
+            Setting::Tutorial,
+            Setting::Economy,
+            Setting::Bindings,
+        )
+    }
+
+    // as in settings.txt and in the locale keys
+    pub fn name(self) -> &'static str {
+        match self {
+            Setting::MasterVolume => "master_volume",
+            Setting::MusicVolume  => "music_volume",
+            Setting::SfxVolume    => "sfx_volume",
+            Setting::Fullscreen   => "fullscreen",
+            Setting::TurnSpeed    => "turn_speed",
+            Setting::Language     => "language",
+            Setting::Palette      => "palette",
+            Setting::Tutorial     => "tutorial",
+            Setting::Economy      => "economy",
+            Setting::Bindings     => "bindings",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Setting> {
+        Setting::all().into_iter().find(|setting| setting.name() == name)
+    }
+}
+
+fn parse_volume(value: &str) -> Option<u32> {
+    value.parse().ok().filter(|&volume| volume <= 100)
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "on"  => Some(true),
+        "off" => Some(false),
+        _     => None,
+    }
+}
+
+fn bool_name(value: bool) -> &'static str {
+    if value {"on"} else {"off"}
+}
+
+impl Settings {
+    pub fn new(path: &Path) -> Settings {
+        Settings {
+            path: path.to_path_buf(),
+            master_volume: 100,
+            music_volume: 100,
+            sfx_volume: 100,
+            fullscreen: false,
+            turn_speed: 1.0,
+            language: None,
+            palette: Preset::Default,
+            show_tutorial: true,
+            economy: Economy::Fixed,
+        }
+    }
+
+    // A missing file gives the defaults, and so does each invalid line.
+    pub fn load(path: &Path) -> Settings {
+        let mut settings = Settings::new(path);
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(_)       => return settings,
+        };
+        for (i, line) in contents.lines().enumerate() {
+            let line = match line.find('#') {
+                Some(j) => &line[..j],
+                None    => line,
+            }.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let parsed = if words.len() == 2 {
+                Setting::from_name(words[0]).and_then(|setting| settings.set(setting, words[1]))
+            } else {
+                None
+            };
+            if parsed.is_none() {
+                println!("settings, line {}: ignoring {:?} and using the default instead", i + 1, line);
+            }
+        }
+        settings
+    }
+
+    // None if the value is invalid for that setting.
+    fn set(&mut self, setting: Setting, value: &str) -> Option<()> {
+        match setting {
+            Setting::MasterVolume => self.master_volume = parse_volume(value)?,
+            Setting::MusicVolume  => self.music_volume = parse_volume(value)?,
+            Setting::SfxVolume    => self.sfx_volume = parse_volume(value)?,
+            Setting::Fullscreen   => self.fullscreen = parse_bool(value)?,
+            Setting::TurnSpeed    => self.turn_speed = value.parse().ok().filter(|speed| TURN_SPEEDS.contains(speed))?,
+            Setting::Language     => self.language = Some(value.to_string()),
+            Setting::Palette      => self.palette = Preset::from_name(value)?,
+            Setting::Tutorial     => self.show_tutorial = parse_bool(value)?,
+            Setting::Economy      => self.economy = Economy::from_name(value)?,
+            Setting::Bindings     => return None,
+        }
+        Some(())
+    }
+
+    pub fn save(&self) {
+        let mut contents = String::from("# name value, see the settings screen (O)\n");
+        contents.push_str(&format!("master_volume {}\n", self.master_volume));
+        contents.push_str(&format!("music_volume {}\n", self.music_volume));
+        contents.push_str(&format!("sfx_volume {}\n", self.sfx_volume));
+        contents.push_str(&format!("fullscreen {}\n", bool_name(self.fullscreen)));
+        contents.push_str(&format!("turn_speed {}\n", self.turn_speed));
+        if let Some(ref language) = self.language {
+            contents.push_str(&format!("language {}\n", language));
+        }
+        contents.push_str(&format!("palette {}\n", self.palette.name()));
+        contents.push_str(&format!("tutorial {}\n", bool_name(self.show_tutorial)));
+        contents.push_str(&format!("economy {}\n", self.economy.name()));
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).unwrap_or(());
+        }
+        if let Err(e) = fs::write(&self.path, contents) {
+            println!("could not save the settings to {:?}: {}", self.path, e);
+        }
+    }
+
+    // 0.0 to 1.0, for the audio sources
+    pub fn music_gain(&self) -> f32 {
+        (self.master_volume * self.music_volume) as f32 / 10000.0
+    }
+
+    pub fn sfx_gain(&self) -> f32 {
+        (self.master_volume * self.sfx_volume) as f32 / 10000.0
+    }
+
+    // Moves one step up or down, wrapping around for the settings which
+    // aren't numbers. `languages` is the list to cycle through.
+    pub fn adjust(&mut self, setting: Setting, up: bool, current_language: &str, languages: &[String]) {
+        let step_volume = |volume: u32| if up {
+            (volume + VOLUME_STEP).min(100)
+        } else {
+            volume.saturating_sub(VOLUME_STEP)
+        };
+        match setting {
+            Setting::MasterVolume => self.master_volume = step_volume(self.master_volume),
+            Setting::MusicVolume  => self.music_volume = step_volume(self.music_volume),
+            Setting::SfxVolume    => self.sfx_volume = step_volume(self.sfx_volume),
+            Setting::Fullscreen   => self.fullscreen = !self.fullscreen,
+            Setting::TurnSpeed    => {
+                let i = TURN_SPEEDS.iter().position(|&speed| speed == self.turn_speed).unwrap_or(2);
+                let i = if up {
+                    (i + 1).min(TURN_SPEEDS.len() - 1)
+                } else {
+                    i.saturating_sub(1)
+                };
+                self.turn_speed = TURN_SPEEDS[i];
+            },
+            Setting::Language     => {
+                if languages.is_empty() {
+                    return;
+                }
+                let i = languages.iter().position(|language| language == current_language).unwrap_or(0);
+                let i = if up {
+                    (i + 1) % languages.len()
+                } else {
+                    (i + languages.len() - 1) % languages.len()
+                };
+                self.language = Some(languages[i].clone());
+            },
+            Setting::Palette      => {
+                let presets = Preset::all();
+                let i = presets.iter().position(|&preset| preset == self.palette).unwrap_or(0);
+                self.palette = if up {
+                    presets[(i + 1) % presets.len()]
+                } else {
+                    presets[(i + presets.len() - 1) % presets.len()]
+                };
+            },
+            Setting::Economy      => {
+                let economies = Economy::all();
+                let i = economies.iter().position(|&economy| economy == self.economy).unwrap_or(0);
+                self.economy = if up {
+                    economies[(i + 1) % economies.len()]
+                } else {
+                    economies[(i + economies.len() - 1) % economies.len()]
+                };
+            },
+            Setting::Tutorial     => self.show_tutorial = !self.show_tutorial,
+            Setting::Bindings     => (),
+        }
+    }
+
+    fn describe(&self, locale: &Locale, setting: Setting) -> String {
+        let on_off = |value: bool| locale.get(if value {"settings.on"} else {"settings.off"});
+        match setting {
+            Setting::MasterVolume => locale.format("settings.percent", &[&self.master_volume]),
+            Setting::MusicVolume  => locale.format("settings.percent", &[&self.music_volume]),
+            Setting::SfxVolume    => locale.format("settings.percent", &[&self.sfx_volume]),
+            Setting::Fullscreen   => on_off(self.fullscreen),
+            Setting::TurnSpeed    => locale.format("settings.speed", &[&self.turn_speed]),
+            // the locale follows this setting
+            Setting::Language     => locale.get("language.name"),
+            Setting::Palette      => locale.get(&format!("palette.{}", self.palette.name())),
+            Setting::Tutorial     => on_off(self.show_tutorial),
+            Setting::Economy      => locale.get(&format!("economy.{}", self.economy.name())),
+            Setting::Bindings     => String::new(),
+        }
+    }
+}
+
+impl Overrides {
+    // The settings in effect.
+    pub fn apply(&self, settings: &Settings) -> Settings {
+        let mut settings = settings.clone();
+        if let Some(ref language) = self.language {
+            settings.language = Some(language.clone());
+        }
+        if let Some(fullscreen) = self.fullscreen {
+            settings.fullscreen = fullscreen;
+        }
+        if let Some(palette) = self.palette {
+            settings.palette = palette;
+        }
+        if let Some(economy) = self.economy {
+            settings.economy = economy;
+        }
+        settings
+    }
+
+    // Hands a setting back to the player, who is about to change it, starting
+    // from the value they can see.
+    pub fn take(&mut self, setting: Setting, settings: &mut Settings) {
+        match setting {
+            Setting::Language   => if let Some(language) = self.language.take() {
+                settings.language = Some(language);
+            },
+            Setting::Fullscreen => if let Some(fullscreen) = self.fullscreen.take() {
+                settings.fullscreen = fullscreen;
+            },
+            Setting::Palette    => if let Some(palette) = self.palette.take() {
+                settings.palette = palette;
+            },
+            Setting::Economy    => if let Some(economy) = self.economy.take() {
+                settings.economy = economy;
+            },
+            _                   => (),
+        }
+    }
+}
+
+impl SettingsScreen {
+    pub fn new() -> SettingsScreen {
+        SettingsScreen {
+            selected: 0,
+        }
+    }
+
+    pub fn key_down(&mut self, settings: &mut Settings, overrides: &mut Overrides, keycode: KeyCode, current_language: &str, languages: &[String]) -> Response {
+        let rows = Setting::all();
+        let setting = rows[self.selected];
+        match keycode {
+            KeyCode::Up     => self.selected = (self.selected + rows.len() - 1) % rows.len(),
+            KeyCode::Down   => self.selected = (self.selected + 1) % rows.len(),
+            KeyCode::Left   => return self.adjust(settings, overrides, setting, false, current_language, languages),
+            KeyCode::Right
+            | KeyCode::Return => return self.adjust(settings, overrides, setting, true, current_language, languages),
+            KeyCode::Escape => return Response::Close,
+            _               => (),
+        }
+        Response::Nothing
+    }
+
+    // Left-click steps up and right-click steps down.
+    pub fn mouse_down(&mut self, settings: &mut Settings, overrides: &mut Overrides, button: MouseButton, y: f32, current_language: &str, languages: &[String]) -> Response {
+        let rows = Setting::all();
+        let row = ((y - SCREEN_TOP) / SCREEN_SPACING + 0.5).floor();
+        if row < 0.0 || row as usize >= rows.len() {
+            return Response::Nothing;
+        }
+        self.selected = row as usize;
+        self.adjust(settings, overrides, rows[self.selected], button != MouseButton::Right, current_language, languages)
+    }
+
+    fn adjust(&self, settings: &mut Settings, overrides: &mut Overrides, setting: Setting, up: bool, current_language: &str, languages: &[String]) -> Response {
+        if setting == Setting::Bindings {
+            return Response::OpenBindings;
+        }
+        overrides.take(setting, settings);
+        settings.adjust(setting, up, current_language, languages);
+        Response::Changed
+    }
+
+    // `settings` are the ones in effect, overrides included.
+    pub fn draw(&self, ctx: &mut Context, font: Font, palette: &Palette, locale: &Locale, settings: &Settings) -> GameResult<()> {
+        let screen = layout::screen_size(ctx);
+        Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(0.0, 0.0, screen.x, screen.y),
+            Color::new(0.0, 0.0, 0.0, 0.8)
+        )?.draw(ctx, DrawParam::default())?;
+        text::draw_centered_message(ctx, font, &locale.get("settings.title"), Vec2::new(screen.x / 2.0, 50.0), palette.accent)?;
+
+        for (i, &setting) in Setting::all().iter().enumerate() {
+            let y = SCREEN_TOP + i as f32 * SCREEN_SPACING;
+            let color = if i == self.selected {
+                palette.accent
+            } else {
+                palette.text
+            };
+            text::draw_centered_message(ctx, font, &locale.get(&format!("settings.{}", setting.name())), Vec2::new(screen.x / 2.0 - 120.0, y), color)?;
+            text::draw_centered_message(ctx, font, &settings.describe(locale, setting), Vec2::new(screen.x / 2.0 + 120.0, y), color)?;
+        }
+
+        text::draw_centered_message(
+            ctx,
+            font,
+            &locale.get("settings.help"),
+            Vec2::new(screen.x / 2.0, screen.y - 20.0),
+            palette.help,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn overrides_win_for_the_session_only() {
+        let path = env::temp_dir().join("ludum-dare-44-test-settings.txt");
+        let mut settings = Settings::new(&path);
+        settings.language = Some("fr".to_string());
+        settings.palette = Preset::HighContrast;
+        let overrides = Overrides {
+            language: Some("en".to_string()),
+            fullscreen: Some(true),
+            palette: None,
+            economy: Some(Economy::Life),
+        };
+
+        let in_effect = overrides.apply(&settings);
+        assert_eq!(in_effect.language, Some("en".to_string()));
+        assert!(in_effect.fullscreen);
+        assert_eq!(in_effect.palette, Preset::HighContrast);
+        assert_eq!(in_effect.economy, Economy::Life);
+
+        settings.save();
+        assert_eq!(Settings::load(&path), settings);
+        fs::remove_file(&path).unwrap_or(());
+    }
+
+    #[test]
+    fn changing_an_overridden_setting_starts_from_the_override() {
+        let mut settings = Settings::new(Path::new("unused.txt"));
+        let mut overrides = Overrides {
+            economy: Some(Economy::Life),
+            fullscreen: Some(true),
+            ..Overrides::default()
+        };
+        let response = SettingsScreen::new().adjust(&mut settings, &mut overrides, Setting::Economy, true, "en", &[]);
+        assert_eq!(response, Response::Changed);
+        assert_eq!(settings.economy, Economy::Size);
+        assert_eq!(overrides.economy, None);
+        // the other overrides still apply
+        assert_eq!(overrides.fullscreen, Some(true));
+        assert!(!settings.fullscreen);
+    }
+
+    #[test]
+    fn invalid_lines_keep_their_default() {
+        let path = env::temp_dir().join("ludum-dare-44-test-invalid-settings.txt");
+        fs::write(&path, "music_volume 80\nsfx_volume 150\nturn_speed 7\npalette sepia  # comment\nfullscreen on\nmystery 1\n").unwrap();
+        let settings = Settings::load(&path);
+        fs::remove_file(&path).unwrap_or(());
+        assert_eq!(settings.music_volume, 80);
+        assert_eq!(settings.sfx_volume, 100);
+        assert_eq!(settings.turn_speed, 1.0);
+        assert_eq!(settings.palette, Preset::Default);
+        assert!(settings.fullscreen);
+    }
+}
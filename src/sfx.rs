@@ -0,0 +1,279 @@
+use ggez::Context;
+use rand::Rng;
+use std::collections::HashMap;
+use std::io::Read;
+
+use board::{Action, Board};
+use cell::Gift;
+use hex::{GiftPoint, ORIGIN_X};
+use sidebar::SIDEBAR_WIDTH;
+use sound::{Audio, Backend, Sound};
+
+
+// across every effect, so that e.g. a whole subtree of creatures leaving at
+// once doesn't turn into a wall of noise
+pub const MAX_VOICES: usize = 8;
+
+
+// One sound effect, see resources/sounds.txt.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Effect {
+    pub name: String,
+    // one is picked at random each time
+    pub files: Vec<String>,
+    // each time, random between the two
+    pub pitch: (f32, f32),
+    pub volume: (f32, f32),
+    // how many copies may play at once
+    pub voices: usize,
+}
+
+struct Voices {
+    effect: Effect,
+    // `effect.voices` sources per file
+    sources: Vec<Vec<Box<dyn Sound>>>,
+}
+
+// The sound effects, played through a small pool of voices each, and panned
+// left or right following where they happen on the board.
+pub struct Sfx {
+    effects: HashMap<String, Voices>,
+    // the sound effects volume from the settings
+    gain: f32,
+    // where each gift was last time, to hear the creatures come and go
+    gifts: HashMap<GiftPoint, Gift>,
+}
+
+// Each "sound NAME" block lists its files, and optionally its pitch and
+// volume ranges and its number of voices.
+pub fn parse(contents: &str) -> Result<Vec<Effect>, String> {
+    let mut effects: Vec<Effect> = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = match line.find('#') {
+            Some(j) => &line[..j],
+            None    => line,
+        }.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (keyword, rest) = match line.find(' ') {
+            Some(j) => (&line[..j], line[j..].trim()),
+            None    => (line, ""),
+        };
+        let error = || format!("line {}: could not parse {:?}", i + 1, line);
+        if keyword == "sound" {
+            effects.push(Effect {
+                name: rest.to_string(),
+                files: Vec::new(),
+                pitch: (1.0, 1.0),
+                volume: (1.0, 1.0),
+                voices: 2,
+            });
+            continue;
+        }
+        let effect = effects.last_mut().ok_or_else(error)?;
+        let range = || -> Option<(f32, f32)> {
+            let numbers: Vec<f32> = rest.split_whitespace().map(|word| word.parse().ok()).collect::<Option<Vec<f32>>>()?;
+            match *numbers.as_slice() {
+                [x] if x > 0.0                          => Some((x, x)),
+                [low, high] if low > 0.0 && low <= high => Some((low, high)),
+                _                                       => None,
+            }
+        };
+        match keyword {
+            "file"   => effect.files.push(rest.to_string()),
+            "pitch"  => effect.pitch = range().ok_or_else(error)?,
+            "volume" => effect.volume = range().filter(|&(_, high)| high <= 1.0).ok_or_else(error)?,
+            "voices" => effect.voices = rest.parse().ok().filter(|&voices| voices > 0).ok_or_else(error)?,
+            _        => return Err(error()),
+        }
+    }
+    for effect in effects.iter() {
+        if effect.files.is_empty() {
+            return Err(format!("sound {:?} needs a file", effect.name));
+        }
+    }
+    Ok(effects)
+}
+
+// What the player hears when an action goes through.
+pub fn action_effect(action: Action) -> Option<&'static str> {
+    match action {
+        Action::Place(_)   => Some("branch_place"),
+        Action::Upgrade(_) => Some("branch_upgrade"),
+        Action::Prune(_)   => Some("branch_break"),
+        Action::Moss(_)    => Some("gift_release"),
+        Action::Wait       => None,
+    }
+}
+
+// e.g. "bird_nest_appears"
+pub fn gift_effect(gift: Gift, appears: bool) -> String {
+    let verb = if appears {"appears"} else {"leaves"};
+    format!("{}_{}", gift.id().replace(' ', "_"), verb)
+}
+
+// From -1.0 on the left sidebar to 1.0 on the right one, for a point in the
+// board's coordinates.
+pub fn pan(x: f32) -> f32 {
+    ((x - ORIGIN_X) / (ORIGIN_X - SIDEBAR_WIDTH)).clamp(-1.0, 1.0)
+}
+
+impl Sfx {
+    // from resources/sounds.txt; like the music, a broken file means fewer
+    // sounds rather than no game
+    pub fn load(ctx: &mut Context, backend: &Backend) -> Sfx {
+        let mut contents = String::new();
+        let effects = ggez::filesystem::open(ctx, "/sounds.txt")
+            .map_err(|e| e.to_string())
+            .and_then(|mut file| file.read_to_string(&mut contents).map_err(|e| e.to_string()))
+            .and_then(|_| parse(&contents))
+            .unwrap_or_else(|e| {
+                println!("could not load the sound effects: {}", e);
+                Vec::new()
+            });
+        Sfx::new(ctx, backend, effects)
+    }
+
+    pub fn new(audio: &mut dyn Audio, backend: &Backend, effects: Vec<Effect>) -> Sfx {
+        let mut voices = HashMap::new();
+        for effect in effects {
+            let mut sources = Vec::new();
+            for file in effect.files.iter() {
+                let loaded = (0..effect.voices)
+                    .map(|_| backend.load(audio, file, true))
+                    .collect::<Result<Vec<Box<dyn Sound>>, _>>();
+                match loaded {
+                    Ok(file_sources) => sources.push(file_sources),
+                    Err(e)           => println!("could not load the sound {:?}: {}", file, e),
+                }
+            }
+            if !sources.is_empty() {
+                voices.insert(effect.name.clone(), Voices {
+                    effect,
+                    sources,
+                });
+            }
+        }
+        Sfx {
+            effects: voices,
+            gain: 1.0,
+            gifts: HashMap::new(),
+        }
+    }
+
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain;
+    }
+
+    fn playing_voices(&self) -> usize {
+        self.effects.values()
+            .flat_map(|voices| voices.sources.iter().flatten())
+            .filter(|source| source.playing())
+            .count()
+    }
+
+    // Does nothing if the effect doesn't exist, or if too many voices are
+    // already playing; `x` is in the board's coordinates.
+    pub fn play(&mut self, audio: &dyn Audio, name: &str, x: f32) {
+        if self.gain <= 0.0 || self.playing_voices() >= MAX_VOICES {
+            return;
+        }
+        let gain = self.gain;
+        let voices = match self.effects.get_mut(name) {
+            Some(voices) => voices,
+            None         => return,
+        };
+        let mut rng = rand::thread_rng();
+        let file = rng.gen_range(0..voices.sources.len());
+        let effect = &voices.effect;
+        let source = match voices.sources[file].iter_mut().find(|source| !source.playing()) {
+            Some(source) => source,
+            None         => return,
+        };
+        let (low_pitch, high_pitch) = effect.pitch;
+        let (low_volume, high_volume) = effect.volume;
+        source.set_pitch(rng.gen_range(low_pitch..=high_pitch));
+        source.set_volume(audio, gain * rng.gen_range(low_volume..=high_volume));
+        source.set_pan(pan(x));
+        source.play(audio);
+    }
+
+    // Plays the sounds of the gifts which appeared or left since the last
+    // call; only the creatures have some in resources/sounds.txt.
+    pub fn watch_gifts(&mut self, audio: &dyn Audio, board: &Board) {
+        let gifts = gifts(board);
+        let mut events: Vec<(String, GiftPoint)> = Vec::new();
+        for (&gift_point, &gift) in gifts.iter() {
+            if self.gifts.get(&gift_point) != Some(&gift) {
+                events.push((gift_effect(gift, true), gift_point));
+            }
+        }
+        for (&gift_point, &gift) in self.gifts.iter() {
+            if gifts.get(&gift_point) != Some(&gift) {
+                events.push((gift_effect(gift, false), gift_point));
+            }
+        }
+        self.gifts = gifts;
+        for (name, gift_point) in events {
+            self.play(audio, &name, gift_point.to_point().x);
+        }
+    }
+
+    // e.g. after loading a level, so that its creatures don't all chirp at
+    // once
+    pub fn forget_gifts(&mut self, board: &Board) {
+        self.gifts = gifts(board);
+    }
+}
+
+fn gifts(board: &Board) -> HashMap<GiftPoint, Gift> {
+    board.gifts.iter()
+        .filter_map(|(&gift_point, gift_cell)| gift_cell.gift.map(|gift| (gift_point, gift)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex;
+
+    #[test]
+    fn the_shipped_effects_cover_every_action() {
+        let effects = parse(include_str!("../resources/sounds.txt")).unwrap();
+        let branch = hex::BranchPoint::new(hex::HexPoint::new(0, -1));
+        let gift_point = hex::GiftPoint::new(hex::HexPoint::new(0, -2));
+        for &action in &[Action::Place(branch), Action::Upgrade(branch), Action::Prune(branch), Action::Moss(gift_point)] {
+            let name = action_effect(action).unwrap();
+            assert!(effects.iter().any(|effect| effect.name == name), "no {} sound", name);
+        }
+        let branch_break = effects.iter().find(|effect| effect.name == "branch_break").unwrap();
+        assert_eq!(branch_break.files.len(), 4);
+        assert_eq!(branch_break.pitch, (0.9, 1.1));
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        assert_eq!(parse("file /a.ogg").err(), Some("line 1: could not parse \"file /a.ogg\"".to_string()));
+        assert_eq!(parse("sound a").err(), Some("sound \"a\" needs a file".to_string()));
+        for line in &["pitch 1.1 0.9", "pitch 0", "pitch fast", "volume 0.5 1.5", "voices 0", "loop yes"] {
+            assert!(parse(&format!("sound a\nfile /a.ogg\n{}", line)).is_err(), "{}", line);
+        }
+        let effects = parse("sound a\nfile /a.ogg\npitch 1.2\nvoices 3").unwrap();
+        assert_eq!((effects[0].pitch, effects[0].volume, effects[0].voices), ((1.2, 1.2), (1.0, 1.0), 3));
+    }
+
+    #[test]
+    fn gifts_are_heard_by_name() {
+        assert_eq!(gift_effect(Gift::Birdnest, true), "bird_nest_appears");
+        assert_eq!(gift_effect(Gift::Squirrel, false), "squirrel_leaves");
+    }
+
+    #[test]
+    fn panning_follows_the_board() {
+        assert_eq!(pan(ORIGIN_X), 0.0);
+        assert_eq!(pan(SIDEBAR_WIDTH), -1.0);
+        assert_eq!(pan(-1000.0), -1.0);
+        assert_eq!(pan(2.0 * ORIGIN_X - SIDEBAR_WIDTH), 1.0);
+    }
+}
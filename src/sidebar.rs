@@ -1,115 +1,250 @@
-
 use ggez::{GameResult, Context};
 use ggez::graphics::*;
+use ggez::timer;
 use glam::f32::Vec2;
 
 use globals::*;
+use layout;
+use locale::Locale;
+use palette::Palette;
 use text;
 
 
 pub const SIDEBAR_WIDTH: f32 = 70.0;
 
+const METER_OFFSET_X:  f32 = 28.0;
+const METER_SPACING_Y: f32 = 14.0;
+const METER_RADIUS:    f32 = 9.0;
+// how quickly the meter catches up with the amount, per second
+const METER_SPEED:     f32 = 6.0;
+// the amounts which get a tick on the side of the meter
+const METER_TICKS: [f32; 3] = [1.0, 10.0, 100.0];
+
+// Which edge of the screen a sidebar sticks to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    Left,
+    Right,
+}
+
+// The shape of the meter's dots, so that the two meters can be told apart
+// without colour.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dot {
+    Circle,
+    Diamond,
+}
+
+// Everything the meter's mesh depends on, to rebuild it only when one of
+// them changes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct MeterKey {
+    x: f32,
+    bottom: f32,
+    num_dots_max: usize,
+    num_dots: usize,
+    num_income_dots: usize,
+    cap: Option<f32>,
+    color: Color,
+    tick_color: Color,
+    cap_color: Color,
+}
+
 #[derive(Debug)]
 pub struct Sidebar {
-    title: Text,
-    color: Color,
-    x: f32,
-    bounty_amount: f32,
-    life_amount: f32,
+    font: Font,
+    // looked up in the current language when drawn
+    title_key: &'static str,
+    edge: Edge,
+    dot: Dot,
+    // the amount can't go above it, e.g. Board::bounty_cap()
+    cap: Option<f32>,
+    amount: f32,
+    // how much the amount will grow at the next turn
+    income: f32,
+    // what the meter currently shows, on its way to `amount`
+    shown: f32,
+    mesh: Option<(MeterKey, Mesh)>,
+}
+
+// The fraction of the meter which an amount fills. The scale is logarithmic,
+// so that the first few points make a visible difference while MAX_BOUNTY
+// still fits.
+pub fn amount_to_fraction(amount: f32) -> f32 {
+    ((1.0 + amount.max(0.0)).ln() / (1.0 + MAX_BOUNTY).ln()).min(1.0)
 }
 
-pub fn amount_to_dots(amount: f32) -> f32 {
-    if amount < THRESHOLD1 {
-        amount
+// e.g. "12" or "3.5"
+pub fn format_amount(amount: f32) -> String {
+    if amount >= 10.0 {
+        format!("{:.0}", amount)
     } else {
-        THRESHOLD1 + (amount - THRESHOLD1).sqrt()
+        format!("{:.1}", amount)
     }
-    /*} else {
-        THRESHOLD1 + (THRESHOLD2 - THRESHOLD1) / 2.0 + (amount - THRESHOLD2) / 4.0
-    }*/
 }
 
 impl Sidebar {
-    pub fn new(font: &Font, title: &'static str, color: Color, x: f32) -> GameResult<Sidebar> {
+    pub fn new(font: &Font, title_key: &'static str, edge: Edge, dot: Dot) -> GameResult<Sidebar> {
         Ok(Sidebar {
-            title: Text::new(
-                TextFragment::new(title)
-                    .font(*font)
-            ),
-            color,
-            x,
-            bounty_amount: 0.0f32,
-            life_amount: 0.0f32,
+            font: *font,
+            title_key,
+            edge,
+            dot,
+            cap: None,
+            amount: 0.0f32,
+            income: 0.0f32,
+            shown: 0.0f32,
+            mesh: None,
         })
     }
 
-    pub fn update(&mut self, _ctx: &mut Context, bounty_amount: f32, life_amount: f32) {
-        self.bounty_amount = bounty_amount;
-        self.life_amount = life_amount;
+    pub fn update(&mut self, ctx: &mut Context, amount: f32, income: f32, cap: Option<f32>) {
+        self.amount = amount;
+        self.cap = cap;
+        self.income = match self.cap {
+            Some(cap) => income.min(cap - amount).max(0.0),
+            None      => income.max(0.0),
+        };
+        let dt = timer::duration_to_f64(timer::delta(ctx)) as f32;
+        self.shown += (self.amount - self.shown) * (1.0 - (-dt * METER_SPEED).exp());
+        if (self.amount - self.shown).abs() < 0.01 {
+            self.shown = self.amount;
+        }
     }
 
-    pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+    fn meter_mesh(&self, ctx: &mut Context, key: MeterKey) -> GameResult<Mesh> {
+        let mut builder = MeshBuilder::new();
+        let income_color = Color::new(key.color.r, key.color.g, key.color.b, 0.35);
+        for i in 0..(key.num_dots + key.num_income_dots).min(key.num_dots_max) {
+            let center = Vec2::new(key.x + METER_OFFSET_X, key.bottom - i as f32 * METER_SPACING_Y);
+            let color = if i < key.num_dots {key.color} else {income_color};
+            match self.dot {
+                Dot::Circle  => {
+                    builder.circle(DrawMode::fill(), center, METER_RADIUS, 2.0, color)?;
+                },
+                Dot::Diamond => {
+                    builder.polygon(
+                        DrawMode::fill(),
+                        &[
+                            center + Vec2::new(0.0, -METER_RADIUS),
+                            center + Vec2::new(METER_RADIUS, 0.0),
+                            center + Vec2::new(0.0, METER_RADIUS),
+                            center + Vec2::new(-METER_RADIUS, 0.0),
+                        ],
+                        color,
+                    )?;
+                },
+            }
+        }
+        let tick_x = key.x + METER_OFFSET_X + METER_RADIUS + 2.0;
+        for &amount in METER_TICKS.iter() {
+            let y = self.amount_to_y(key, amount);
+            builder.line(&[Vec2::new(tick_x, y), Vec2::new(tick_x + 5.0, y)], 1.0, key.tick_color)?;
+        }
+        if let Some(cap) = key.cap {
+            let y = self.amount_to_y(key, cap);
+            builder.line(&[Vec2::new(key.x + 4.0, y), Vec2::new(tick_x + 5.0, y)], 2.0, key.cap_color)?;
+        }
+        builder.build(ctx)
+    }
+
+    // the height at which the meter reaches an amount
+    fn amount_to_y(&self, key: MeterKey, amount: f32) -> f32 {
+        key.bottom - (amount_to_fraction(amount) * key.num_dots_max as f32 - 0.5) * METER_SPACING_Y
+    }
+
+    pub fn draw(&mut self, ctx: &mut Context, palette: &Palette, locale: &Locale, background: Color, meter: Color) -> GameResult<()> {
+        let screen = layout::screen_size(ctx);
+        let x = match self.edge {
+            Edge::Left  => 0.0,
+            Edge::Right => screen.x - SIDEBAR_WIDTH,
+        };
         Mesh::new_rectangle(
             ctx,
             DrawMode::fill(),
-            Rect::new(self.x, 0.0, SIDEBAR_WIDTH, WINDOW_HEIGHT as f32),
-            self.color
+            Rect::new(x, 0.0, SIDEBAR_WIDTH, screen.y),
+            background
         )?.draw(ctx, DrawParam::default())?;
 
-        let center = Vec2::new(
-            self.x + SIDEBAR_WIDTH / 2.0,
-            WINDOW_HEIGHT as f32 - 50.0,
+        let center_x = x + SIDEBAR_WIDTH / 2.0;
+        let title = Text::new(
+            TextFragment::new(locale.get(self.title_key))
+                .font(self.font)
         );
         text::draw_centered_text(
             ctx,
-            &self.title,
-            center,
+            &title,
+            Vec2::new(center_x, screen.y - 50.0),
             0.0,
             DrawParam::default()
-                .color(Color::from_rgb(0, 0, 0))
+                .color(palette.sidebar_title)
         )?;
 
-        let meter_bounty_color = Color::from_rgb(15, 117, 188);
-        let meter_life_color = Color::from_rgb(247, 148, 30);
-        let meter_offset_x = 36.0f32;
-        let meter_spacing_y = 14.0f32;
-        let meter_radius = 9.0f32;
-        let meter_bottom = Vec2::new(
-            self.x + meter_offset_x,
-            WINDOW_HEIGHT as f32 - 100.0,
-        );
-
-        let num_bounty_dots = amount_to_dots(self.bounty_amount.floor()) as usize;
-        //let num_life_dots = (amount_to_dots(self.life_amount + self.bounty_amount) as usize) - num_bounty_dots;
-        let num_life_dots = if self.bounty_amount > 0.0 {0} else
-        {self.life_amount.floor() as usize};
-        // let num_life_dots = (amount_to_dots(self.life_amount + self.bounty_amount) as usize) - num_bounty_dots;
-        let num_dots_max = 34;
-
-        let mut meter_cur = meter_bottom.clone();
-
-        for _ in 0..num_life_dots.min(num_dots_max) {
-            Mesh::new_circle(
+        // the numbers, which the meter can only approximate
+        let capped = self.cap.is_some_and(|cap| self.amount >= cap);
+        text::draw_centered_message(
+            ctx,
+            self.font,
+            &format_amount(self.amount),
+            Vec2::new(center_x, screen.y - 78.0),
+            if capped {palette.warning} else {palette.sidebar_title},
+        )?;
+        if self.income > 0.0 {
+            let income = Text::new(
+                TextFragment::new(locale.format("sidebar.income", &[&format_amount(self.income)]))
+                    .font(self.font)
+                    .scale(PxScale::from(12.0))
+            );
+            text::draw_centered_text(
                 ctx,
-                ggez::graphics::DrawMode::fill(),
-                meter_cur,
-                meter_radius,
-                2.0,
-                meter_life_color
-            )?.draw(ctx, DrawParam::default())?;
-            meter_cur.y = meter_cur.y - meter_spacing_y;
+                &income,
+                Vec2::new(center_x, screen.y - 28.0),
+                0.0,
+                DrawParam::default()
+                    .color(palette.sidebar_title)
+            )?;
+        }
+
+        // as many dots as fit above the numbers, 34 at the original height
+        let bottom = screen.y - 100.0;
+        let num_dots_max = ((bottom - 20.0) / METER_SPACING_Y).max(0.0) as usize;
+        let num_dots = (amount_to_fraction(self.shown) * num_dots_max as f32).round() as usize;
+        let num_income_dots = ((amount_to_fraction(self.shown + self.income) * num_dots_max as f32).round() as usize).saturating_sub(num_dots);
+        let key = MeterKey {
+            x,
+            bottom,
+            num_dots_max,
+            num_dots,
+            num_income_dots,
+            cap: self.cap,
+            color: meter,
+            tick_color: palette.sidebar_title,
+            cap_color: palette.warning,
+        };
+        let stale = match self.mesh {
+            Some((cached_key, _)) => cached_key != key,
+            None                  => true,
+        };
+        if stale {
+            self.mesh = Some((key, self.meter_mesh(ctx, key)?));
+        }
+        if let Some((_, ref mesh)) = self.mesh {
+            mesh.draw(ctx, DrawParam::default())?;
         }
 
-        for _ in num_life_dots..num_bounty_dots.min(num_dots_max) {
-            Mesh::new_circle(
+        // the ticks' amounts, small enough to fit beside the meter
+        for &amount in METER_TICKS.iter().chain(self.cap.iter()) {
+            let label = Text::new(
+                TextFragment::new(format_amount(amount))
+                    .font(self.font)
+                    .scale(PxScale::from(11.0))
+            );
+            label.draw(
                 ctx,
-                ggez::graphics::DrawMode::fill(),
-                meter_cur,
-                meter_radius,
-                2.0,
-                meter_bounty_color
-            )?.draw(ctx, DrawParam::default())?;
-            meter_cur.y = meter_cur.y - meter_spacing_y;
+                DrawParam::default()
+                    .dest(Vec2::new(x + METER_OFFSET_X + METER_RADIUS + 9.0, self.amount_to_y(key, amount) - 6.0))
+                    .color(palette.sidebar_title)
+            )?;
         }
         Ok(())
     }
@@ -0,0 +1,271 @@
+use std::collections::HashSet;
+
+use board::{Action, Board};
+use cell::Gift;
+use goal;
+use level;
+use script::Script;
+use strategy;
+
+
+#[derive(Clone, Debug)]
+pub enum Target {
+    // stop as soon as stats.life_max reaches this level
+    LifeLevel(usize),
+    // stop as soon as the level is won
+    Level(Box<level::Level>),
+    // play for the whole turn budget and keep the highest life reached
+    MaximumLife,
+}
+
+pub struct Options {
+    pub target: Target,
+    pub beam_width: usize,
+    pub max_turns: usize,
+    pub actions_per_turn: usize,
+}
+
+pub struct Solution {
+    pub script: Script,
+    pub turns: usize,
+    pub life_max: usize,
+}
+
+#[derive(Clone)]
+struct Node {
+    board: Board,
+    script: Script,
+}
+
+// What needs to grow nearby before a gift can appear, according to
+// `life_cycle`.
+fn precursors(gift: Gift) -> Vec<Gift> {
+    match gift {
+        Gift::Leaves   => vec!(),
+        Gift::Flowers  => vec!(Gift::Leaves),
+        Gift::Beehive  => vec!(Gift::Flowers),
+        Gift::Berries  => vec!(Gift::Beehive, Gift::Leaves),
+        Gift::Nuts     => vec!(Gift::Flowers, Gift::Leaves),
+        Gift::Birdnest => vec!(Gift::Berries),
+        Gift::Squirrel => vec!(Gift::Nuts),
+    }
+}
+
+// How close the board is to the target, so that the beam favours boards on
+// their way to a rare creature even if they don't produce much life yet.
+fn progress(target: &Target, board: &Board) -> f32 {
+    match *target {
+        Target::Level(ref level) => {
+            let goals: f32 = level.goals.iter()
+                .map(|&goal| match goal {
+                    goal::Goal::Creature(gift, count) => {
+                        let precursor_count: usize = precursors(gift).iter()
+                            .map(|&precursor| board.stats.gift_count(precursor).min(2))
+                            .sum();
+                        10.0 * board.stats.gift_count(gift).min(count) as f32 + precursor_count as f32
+                    },
+                    _ => if goal.reached(board) {10.0} else {0.0},
+                })
+                .sum();
+            // each branch over the limit must be pruned again before winning
+            let excess = level.max_branches
+                .map_or(0, |max_branches| level::branch_count(board).saturating_sub(max_branches));
+            goals - 5.0 * excess as f32
+        },
+        _ => 0.0,
+    }
+}
+
+fn score(target: &Target, board: &Board) -> f32 {
+    progress(target, board) + strategy::evaluate(board) + 0.01 * board.bounty_amount
+}
+
+// Two boards with the same trees, gifts and moss are interchangeable as far
+// as the search is concerned, even if they were reached by different paths.
+fn fingerprint(board: &Board) -> String {
+    let mut branches: Vec<_> = board.branches.iter()
+        .map(|(p, b)| (p.hex_point.q, p.hex_point.r, b.branch_upgrade))
+        .collect();
+    let mut gifts: Vec<_> = board.gifts.iter()
+        .map(|(p, g)| (p.hex_point.q, p.hex_point.r, g.gift.map(|gift| gift.id())))
+        .collect();
+    let mut forbidden: Vec<_> = board.forbidden.iter()
+        .filter(|&(_, &b)| b)
+        .map(|(p, _)| (p.hex_point.q, p.hex_point.r))
+        .collect();
+    branches.sort();
+    gifts.sort();
+    forbidden.sort();
+    format!("{:?}{:?}{:?}{}", branches, gifts, forbidden, (board.bounty_amount * 100.0) as i64)
+}
+
+// Keeps the best `width` nodes, dropping duplicates. Each node is scored
+// once, and a NaN score sorts last instead of panicking.
+fn prune_beam(target: &Target, nodes: Vec<Node>, width: usize) -> Vec<Node> {
+    let mut scored: Vec<(f32, Node)> = nodes.into_iter()
+        .map(|node| (score(target, &node.board), node))
+        .map(|(score, node)| (if score.is_nan() {f32::NEG_INFINITY} else {score}, node))
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut seen = HashSet::new();
+    scored.into_iter()
+        .map(|(_, node)| node)
+        .filter(|node| seen.insert(fingerprint(&node.board)))
+        .take(width)
+        .collect()
+}
+
+fn reached(target: &Target, board: &Board) -> bool {
+    match *target {
+        Target::LifeLevel(level) => board.stats.life_max >= level,
+        Target::Level(ref level) => matches!(level.outcome(board), level::Outcome::Won(_)),
+        Target::MaximumLife      => false,
+    }
+}
+
+// A beam search over the actions available at each turn. Within a turn, the
+// search may play up to `actions_per_turn` actions before the turn ends and
+// `life_cycle` runs.
+pub fn solve(start: &Board, options: &Options) -> Option<Solution> {
+    let mut beam = vec!(Node {
+        board: start.clone(),
+        script: Script::new(),
+    });
+    let mut best: Option<Node> = None;
+
+    for _ in 0..options.max_turns {
+        let mut pool = beam.clone();
+        let mut layer = beam;
+        for _ in 0..options.actions_per_turn {
+            let mut next = Vec::new();
+            for node in layer.iter() {
+                for action in strategy::valid_actions(&node.board) {
+                    let mut child = node.clone();
+                    child.board.apply(action).ok();
+                    child.script.push(node.board.turn, action);
+                    next.push(child);
+                }
+            }
+            layer = prune_beam(&options.target, next, options.beam_width);
+            pool.extend(layer.iter().cloned());
+        }
+
+        beam = prune_beam(&options.target, pool, options.beam_width);
+        for node in beam.iter_mut() {
+            node.board.update_stats();
+            node.board.next_turn();
+            node.board.update_stats();
+        }
+
+        for node in beam.iter() {
+            if reached(&options.target, &node.board) {
+                return Some(to_solution(start, &options.target, node.clone()));
+            }
+            let is_better = match best {
+                None => true,
+                Some(ref best) => node.board.stats.life_max > best.board.stats.life_max,
+            };
+            if is_better {
+                best = Some(node.clone());
+            }
+        }
+    }
+
+    match options.target {
+        Target::MaximumLife => best.map(|node| to_solution(start, &options.target, node)),
+        _                   => None,
+    }
+}
+
+fn to_solution(start: &Board, target: &Target, node: Node) -> Solution {
+    let turns = node.board.turn;
+    let script = without_undone_moss(start, target, node.script, turns);
+    Solution {
+        turns,
+        life_max: replay(start, &script, turns).stats.life_max,
+        script,
+    }
+}
+
+// Drops a moss toggle which undoes the previous one on the same cell during
+// the same turn, or both of them, as long as the plan still does as well.
+fn without_undone_moss(start: &Board, target: &Target, mut script: Script, turns: usize) -> Script {
+    let outcome = |script: &Script| {
+        let board = replay(start, script, turns);
+        (reached(target, &board), board.stats.life_max)
+    };
+    let end = outcome(&script);
+    let mut i = 0;
+    while i + 1 < script.steps.len() {
+        let (a, b) = (script.steps[i], script.steps[i + 1]);
+        if let (Action::Moss(_), true) = (a.action, a == b) {
+            let shorter = [i..i + 2, i + 1..i + 2].iter()
+                .map(|range| {
+                    let mut shorter = script.clone();
+                    shorter.steps.drain(range.clone());
+                    shorter
+                })
+                .find(|shorter| {
+                    let (won, life_max) = outcome(shorter);
+                    won == end.0 && life_max >= end.1
+                });
+            if let Some(shorter) = shorter {
+                script = shorter;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    script
+}
+
+// Replays a script from the given board and returns the board it leads to,
+// e.g. to double-check a solution.
+pub fn replay(start: &Board, script: &Script, turns: usize) -> Board {
+    let mut board = start.clone();
+    while board.turn < turns {
+        script.play_turn(&mut board);
+    }
+    board
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn options(target: Target) -> Options {
+        Options {
+            target,
+            beam_width: 20,
+            max_turns: 40,
+            actions_per_turn: 3,
+        }
+    }
+
+    #[test]
+    fn solves_a_level_with_a_replayable_plan() {
+        let level = level::Level::load(Path::new("resources/levels/01-leaves.txt")).unwrap();
+        let start = level.to_board().unwrap();
+        let solution = solve(&start, &options(Target::Level(Box::new(level.clone())))).unwrap();
+        let board = replay(&start, &solution.script, solution.turns);
+        assert!(matches!(level.outcome(&board), level::Outcome::Won(_)));
+        assert_eq!(board.stats.life_max, solution.life_max);
+    }
+
+    #[test]
+    fn reaches_a_life_level() {
+        let solution = solve(&Board::new(), &options(Target::LifeLevel(2))).unwrap();
+        assert!(replay(&Board::new(), &solution.script, solution.turns).stats.life_max >= 2);
+    }
+
+    #[test]
+    fn plans_never_toggle_moss_back_straight_away() {
+        let level = level::Level::load(Path::new("resources/levels/08-squirrel.txt")).unwrap();
+        let start = level.to_board().unwrap();
+        let solution = solve(&start, &options(Target::Level(Box::new(level)))).unwrap();
+        for pair in solution.script.steps.windows(2) {
+            assert!(!(matches!(pair[0].action, Action::Moss(_)) && pair[0] == pair[1]), "{}", solution.script);
+        }
+    }
+}
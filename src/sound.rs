@@ -0,0 +1,379 @@
+use core::time::Duration;
+use ggez::{GameError, GameResult, Context};
+use ggez::audio::{Source, SoundSource, SpatialSource};
+use std::cell::RefCell;
+use std::fmt;
+use std::io::Write;
+use std::rc::Rc;
+
+use globals::*;
+
+
+// Where the music and the sound effects go. Only Device needs a sound card;
+// the other two let the game run on a machine without one.
+#[derive(Clone, Debug)]
+pub enum Backend {
+    Device,
+    Null,
+    // plays nothing, but writes down every sound and volume change, e.g.
+    // for a test to check that pruning plays a break sound
+    Recording(Rc<RefCell<Recording>>),
+}
+
+// Each sound played and each volume change, one per line, e.g.
+//
+//     12.345 play /branch_break.ogg volume 0.80 pitch 1.05 pan -0.30
+//     12.500 volume /guitar.ogg 0.25
+//
+// with the time in seconds since the game started.
+pub struct Recording {
+    log: Box<dyn Write>,
+}
+
+// What the sounds need from the game: the time, and ggez's audio for the
+// Device backend. The game passes its Context, and a test can pass a clock.
+pub trait Audio {
+    fn now(&self) -> Duration;
+    fn context(&self) -> Option<&Context>;
+    fn context_mut(&mut self) -> Option<&mut Context>;
+}
+
+// What the game needs from a sound, whichever the backend.
+pub trait Sound: fmt::Debug {
+    // restarts it if it was already playing
+    fn play(&mut self, audio: &dyn Audio);
+    fn set_repeat(&mut self, repeat: bool);
+    fn set_volume(&mut self, audio: &dyn Audio, volume: f32);
+    fn set_pitch(&mut self, pitch: f32);
+    // from -1.0 for the left ear to 1.0 for the right one; only for the
+    // sounds which were loaded as panned
+    fn set_pan(&mut self, pan: f32);
+    fn playing(&self) -> bool;
+}
+
+#[derive(Debug)]
+struct PlainSound {
+    source: Source,
+}
+
+#[derive(Debug)]
+struct PannedSound {
+    source: SpatialSource,
+}
+
+#[derive(Debug)]
+struct NullSound;
+
+#[derive(Debug)]
+struct RecordedSound {
+    path: String,
+    recording: Rc<RefCell<Recording>>,
+    volume: f32,
+    pitch: f32,
+    pan: f32,
+}
+
+impl Audio for Context {
+    fn now(&self) -> Duration {
+        get_current_time(self)
+    }
+
+    fn context(&self) -> Option<&Context> {
+        Some(self)
+    }
+
+    fn context_mut(&mut self) -> Option<&mut Context> {
+        Some(self)
+    }
+}
+
+impl fmt::Debug for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Recording")
+    }
+}
+
+impl Recording {
+    pub fn new(log: Box<dyn Write>) -> Recording {
+        Recording {
+            log,
+        }
+    }
+
+    fn write(&mut self, audio: &dyn Audio, line: String) {
+        let time = duration_to_f32(audio.now());
+        writeln!(self.log, "{:.3} {}", time, line).unwrap_or(());
+    }
+}
+
+impl Backend {
+    // A panned sound can then be moved with `set_pan`.
+    pub fn load(&self, audio: &mut dyn Audio, path: &str, panned: bool) -> GameResult<Box<dyn Sound>> {
+        match *self {
+            Backend::Device => {
+                let ctx = audio.context_mut()
+                    .ok_or_else(|| GameError::AudioError("no audio device".to_string()))?;
+                if panned {
+                    Ok(Box::new(PannedSound {
+                        source: SpatialSource::new(ctx, path)?,
+                    }))
+                } else {
+                    Ok(Box::new(PlainSound {
+                        source: Source::new(ctx, path)?,
+                    }))
+                }
+            },
+            Backend::Null => Ok(Box::new(NullSound)),
+            Backend::Recording(ref recording) => Ok(Box::new(RecordedSound {
+                path: path.to_string(),
+                recording: recording.clone(),
+                volume: 1.0,
+                pitch: 1.0,
+                pan: 0.0,
+            })),
+        }
+    }
+}
+
+impl Sound for PlainSound {
+    fn play(&mut self, audio: &dyn Audio) {
+        if let Some(ctx) = audio.context() {
+            self.source.play(ctx).unwrap_or(());
+        }
+    }
+
+    fn set_repeat(&mut self, repeat: bool) {
+        self.source.set_repeat(repeat);
+    }
+
+    fn set_volume(&mut self, _audio: &dyn Audio, volume: f32) {
+        self.source.set_volume(volume);
+    }
+
+    fn set_pitch(&mut self, pitch: f32) {
+        self.source.set_pitch(pitch);
+    }
+
+    fn set_pan(&mut self, _pan: f32) {}
+
+    fn playing(&self) -> bool {
+        self.source.playing()
+    }
+}
+
+impl Sound for PannedSound {
+    fn play(&mut self, audio: &dyn Audio) {
+        if let Some(ctx) = audio.context() {
+            self.source.play(ctx).unwrap_or(());
+        }
+    }
+
+    fn set_repeat(&mut self, repeat: bool) {
+        self.source.set_repeat(repeat);
+    }
+
+    fn set_volume(&mut self, _audio: &dyn Audio, volume: f32) {
+        self.source.set_volume(volume);
+    }
+
+    fn set_pitch(&mut self, pitch: f32) {
+        self.source.set_pitch(pitch);
+    }
+
+    // the ears are at -1.0 and 1.0, so this keeps a little of the sound in
+    // the far ear
+    fn set_pan(&mut self, pan: f32) {
+        self.source.set_position([pan * 0.8, 0.0, 0.0]);
+    }
+
+    fn playing(&self) -> bool {
+        self.source.playing()
+    }
+}
+
+impl Sound for NullSound {
+    fn play(&mut self, _audio: &dyn Audio) {}
+    fn set_repeat(&mut self, _repeat: bool) {}
+    fn set_volume(&mut self, _audio: &dyn Audio, _volume: f32) {}
+    fn set_pitch(&mut self, _pitch: f32) {}
+    fn set_pan(&mut self, _pan: f32) {}
+
+    fn playing(&self) -> bool {
+        false
+    }
+}
+
+impl Sound for RecordedSound {
+    fn play(&mut self, audio: &dyn Audio) {
+        let line = format!(
+            "play {} volume {:.2} pitch {:.2} pan {:.2}",
+            self.path, self.volume, self.pitch, self.pan,
+        );
+        self.recording.borrow_mut().write(audio, line);
+    }
+
+    fn set_repeat(&mut self, _repeat: bool) {}
+
+    // the music sets its volume every frame, so only the changes count
+    fn set_volume(&mut self, audio: &dyn Audio, volume: f32) {
+        if (volume - self.volume).abs() < 0.005 {
+            return;
+        }
+        self.volume = volume;
+        let line = format!("volume {} {:.2}", self.path, volume);
+        self.recording.borrow_mut().write(audio, line);
+    }
+
+    fn set_pitch(&mut self, pitch: f32) {
+        self.pitch = pitch;
+    }
+
+    fn set_pan(&mut self, pan: f32) {
+        self.pan = pan;
+    }
+
+    // as if every sound were over straight away
+    fn playing(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    use board::{Action, Board};
+    use cell::Gift;
+    use hex;
+    use music;
+    use sfx;
+
+    // Moved by hand, with no sound card behind it.
+    struct Clock(Duration);
+
+    impl Audio for Clock {
+        fn now(&self) -> Duration {
+            self.0
+        }
+
+        fn context(&self) -> Option<&Context> {
+            None
+        }
+
+        fn context_mut(&mut self) -> Option<&mut Context> {
+            None
+        }
+    }
+
+    // Where the recording goes, so that it can be read back.
+    #[derive(Clone, Default)]
+    struct Log(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Log {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Log {
+        // (seconds, the rest of the line)
+        fn lines(&self) -> Vec<(f32, String)> {
+            String::from_utf8_lossy(&self.0.borrow())
+                .lines()
+                .map(|line| {
+                    let (time, rest) = line.split_at(line.find(' ').unwrap());
+                    (time.parse().unwrap(), rest.trim().to_string())
+                })
+                .collect()
+        }
+
+        // the volume changes of one file, after the one which silenced it at
+        // the start
+        fn volumes(&self, file: &str) -> Vec<(f32, f32)> {
+            let prefix = format!("volume {} ", file);
+            self.lines().into_iter()
+                .filter_map(|(time, rest)| rest.strip_prefix(&prefix).map(|volume| (time, volume.parse().unwrap())))
+                .skip(1)
+                .collect()
+        }
+    }
+
+    fn recording() -> (Backend, Log) {
+        let log = Log::default();
+        let recording = Recording::new(Box::new(log.clone()));
+        (Backend::Recording(Rc::new(RefCell::new(recording))), log)
+    }
+
+    fn branch(q: i32, r: i32) -> hex::BranchPoint {
+        hex::BranchPoint::new(hex::HexPoint::new(q, r))
+    }
+
+    fn gift(q: i32, r: i32) -> hex::GiftPoint {
+        hex::GiftPoint::new(hex::HexPoint::new(q, r))
+    }
+
+    #[test]
+    fn pruning_plays_a_break_sound() {
+        let (backend, log) = recording();
+        let mut clock = Clock(Duration::from_secs(3));
+        let effects = sfx::parse(include_str!("../resources/sounds.txt")).unwrap();
+        let mut effects = sfx::Sfx::new(&mut clock, &backend, effects);
+
+        let mut board = Board::new();
+        board.bounty_amount = 10.0;
+        board.apply(Action::Place(branch(0, -1))).unwrap();
+        let prune = Action::Prune(branch(0, -1));
+        board.apply(prune).unwrap();
+        effects.play(&clock, sfx::action_effect(prune).unwrap(), branch(0, -1).to_point().x);
+
+        let plays: Vec<(f32, String)> = log.lines().into_iter()
+            .filter(|(_, rest)| rest.starts_with("play "))
+            .collect();
+        assert_eq!(plays.len(), 1, "{:?}", plays);
+        assert_eq!(plays[0].0, 3.0);
+        assert!(plays[0].1.starts_with("play /branch_break"), "{:?}", plays);
+    }
+
+    #[test]
+    fn the_guitar_fades_in_on_the_bar_after_the_first_leaf() {
+        let (backend, log) = recording();
+        let mut clock = Clock(Duration::ZERO);
+        let mix = music::parse(include_str!("../resources/music.txt")).unwrap();
+        let mut mixer = music::Mixer::new(&mut clock, &backend, mix);
+        mixer.play(&clock);
+
+        let mut board = Board::new();
+        board.insert_branch(branch(0, -1), 0).unwrap();
+        board.insert_branch(branch(1, -1), 0).unwrap();
+        mixer.update(&clock, &board);
+        assert_eq!(log.volumes("/guitar.ogg"), vec!());
+
+        // at 120 bpm and 2 beats per bar, the next bar starts at 1s, even
+        // though a second leaf appears in between
+        clock.0 = Duration::from_millis(200);
+        board.set_gift(gift(0, -2), Some(Gift::Leaves));
+        mixer.update(&clock, &board);
+        clock.0 = Duration::from_millis(600);
+        board.set_gift(gift(2, -2), Some(Gift::Leaves));
+        mixer.update(&clock, &board);
+        clock.0 = Duration::from_millis(900);
+        mixer.update(&clock, &board);
+        assert_eq!(log.volumes("/guitar.ogg"), vec!());
+
+        // then it takes the two seconds of its fade to reach 2 leaves out of 4
+        for millis in [2000, 3500] {
+            clock.0 = Duration::from_millis(millis);
+            mixer.update(&clock, &board);
+        }
+        let volumes = log.volumes("/guitar.ogg");
+        assert_eq!(volumes.len(), 2, "{:?}", volumes);
+        assert_eq!(volumes[0].0, 2.0);
+        assert!(volumes[0].1 > 0.0 && volumes[0].1 < 0.5, "{:?}", volumes);
+        assert_eq!(volumes[1], (3.5, 0.5));
+    }
+}
@@ -0,0 +1,200 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+
+use board::{self, Action, Board, Economy};
+use life;
+
+
+// how many actions a bot may take before it has to wait for the next turn
+pub const MAX_ACTIONS_PER_TURN: usize = 10;
+
+pub trait Strategy {
+    fn name(&self) -> &'static str;
+
+    // Called repeatedly during a turn until it returns Action::Wait.
+    fn choose(&mut self, board: &Board) -> Action;
+}
+
+pub fn from_name(name: &str, seed: u64) -> Option<Box<dyn Strategy>> {
+    match name {
+        "greedy"    => Some(Box::new(Greedy)),
+        "random"    => Some(Box::new(Random::new(seed))),
+        "lookahead" => Some(Box::new(Lookahead::new(3))),
+        _           => None,
+    }
+}
+
+// All the actions which the rules would currently accept, except Wait.
+pub fn valid_actions(board: &Board) -> Vec<Action> {
+    board::candidate_actions(board)
+        .into_iter()
+        .filter(|&action| board::validate_action(board, action).is_ok())
+        .collect()
+}
+
+// A rough measure of how promising a board is: its life production, plus a
+// little something for the room it leaves to grow.
+pub fn evaluate(board: &Board) -> f32 {
+    let open_tips = board.gifts.iter()
+        .filter(|&(&gift_point, gift_cell)|
+            gift_cell.gift.is_none()
+            && !*board.forbidden.get(&gift_point).unwrap_or(&false)
+            && board.gift_children(gift_point).is_empty()
+        )
+        .count();
+    let upgrades: usize = board.branches.values()
+        .map(|branch_cell| branch_cell.branch_upgrade)
+        .sum();
+    life::life_production(&board.gifts) + 0.3 * open_tips as f32 + 0.3 * upgrades as f32
+}
+
+// Let the strategy act until it waits, then advance the board by one turn.
+pub fn play_turn(strategy: &mut dyn Strategy, board: &mut Board) -> Vec<Action> {
+    let mut actions = Vec::new();
+    for _ in 0..MAX_ACTIONS_PER_TURN {
+        let action = strategy.choose(board);
+        if action == Action::Wait || board.apply(action).is_err() {
+            break;
+        }
+        actions.push(action);
+    }
+    board.update_stats();
+    board.next_turn();
+    board.update_stats();
+    actions
+}
+
+// Returns the number of turns it took to reach the target life level.
+pub fn run_headless(strategy: &mut dyn Strategy, economy: Economy, target_life: usize, max_turns: usize) -> Option<usize> {
+    let mut board = Board::new();
+    board.economy = economy;
+    while board.turn < max_turns {
+        play_turn(strategy, &mut board);
+        if board.stats.life_max >= target_life {
+            return Some(board.turn);
+        }
+    }
+    None
+}
+
+
+// Picks whichever action improves the evaluation the most right now.
+pub struct Greedy;
+
+impl Strategy for Greedy {
+    fn name(&self) -> &'static str {
+        "greedy"
+    }
+
+    fn choose(&mut self, board: &Board) -> Action {
+        let mut best_action = Action::Wait;
+        let mut best_score = evaluate(board);
+        for action in valid_actions(board) {
+            let mut simulated = board.clone();
+            simulated.apply(action).ok();
+            let score = evaluate(&simulated);
+            if score > best_score {
+                best_action = action;
+                best_score = score;
+            }
+        }
+        best_action
+    }
+}
+
+// Mostly waits, occasionally does something arbitrary. A baseline which the
+// other bots should comfortably beat.
+pub struct Random {
+    rng: StdRng,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Strategy for Random {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn choose(&mut self, board: &Board) -> Action {
+        if self.rng.gen_bool(0.5) {
+            return Action::Wait;
+        }
+        *valid_actions(board)
+            .choose(&mut self.rng)
+            .unwrap_or(&Action::Wait)
+    }
+}
+
+// Simulates `life_cycle` a few turns ahead after each candidate action, and
+// picks the one leading to the best board.
+pub struct Lookahead {
+    depth: usize,
+}
+
+impl Lookahead {
+    pub fn new(depth: usize) -> Lookahead {
+        Lookahead {depth}
+    }
+
+    fn simulate(&self, board: &Board, action: Action) -> f32 {
+        let mut simulated = board.clone();
+        simulated.apply(action).ok();
+        let immediate = evaluate(&simulated);
+        for _ in 0..self.depth {
+            simulated.next_turn();
+        }
+        // the immediate evaluation breaks ties between actions whose benefits
+        // only show up after a few more actions
+        evaluate(&simulated) + 0.1 * immediate
+    }
+}
+
+impl Strategy for Lookahead {
+    fn name(&self) -> &'static str {
+        "lookahead"
+    }
+
+    fn choose(&mut self, board: &Board) -> Action {
+        let mut best_action = Action::Wait;
+        let mut best_score = self.simulate(board, Action::Wait);
+        for action in valid_actions(board) {
+            let score = self.simulate(board, action);
+            if score > best_score {
+                best_action = action;
+                best_score = score;
+            }
+        }
+        best_action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strategies_only_play_valid_actions() {
+        for name in &["greedy", "random", "lookahead"] {
+            let mut strategy = from_name(name, 0).unwrap();
+            let mut board = Board::new();
+            for _ in 0..20 {
+                for _ in 0..MAX_ACTIONS_PER_TURN {
+                    let action = strategy.choose(&board);
+                    if action == Action::Wait {
+                        break;
+                    }
+                    assert_eq!(board::validate_action(&board, action).map(|_| ()), Ok(()), "{} played {:?}", name, action);
+                    board.apply(action).unwrap();
+                }
+                board.next_turn();
+            }
+        }
+    }
+}
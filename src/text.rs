@@ -1,5 +1,5 @@
 use ggez::{GameResult, Context};
-use ggez::graphics::{DrawParam, Text};
+use ggez::graphics::{Color, DrawParam, Font, Text, TextFragment};
 use glam::f32::Vec2;
 
 use center::draw_centered;
@@ -19,3 +19,17 @@ pub fn draw_centered_text(
     let size = text_size(ctx, text);
     draw_centered(ctx, text, size, dest, rotation, draw_param)
 }
+
+pub fn draw_centered_message(
+    ctx: &mut Context,
+    font: Font,
+    message: &str,
+    dest: Vec2,
+    color: Color
+) -> GameResult<()> {
+    let text = Text::new(
+        TextFragment::new(message)
+            .font(font)
+    );
+    draw_centered_text(ctx, &text, dest, 0.0, DrawParam::default().color(color))
+}
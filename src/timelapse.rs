@@ -0,0 +1,145 @@
+use ggez::graphics::Color;
+use glam::f32::Vec2;
+
+use board::Board;
+use cell;
+use hex::{BranchPoint, HEX_HEIGHT, HEX_RADIUS, VISIBLE_HEX_RADIUS};
+use palette::{Marker, Palette};
+use raster::Canvas;
+
+
+pub const GRAPH_HEIGHT: f32 = 60.0;
+
+// From the twig to the trunk, in board units.
+const BRANCH_THICKNESS: [f32; 4] = [3.0, 5.0, 7.0, 10.0];
+
+
+// The two ends of a branch, on the gift points on either side of it.
+fn branch_ends(branch_point: BranchPoint) -> (Vec2, Vec2) {
+    let angle = cell::branch_angle(branch_point);
+    let half = Vec2::new(angle.sin(), -angle.cos()) * HEX_HEIGHT;
+    let point = branch_point.to_point();
+    (point - half, point + half)
+}
+
+// One frame per board, e.g. one per turn of a replay, all framed alike so
+// that the tree grows in place. The optional graph strip underneath traces
+// the two sidebar amounts up to each frame's turn.
+pub struct Timelapse<'a> {
+    boards: &'a [Board],
+    palette: &'a Palette,
+    // pixels per board unit
+    scale: f32,
+    graph: bool,
+    // the top-left corner, in board units
+    min: Vec2,
+    pub width: usize,
+    pub height: usize,
+    // the highest amount, so that the graph keeps the same scale throughout
+    top: f32,
+}
+
+impl<'a> Timelapse<'a> {
+    pub fn new(boards: &'a [Board], palette: &'a Palette, scale: f32, graph: bool) -> Timelapse<'a> {
+        let points: Vec<Vec2> = boards.iter()
+            .flat_map(|board| board.branches.keys().flat_map(|&branch_point| {
+                let (a, b) = branch_ends(branch_point);
+                vec!(a, b)
+            }))
+            .collect();
+        let first = points.first().cloned().unwrap_or(Vec2::ZERO);
+        let min = points.iter().fold(first, |min, &point| min.min(point)) - Vec2::splat(VISIBLE_HEX_RADIUS);
+        let max = points.iter().fold(first, |max, &point| max.max(point)) + Vec2::splat(VISIBLE_HEX_RADIUS);
+        let graph_height = if graph {GRAPH_HEIGHT} else {0.0};
+        Timelapse {
+            boards,
+            palette,
+            scale,
+            graph,
+            min,
+            width: ((max.x - min.x) * scale).ceil() as usize,
+            height: ((max.y - min.y + graph_height) * scale).ceil() as usize,
+            top: boards.iter()
+                .map(|board| board.bounty_amount.max(board.life_amount))
+                .fold(1.0, f32::max),
+        }
+    }
+
+    fn to_canvas(&self, point: Vec2) -> Vec2 {
+        (point - self.min) * self.scale
+    }
+
+    // where the graph strip puts an amount at the `i`th frame
+    fn graph_point(&self, i: usize, amount: f32) -> Vec2 {
+        let x = self.width as f32 * i as f32 / (self.boards.len().max(2) - 1) as f32;
+        let y = self.height as f32 - (2.0 + amount.max(0.0) / self.top * (GRAPH_HEIGHT - 4.0)) * self.scale;
+        Vec2::new(x, y)
+    }
+
+    pub fn frame(&self, i: usize) -> Canvas {
+        let board = &self.boards[i];
+        let mut canvas = Canvas::new(self.width, self.height, Color::from_rgb(235, 240, 225));
+
+        // sorted, so that the overlaps look the same from one frame to the next
+        let mut branches: Vec<_> = board.branches.iter().collect();
+        branches.sort_by_key(|&(&branch_point, _)| branch_point);
+        for (&branch_point, branch_cell) in branches {
+            let (a, b) = branch_ends(branch_point);
+            // the game tints the branches black
+            let thickness = BRANCH_THICKNESS[branch_cell.branch_upgrade] * self.scale;
+            canvas.line(self.to_canvas(a), self.to_canvas(b), thickness, Color::BLACK);
+        }
+        let mut gifts: Vec<_> = board.gifts.iter()
+            .filter_map(|(&gift_point, gift_cell)| gift_cell.gift.map(|gift| (gift_point, gift)))
+            .collect();
+        gifts.sort_by_key(|&(gift_point, _)| gift_point);
+        // the game's images can't be used without a window, so each gift is a
+        // disc in its colour with its marker from the graphs on top
+        for (gift_point, gift) in gifts {
+            let center = self.to_canvas(gift_point.to_point());
+            let radius = HEX_RADIUS * 0.8 * self.scale;
+            canvas.circle(center, radius, self.palette.gift(gift));
+            draw_marker(&mut canvas, Marker::for_gift(gift), center, radius * 0.5, 1.5 * self.scale, Color::BLACK);
+        }
+        for (&gift_point, &forbidden) in board.forbidden.iter() {
+            if forbidden && board.gift_children(gift_point).is_empty() {
+                canvas.circle(self.to_canvas(gift_point.to_point()), HEX_RADIUS * 0.6 * self.scale, self.palette.moss);
+            }
+        }
+
+        if self.graph {
+            canvas.rectangle(
+                Vec2::new(0.0, self.height as f32 - GRAPH_HEIGHT * self.scale),
+                Vec2::new(self.width as f32, self.height as f32),
+                Color::from_rgb(60, 60, 60),
+            );
+            for j in 1..=i {
+                let (before, after) = (&self.boards[j - 1], &self.boards[j]);
+                let thickness = 2.0 * self.scale;
+                canvas.line(self.graph_point(j - 1, before.bounty_amount), self.graph_point(j, after.bounty_amount), thickness, self.palette.meter_bounty);
+                canvas.line(self.graph_point(j - 1, before.life_amount), self.graph_point(j, after.life_amount), thickness, self.palette.meter_life);
+            }
+            // the markers of the statistics screen, a dozen at most, with the
+            // second line's halfway between the first's
+            let step = self.boards.len().div_ceil(12).max(2);
+            for (j, board) in self.boards[..=i].iter().enumerate() {
+                let (radius, thickness) = (3.0 * self.scale, 1.5 * self.scale);
+                if j % step == 0 {
+                    draw_marker(&mut canvas, Marker::Diamond, self.graph_point(j, board.bounty_amount), radius, thickness, self.palette.meter_bounty);
+                }
+                if j % step == step / 2 {
+                    draw_marker(&mut canvas, Marker::Circle, self.graph_point(j, board.life_amount), radius, thickness, self.palette.meter_life);
+                }
+            }
+        }
+        canvas
+    }
+}
+
+fn draw_marker(canvas: &mut Canvas, marker: Marker, center: Vec2, radius: f32, thickness: f32, color: Color) {
+    for stroke in marker.strokes() {
+        for segment in stroke.windows(2) {
+            canvas.line(center + segment[0] * radius, center + segment[1] * radius, thickness, color);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use ggez::{GameResult, Context};
+use ggez::graphics::*;
+use glam::f32::Vec2;
+
+use bindings::{Bindings, Command};
+use board::{self, Action, ActionError, ActionKind, Board, Cost};
+use hex;
+use layout::{self, Layout};
+use locale::Locale;
+use palette::Palette;
+use sidebar::{self, SIDEBAR_WIDTH};
+use text;
+
+
+pub const TOOLBAR_TOP:     f32 = 40.0;
+pub const TOOLBAR_SPACING: f32 = 20.0;
+pub const TOOLBAR_WIDTH:   f32 = 140.0;
+
+const TARGET_RADIUS: f32 = 6.0;
+
+
+// The build menu, as in the design proposal: pick an action, then click where
+// it should happen, instead of letting the click guess from what's there.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Toolbar {
+    // None guesses, as before
+    pub tool: Option<ActionKind>,
+}
+
+// in the order of the toolbar
+pub fn tools() -> Vec<(ActionKind, Command)> {
+    vec!(
+        (ActionKind::Place,   Command::ToolBranch),
+        (ActionKind::Upgrade, Command::ToolUpgrade),
+        (ActionKind::Prune,   Command::ToolPrune),
+        (ActionKind::Moss,    Command::ToolMoss),
+    )
+}
+
+// What a tool does to the clicked point, if it applies to that kind of point.
+pub fn tool_action(tool: ActionKind, in_bounds_point: hex::InBoundsPoint) -> Option<Action> {
+    match (tool, in_bounds_point) {
+        (ActionKind::Place, hex::InBoundsPoint::BranchPoint(branch_point))   => Some(Action::Place(branch_point)),
+        (ActionKind::Upgrade, hex::InBoundsPoint::BranchPoint(branch_point)) => Some(Action::Upgrade(branch_point)),
+        (ActionKind::Prune, hex::InBoundsPoint::BranchPoint(branch_point))   => Some(Action::Prune(branch_point)),
+        (ActionKind::Moss, hex::InBoundsPoint::GiftPoint(gift_point))        => Some(Action::Moss(gift_point)),
+        _                                                                    => None,
+    }
+}
+
+impl Toolbar {
+    pub fn new() -> Toolbar {
+        Toolbar {
+            tool: None,
+        }
+    }
+
+    // Picking the active tool again puts it back.
+    pub fn select(&mut self, tool: ActionKind) {
+        self.tool = if self.tool == Some(tool) {None} else {Some(tool)};
+    }
+
+    // Everything the active tool could be used on, with the rules' verdict,
+    // as the click handler would get it.
+    pub fn targets(&self, board: &Board) -> Vec<(Action, Result<Cost, ActionError>)> {
+        let tool = match self.tool {
+            Some(tool) => tool,
+            None       => return Vec::new(),
+        };
+        board::candidate_actions(board)
+            .into_iter()
+            .filter(|&action| action.kind() == Some(tool))
+            .filter(|&action| match action {
+                // not the slots off the edge of the board
+                Action::Place(branch_point) => board.shape.contains(branch_point.hex_point),
+                _                           => true,
+            })
+            .map(|action| (action, board::validate_action(board, action)))
+            .collect()
+    }
+
+    pub fn draw(&self, ctx: &mut Context, font: Font, palette: &Palette, locale: &Locale, bindings: &Bindings, board: &Board) -> GameResult<()> {
+        let screen = layout::screen_size(ctx);
+        let tools = tools();
+        let x = screen.x - SIDEBAR_WIDTH - TOOLBAR_WIDTH;
+        Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(x, TOOLBAR_TOP - TOOLBAR_SPACING, TOOLBAR_WIDTH, (tools.len() + 1) as f32 * TOOLBAR_SPACING),
+            Color::new(0.0, 0.0, 0.0, 0.6)
+        )?.draw(ctx, DrawParam::default())?;
+        for (i, &(tool, command)) in tools.iter().enumerate() {
+            let color = if board.locked.contains(&tool) {
+                palette.disabled
+            } else if self.tool == Some(tool) {
+                palette.accent
+            } else {
+                palette.text
+            };
+            text::draw_centered_message(
+                ctx,
+                font,
+                &format!("{}  {}", bindings.describe(locale, command), locale.get(&format!("tool.{}", tool.name()))),
+                Vec2::new(x + TOOLBAR_WIDTH / 2.0, TOOLBAR_TOP + i as f32 * TOOLBAR_SPACING),
+                color,
+            )?;
+        }
+        Ok(())
+    }
+
+    // A dot on each target, bright with its cost if the tool can be used
+    // there right now, dimmed if not.
+    pub fn draw_targets(&self, ctx: &mut Context, font: Font, palette: &Palette, layout: &Layout, board: &Board) -> GameResult<()> {
+        let targets = self.targets(board);
+        if targets.is_empty() {
+            return Ok(());
+        }
+        let dimmed = Color::new(palette.disabled.r, palette.disabled.g, palette.disabled.b, 0.5);
+        let mut builder = MeshBuilder::new();
+        let mut labels: Vec<(Vec2, Cost, Color)> = Vec::new();
+        for &(action, result) in targets.iter() {
+            let center = match action.to_point() {
+                Some(point) => layout.board_to_screen(point),
+                None        => continue,
+            };
+            match result {
+                Ok(cost) => {
+                    builder.circle(DrawMode::fill(), center, TARGET_RADIUS, 1.0, palette.accent)?;
+                    if cost > 0.0 {
+                        labels.push((center, cost, palette.text));
+                    }
+                },
+                Err(ActionError::NotEnoughBounty {need, ..}) => {
+                    builder.circle(DrawMode::fill(), center, TARGET_RADIUS, 1.0, dimmed)?;
+                    labels.push((center, need, palette.disabled));
+                },
+                Err(_) => {
+                    builder.circle(DrawMode::fill(), center, TARGET_RADIUS * 0.6, 1.0, dimmed)?;
+                },
+            }
+        }
+        builder.build(ctx)?.draw(ctx, DrawParam::default())?;
+        for (center, cost, color) in labels {
+            let label = Text::new(
+                TextFragment::new(sidebar::format_amount(cost))
+                    .font(font)
+                    .scale(PxScale::from(12.0))
+            );
+            text::draw_centered_text(
+                ctx,
+                &label,
+                center + Vec2::new(0.0, -TARGET_RADIUS - 8.0),
+                0.0,
+                DrawParam::default()
+                    .color(color)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub fn toolbar_tool_at(point: Vec2, screen: Vec2) -> Option<ActionKind> {
+    let x = screen.x - SIDEBAR_WIDTH - TOOLBAR_WIDTH;
+    if point.x < x || point.x > x + TOOLBAR_WIDTH {
+        return None;
+    }
+    let tools = tools();
+    let row = ((point.y - TOOLBAR_TOP) / TOOLBAR_SPACING + 0.5).floor();
+    if row >= 0.0 && (row as usize) < tools.len() {
+        Some(tools[row as usize].0)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picking_the_active_tool_again_puts_it_back() {
+        let mut toolbar = Toolbar::new();
+        assert_eq!(toolbar.tool, None);
+        toolbar.select(ActionKind::Prune);
+        assert_eq!(toolbar.tool, Some(ActionKind::Prune));
+        toolbar.select(ActionKind::Moss);
+        assert_eq!(toolbar.tool, Some(ActionKind::Moss));
+        toolbar.select(ActionKind::Moss);
+        assert_eq!(toolbar.tool, None);
+    }
+
+    #[test]
+    fn tools_apply_to_their_kind_of_point() {
+        let branch_point = hex::BranchPoint::new(hex::HexPoint::new(0, 1));
+        let gift_point = hex::GiftPoint::new(hex::HexPoint::new(0, 0));
+        let on_branch = hex::InBoundsPoint::BranchPoint(branch_point);
+        let on_gift = hex::InBoundsPoint::GiftPoint(gift_point);
+        assert_eq!(tool_action(ActionKind::Place, on_branch), Some(Action::Place(branch_point)));
+        assert_eq!(tool_action(ActionKind::Upgrade, on_branch), Some(Action::Upgrade(branch_point)));
+        assert_eq!(tool_action(ActionKind::Prune, on_branch), Some(Action::Prune(branch_point)));
+        assert_eq!(tool_action(ActionKind::Moss, on_gift), Some(Action::Moss(gift_point)));
+        assert_eq!(tool_action(ActionKind::Moss, on_branch), None);
+        for &(tool, _) in tools().iter().filter(|&&(tool, _)| tool != ActionKind::Moss) {
+            assert_eq!(tool_action(tool, on_gift), None);
+        }
+    }
+
+    #[test]
+    fn targets_follow_the_active_tool() {
+        let mut board = Board::new();
+        let mut toolbar = Toolbar::new();
+        assert!(toolbar.targets(&board).is_empty());
+
+        for &(tool, _) in tools().iter() {
+            toolbar.tool = Some(tool);
+            for (action, _) in toolbar.targets(&board) {
+                assert_eq!(action.kind(), Some(tool));
+            }
+        }
+
+        toolbar.tool = Some(ActionKind::Place);
+        let targets = toolbar.targets(&board);
+        assert!(!targets.is_empty());
+        assert!(targets.iter().any(|&(_, result)| result.is_ok()));
+
+        board.locked.push(ActionKind::Place);
+        assert!(toolbar.targets(&board).iter().all(|&(_, result)| result.is_err()));
+    }
+
+    #[test]
+    fn rows_pick_tools_in_order() {
+        let screen = Vec2::new(800.0, 600.0);
+        let x = screen.x - SIDEBAR_WIDTH - TOOLBAR_WIDTH / 2.0;
+        for (i, &(tool, _)) in tools().iter().enumerate() {
+            let y = TOOLBAR_TOP + i as f32 * TOOLBAR_SPACING;
+            assert_eq!(toolbar_tool_at(Vec2::new(x, y), screen), Some(tool));
+        }
+        let below = TOOLBAR_TOP + tools().len() as f32 * TOOLBAR_SPACING;
+        assert_eq!(toolbar_tool_at(Vec2::new(x, below), screen), None);
+        assert_eq!(toolbar_tool_at(Vec2::new(x, TOOLBAR_TOP - TOOLBAR_SPACING), screen), None);
+        assert_eq!(toolbar_tool_at(Vec2::new(10.0, TOOLBAR_TOP), screen), None);
+    }
+}